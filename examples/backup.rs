@@ -0,0 +1,30 @@
+//! Back up a directory into a compressed archive, skipping build output.
+//!
+//! ```text
+//! cargo run --example backup --features gzip -- <dir> <archive.tar.gz>
+//! ```
+
+use std::env;
+use std::process;
+
+use minitar::prelude::*;
+
+fn main() -> Result<(), TarError> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: backup <dir> <archive>");
+        process::exit(1);
+    }
+
+    let options = ArchiveOptions::default()
+        .exclude("target".to_string())
+        .exclude("*.tmp".to_string());
+    minitar::create(args[0].clone(), args[1].clone(), &options)?;
+
+    let count = match Compression::from_path(&args[1]) {
+        Compression::None => TarFile::open(args[1].clone())?.list().len(),
+        _ => return Ok(()),
+    };
+    println!("{}: {} entries", args[1], count);
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! Extract only the entries matching glob patterns, like ``tar -xf archive.tar --wildcards 'src/*'``.
+//!
+//! ```text
+//! cargo run --example extract_selected -- <archive> <dest> <pattern>...
+//! ```
+
+use std::env;
+use std::process;
+
+use minitar::prelude::*;
+
+fn main() -> Result<(), TarError> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 3 {
+        eprintln!("usage: extract_selected <archive> <dest> <pattern>...");
+        process::exit(1);
+    }
+
+    let patterns = args[2..]
+        .iter()
+        .map(|p| glob::Pattern::new(p).map_err(|e| TarError::Pattern(e.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut data = TarFile::open(args[0].clone())?;
+    data.retain(|entry| patterns.iter().any(|p| p.matches(&entry.name)));
+    for entry in data.list() {
+        println!("{}", entry.name);
+    }
+    let stats = data.extract_with(args[1].clone(), &ExtractOptions::default())?;
+    for warning in &stats.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    eprintln!("{}", stats);
+    Ok(())
+}
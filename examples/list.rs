@@ -0,0 +1,70 @@
+//! List the entries of an archive like ``tar -tvf``, optionally filtered by type and size. With
+//! ``--summary``, print counts by type and extension and the largest entries instead, and with
+//! ``--manifest``, print a ``sha256sum`` style digest of each file.
+//!
+//! ```text
+//! cargo run --example list -- <archive> [--type file|dir|link] [--min-size bytes] [--summary top]
+//!     [--manifest sha256|sha512|blake3]
+//! ```
+
+use std::env;
+use std::process;
+
+use minitar::prelude::*;
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: list <archive> [--type file|dir|link] [--min-size bytes] [--summary top] \
+         [--manifest sha256|sha512|blake3]"
+    );
+    process::exit(1);
+}
+
+fn main() -> Result<(), TarError> {
+    let mut args = env::args().skip(1);
+    let archive = args.next().unwrap_or_else(|| usage());
+
+    let mut types: Option<Vec<FileType>> = None;
+    let mut min_size = 0;
+    let mut summary = None;
+    let mut manifest = None;
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--type", Some(t)) => {
+                types = Some(match t.as_str() {
+                    "file" => vec![FileType::Normal, FileType::GnuSparse],
+                    "dir" => vec![FileType::Dir],
+                    "link" => vec![FileType::Sym, FileType::Hard],
+                    _ => usage(),
+                })
+            }
+            ("--min-size", Some(n)) => min_size = n.parse()?,
+            ("--summary", Some(n)) => summary = Some(n.parse()?),
+            ("--manifest", Some(a)) => {
+                manifest = Some(HashAlgorithm::from_name(&a).unwrap_or_else(|| usage()))
+            }
+            _ => usage(),
+        }
+    }
+
+    let data = TarFile::open(archive)?;
+    if let Some(top) = summary {
+        print!("{}", data.stats(top));
+        return Ok(());
+    }
+    if let Some(algorithm) = manifest {
+        print!("{}", data.manifest(algorithm)?);
+        return Ok(());
+    }
+
+    for entry in data.list() {
+        let type_matches = match &types {
+            Some(t) => t.contains(&entry.file_type),
+            None => true,
+        };
+        if type_matches && entry.size >= min_size {
+            println!("{}", entry);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+//! Copy an archive with a directory renamed, such as moving ``pkg-1.2.3/`` to ``pkg/``, and compare the
+//! two.
+//!
+//! ```text
+//! cargo run --example repack -- <in.tar> <out.tar> <from-dir> <to-dir>
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::process;
+
+use minitar::prelude::*;
+
+fn main() -> Result<(), TarError> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 4 {
+        eprintln!("usage: repack <in.tar> <out.tar> <from-dir> <to-dir>");
+        process::exit(1);
+    }
+
+    let old = TarFile::open(args[0].clone())?;
+    let mut new = old.clone();
+    new.transform(&Transform::replace_dir(args[2].clone(), args[3].clone()))?;
+    new.clone().write(&File::create(&args[1])?)?;
+
+    for change in old.diff(&new)? {
+        println!("{:?}", change);
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Stream an archive over TCP without staging it on disk. The sender writes entries as it reads them and the
+//! receiver parses the archive straight off the socket.
+//!
+//! ```text
+//! cargo run --example tcp_stream -- [file]...
+//! ```
+
+use std::env;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use minitar::prelude::*;
+
+fn main() -> Result<(), TarError> {
+    let mut files: Vec<String> = env::args().skip(1).collect();
+    if files.is_empty() {
+        files = vec!["Cargo.toml".to_string(), "README.md".to_string()];
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let sender = thread::spawn(move || -> Result<(), TarError> {
+        let stream = TcpStream::connect(addr)?;
+        let mut builder = TarBuilder::new(stream);
+        for file in files {
+            builder.append_file(file)?;
+        }
+        builder.finish()?;
+        Ok(())
+    });
+
+    /* Give up on a sender that stalls rather than waiting for it forever */
+    let (stream, peer) = listener.accept()?;
+    let options = ReadOptions::default().timeout(Duration::from_secs(10));
+    let data = TarFile::open_tcp(stream, &options)?;
+    sender.join().expect("sender panicked")?;
+
+    println!("received from {}:", peer);
+    for entry in data.list() {
+        println!("{}", entry);
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Create, extract and list tar archives, with the compression picked from the archive extension.
+//!
+//! ```text
+//! minitar create <archive> <path>
+//! minitar extract <archive> <dir> [--strip-components count]
+//! minitar list <archive>
+//! ```
+
+use std::env;
+use std::process;
+
+use minitar::prelude::*;
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: minitar create <archive> <path>\n       \
+         minitar extract <archive> <dir> [--strip-components count]\n       \
+         minitar list <archive>"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("minitar: {}", e);
+        process::exit(2);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), TarError> {
+    match args {
+        [cmd, archive, path] if cmd == "create" => {
+            minitar::create(path.clone(), archive.clone(), &ArchiveOptions::default())
+        }
+        [cmd, archive, dir, rest @ ..] if cmd == "extract" => {
+            let options = match rest {
+                [] => ExtractOptions::default(),
+                [flag, count] if flag == "--strip-components" => {
+                    ExtractOptions::default().strip_components(count.parse()?)
+                }
+                _ => usage(),
+            };
+            minitar::extract(archive.clone(), dir.clone(), &options)
+        }
+        [cmd, archive] if cmd == "list" => {
+            if Compression::from_path(archive) != Compression::None {
+                eprintln!("minitar: only uncompressed archives can be listed");
+                process::exit(1);
+            }
+            for entry in TarFile::open(archive.clone())?.list() {
+                println!("{}", entry);
+            }
+            Ok(())
+        }
+        _ => usage(),
+    }
+}
@@ -0,0 +1,133 @@
+use std::io;
+use std::num::ParseIntError;
+use std::str::Utf8Error;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TarError {
+    #[error("DekuError: {0}")]
+    Deku(#[from] deku::DekuError),
+    #[error("IoError: {0}")]
+    Io(#[from] io::Error),
+    #[error("Error in conversion of oct_to_dev")]
+    Utf8Error(#[from] Utf8Error),
+    #[error("Error in conversion of oct_to_dev")]
+    ParseIntError(#[from] ParseIntError),
+    #[error("End of tar")]
+    EndOfTar,
+    #[error("Truncated tar")]
+    Truncated,
+    #[error("Timed out reading the archive")]
+    Timeout,
+    #[error("Operation cancelled")]
+    Cancelled,
+    #[error("Invalid magic")]
+    InvalidMagic,
+    #[error("Invalid Checksum")]
+    InvalidChecksum,
+    #[error("Invalid PAX extended header")]
+    InvalidPax,
+    #[error("File name too long")]
+    NameTooLong,
+    #[error("Value too large for header field")]
+    FieldOverflow,
+    #[error("Invalid sparse map")]
+    InvalidSparseMap,
+    #[error("Entry not found: {0}")]
+    NotFound(String),
+    #[error("Link {name} refers to {target}, which was not extracted")]
+    MissingLinkTarget { name: String, target: String },
+    #[error("Link {name} points outside the destination to {target}")]
+    UnsafeLink { name: String, target: String },
+    #[error("Special file not allowed: {0}")]
+    SpecialFile(String),
+    #[error("Unsafe path in archive: {0}")]
+    UnsafePath(String),
+    #[error("Can't patch {0} in place")]
+    NotPatchable(String),
+    #[error("Duplicate entry: {0}")]
+    DuplicateEntry(String),
+    #[error("Entry {0} is missing data")]
+    IncompleteEntry(String),
+    #[error("Entry {name} declares {size} bytes but holds {blocks} data blocks")]
+    SizeMismatch {
+        name: String,
+        size: usize,
+        blocks: usize,
+    },
+    #[error("Entry {0} can't be represented in the requested format")]
+    Unrepresentable(String),
+    #[error("Entry {name} is not allowed: {reason}")]
+    Disallowed { name: String, reason: String },
+    #[error("Entry {0} is not signed")]
+    Unsigned(String),
+    #[error("Signature of entry {0} is not valid")]
+    InvalidSignature(String),
+    #[error("Support for {0} compression was not enabled")]
+    UnsupportedCompression(String),
+    #[error("Archive expands to more than {0} times its compressed size")]
+    CompressionRatio(u64),
+    #[error("Archive was compressed with zstd dictionary {0}, which was not supplied")]
+    MissingDictionary(u32),
+    #[error("Invalid pattern: {0}")]
+    Pattern(String),
+}
+
+/// Problems that did not stop an operation, but that the caller may want to know about.
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum TarWarning {
+    #[error("Name {name} was stored as {stored}")]
+    NameTruncated { name: String, stored: String },
+    #[error("Removed leading / or .. from {name}, stored as {stored}")]
+    NameSanitized { name: String, stored: String },
+    #[error("Skipped special file {0}")]
+    SpecialSkipped(String),
+    #[error("{0}: socket ignored")]
+    SocketSkipped(String),
+    #[error("Added {0} again, the archive holds duplicate entries")]
+    DuplicateName(String),
+    #[error("{name} stored as a hard link to {target}")]
+    HardLinked { name: String, target: String },
+    #[error("{0}: file system loop detected, not descending")]
+    FilesystemLoop(String),
+    #[error("{name}: extended attribute {attr} not restored")]
+    XattrSkipped { name: String, attr: String },
+    #[error("{name}: empty {field} field read as 0")]
+    EmptyField { name: String, field: String },
+    #[error("{name}: owner {uid}:{gid} not restored")]
+    OwnerNotRestored { name: String, uid: u32, gid: u32 },
+}
+
+impl TarWarning {
+    /// Returns the lowest `Verbosity` at which the warning is reported.
+    pub fn verbosity(&self) -> Verbosity {
+        match self {
+            TarWarning::NameTruncated { .. }
+            | TarWarning::SpecialSkipped(_)
+            | TarWarning::SocketSkipped(_)
+            | TarWarning::FilesystemLoop(_) => Verbosity::Quiet,
+            TarWarning::NameSanitized { .. }
+            | TarWarning::DuplicateName(_)
+            | TarWarning::XattrSkipped { .. }
+            | TarWarning::EmptyField { .. }
+            | TarWarning::OwnerNotRestored { .. } => Verbosity::Normal,
+            TarWarning::HardLinked { .. } => Verbosity::Verbose,
+        }
+    }
+}
+
+/// How much is reported through `TarWarning`s while adding files to an archive or extracting it. Each level
+/// includes the warnings of the levels below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Report nothing.
+    Silent,
+    /// Report only files or names that could not be stored as they are on disk: truncated names and
+    /// skipped special files and sockets.
+    Quiet,
+    /// Also report names that were rewritten or added more than once. This is the default.
+    Normal,
+    /// Also report routine events, such as files stored as hard links.
+    Verbose,
+}
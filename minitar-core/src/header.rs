@@ -0,0 +1,381 @@
+/* The code generated by ``deku`` for ``TarHeader`` trips this lint */
+#![allow(clippy::manual_div_ceil)]
+
+use std::convert::TryFrom;
+use std::str;
+
+use deku::prelude::*;
+
+use crate::error::TarError;
+
+/// Represents the different types of files that can be encoded in a tar file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum FileType {
+    Normal = 0x30,
+    Hard = 0x31,
+    Sym = 0x32,
+    Char = 0x33,
+    Block = 0x34,
+    Dir = 0x35,
+    FIFO = 0x36,
+    GnuSparse = 0x53,
+    GnuLongName = 0x4c,
+    GnuLongLink = 0x4b,
+    Pax = 0x78,
+    PaxGlobal = 0x67,
+    Unknown = 0x00,
+}
+
+/// Contains the representation of a Tar file header.
+#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, PartialEq)]
+#[deku(endian = "little")]
+pub struct TarHeader {
+    /// The entry name, or its last part when the prefix field is used.
+    pub file_name: [u8; 100],
+    /// The permission bits, in octal.
+    pub file_mode: [u8; 8],
+    /// The numeric user id of the owner, in octal or base-256.
+    pub own_user: [u8; 8],
+    /// The numeric group id of the owner, in octal or base-256.
+    pub own_group: [u8; 8],
+    /// The size of the entry data in bytes, in octal or base-256.
+    pub file_size: [u8; 12],
+    /// The modification time in seconds since the epoch, in octal, or base-256 for times before 1970 or
+    /// too late for octal digits.
+    pub mod_time: [u8; 12],
+    /// The sum of the header bytes, counting this field as spaces.
+    pub header_checksum: [u8; 8],
+    /// The type flag of the entry, a `FileType`.
+    pub link_indicator: [u8; 1],
+    /// The target of a symbolic or hard link.
+    pub link_name: [u8; 100],
+    /// ``ustar\0`` for POSIX archives, or ``ustar `` for GNU ones.
+    pub ustar_magic: [u8; 6],
+    /// ``00`` for POSIX archives, or `` \0`` for GNU ones.
+    pub ustar_version: [u8; 2],
+    /// The user name of the owner.
+    pub own_user_name: [u8; 32],
+    /// The group name of the owner.
+    pub own_group_name: [u8; 32],
+    /// The major number of a device entry.
+    pub device_major: [u8; 8],
+    /// The minor number of a device entry.
+    pub device_minor: [u8; 8],
+    /// The leading directories of a long ustar name, or the old GNU sparse map.
+    pub file_prefix: [u8; 155],
+    /// Unused padding up to the end of the block.
+    pub reserved: [u8; 12],
+}
+
+impl Default for TarHeader {
+    fn default() -> TarHeader {
+        TarHeader {
+            file_name: [0; 100],
+            file_mode: [0; 8],
+            own_user: [0; 8],
+            own_group: [0; 8],
+            file_size: [0; 12],
+            mod_time: [0; 12],
+            header_checksum: [0; 8],
+            link_indicator: [0; 1],
+            link_name: [0; 100],
+            ustar_magic: [0; 6],
+            ustar_version: [0; 2],
+            own_user_name: [0; 32],
+            own_group_name: [0; 32],
+            device_major: [0; 8],
+            device_minor: [0; 8],
+            file_prefix: [0; 155],
+            reserved: [0; 12],
+        }
+    }
+}
+
+impl TarHeader {
+    /// Validates that the magic value received matches the magic value required in the Tar specification.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar_core::TarHeader;
+    /// let header = TarHeader::default();
+    /// if !header.validate_magic() {
+    ///     println!("Magic value is invalid");
+    /// }
+    /// ```
+    pub fn validate_magic(self) -> bool {
+        /* GNU tar writes ``ustar  \0`` while POSIX archives use ``ustar\0`` followed by version ``00`` */
+        self.ustar_magic == *b"ustar " || self.ustar_magic == *b"ustar\x00"
+    }
+
+    /// Validates the header checksum computes to the expected value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar_core::TarHeader;
+    /// let header = TarHeader::default();
+    /// if header.validate_checksum().unwrap() {
+    ///     println!("Checksum is valid");
+    /// }
+    /// ```
+    pub fn validate_checksum(self) -> Result<bool, TarError> {
+        let mut test = self;
+        test.update_checksum()?;
+
+        Ok(self.header_checksum == test.header_checksum)
+    }
+
+    /// Updates the header checksum value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar_core::TarHeader;
+    /// let mut header = TarHeader::default();
+    ///
+    /// /* Fill in header information */
+    ///
+    /// header.update_checksum();
+    /// ```
+    pub fn update_checksum(&mut self) -> Result<(), TarError> {
+        /* The checksum is calculated as if the checksum field were all spaces */
+        self.header_checksum = [0x20; 8];
+        let checksum = self.calc_checksum()? as u64;
+        dec_to_oct(
+            checksum,
+            &mut self.header_checksum,
+            OctalTerminator::NulSpace,
+        )
+    }
+
+    /// Parse and validate a header from a ``512`` byte block. Fails with `TarError::EndOfTar` for a block
+    /// of zeros, and with `TarError::InvalidMagic` or `TarError::InvalidChecksum` for anything that isn't
+    /// a valid header.
+    pub fn from_block(h: &[u8; 512]) -> Result<TarHeader, TarError> {
+        let (_, header) = TarHeader::from_bytes((h, 0))?;
+        if header == TarHeader::default() {
+            return Err(TarError::EndOfTar);
+        }
+        if !header.validate_magic() {
+            return Err(TarError::InvalidMagic);
+        }
+        if !header.validate_checksum()? {
+            return Err(TarError::InvalidChecksum);
+        }
+
+        Ok(header)
+    }
+
+    fn calc_checksum(self) -> Result<usize, TarError> {
+        let out = self.to_bytes()?;
+        let mut checksum = 0;
+        for i in out {
+            checksum += i as usize;
+        }
+        Ok(checksum)
+    }
+}
+
+/// Convert a NUL terminated field to a string.
+pub fn cstr(input: &[u8]) -> String {
+    let end = input.iter().position(|&x| x == 0).unwrap_or(input.len());
+    String::from_utf8_lossy(&input[..end]).to_string()
+}
+
+/// How a numeric header field written by `dec_to_oct` is terminated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OctalTerminator {
+    /// Digits followed by a NUL, as used for most fields.
+    Nul,
+    /// Digits followed by a space, as written by some older tars.
+    Space,
+    /// Digits followed by a NUL and a space, as used for the header checksum.
+    NulSpace,
+    /// Digits filling the whole field.
+    None,
+}
+
+/// Encode `value` into `field` as zero padded octal digits followed by the `terminator`. Fails with
+/// `TarError::FieldOverflow` if the value needs more digits than the field can hold, leaving `field`
+/// unchanged.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::{dec_to_oct, OctalTerminator};
+///
+/// let mut field = [0u8; 8];
+/// dec_to_oct(0o644, &mut field, OctalTerminator::Nul).unwrap();
+/// assert_eq!(&field, b"0000644\0");
+/// assert!(dec_to_oct(1 << 21, &mut field, OctalTerminator::Nul).is_err());
+/// ```
+pub fn dec_to_oct(
+    value: u64,
+    field: &mut [u8],
+    terminator: OctalTerminator,
+) -> Result<(), TarError> {
+    let end: &[u8] = match terminator {
+        OctalTerminator::Nul => b"\0",
+        OctalTerminator::Space => b" ",
+        OctalTerminator::NulSpace => b"\0 ",
+        OctalTerminator::None => b"",
+    };
+    if end.len() > field.len() {
+        return Err(TarError::FieldOverflow);
+    }
+    let width = field.len() - end.len();
+    let mut digits = format!("{:0width$o}", value, width = width).into_bytes();
+    if digits.len() > width {
+        return Err(TarError::FieldOverflow);
+    }
+
+    digits.extend_from_slice(end);
+    set_field(field, &digits)
+}
+
+/// Encode `value` into `field` as a GNU base-256 number: the high bit of the first byte is set and the value
+/// follows in big-endian order, so an ``8`` byte field holds values up to ``2^63``. Used for numbers too
+/// large for octal digits. Fails with `TarError::FieldOverflow` if the value doesn't fit, leaving `field`
+/// unchanged.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::dec_to_base256;
+///
+/// let mut field = [0u8; 8];
+/// dec_to_base256(1 << 32, &mut field).unwrap();
+/// assert_eq!(field, [0x80, 0, 0, 1, 0, 0, 0, 0]);
+/// ```
+pub fn dec_to_base256(value: u64, field: &mut [u8]) -> Result<(), TarError> {
+    let bits = (field.len() * 8).saturating_sub(1);
+    if field.is_empty() || (bits < 64 && value >> bits != 0) {
+        return Err(TarError::FieldOverflow);
+    }
+
+    let bytes = value.to_be_bytes();
+    let n = field.len().min(bytes.len());
+    let start = field.len() - n;
+    field.fill(0);
+    field[start..].copy_from_slice(&bytes[bytes.len() - n..]);
+    field[0] |= 0x80;
+    Ok(())
+}
+
+/// Encode the signed `value` into `field` as a GNU base-256 number. Negative values, such as times before
+/// 1970, are stored in two's complement with every leading byte ``0xff``; others as by `dec_to_base256`.
+/// Fails with `TarError::FieldOverflow` if the value doesn't fit, leaving `field` unchanged.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::header::{oct_to_signed, signed_to_base256};
+///
+/// let mut field = [0u8; 12];
+/// signed_to_base256(-1, &mut field).unwrap();
+/// assert_eq!(field, [0xff; 12]);
+/// assert_eq!(oct_to_signed(&field).unwrap(), -1);
+/// ```
+pub fn signed_to_base256(value: i64, field: &mut [u8]) -> Result<(), TarError> {
+    if value >= 0 {
+        return dec_to_base256(value as u64, field);
+    }
+    /* The first byte must keep its sign bit, so an ``8`` byte field holds down to ``-2^63`` */
+    let bits = (field.len() * 8).saturating_sub(1);
+    if field.is_empty() || (bits < 64 && value >> bits != -1) {
+        return Err(TarError::FieldOverflow);
+    }
+
+    let bytes = value.to_be_bytes();
+    let n = field.len().min(bytes.len());
+    let start = field.len() - n;
+    field.fill(0xff);
+    field[start..].copy_from_slice(&bytes[bytes.len() - n..]);
+    Ok(())
+}
+
+/// Copy `value` into the header field `field`, padding the rest of the field with NULs. Fails with
+/// `TarError::FieldOverflow` if the value is longer than the field, leaving `field` unchanged, so no
+/// caller supplied length can cause a panic.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::set_field;
+///
+/// let mut field = [b'x'; 8];
+/// set_field(&mut field, b"root").unwrap();
+/// assert_eq!(&field, b"root\0\0\0\0");
+/// assert!(set_field(&mut field, b"too long!").is_err());
+/// ```
+pub fn set_field(field: &mut [u8], value: &[u8]) -> Result<(), TarError> {
+    match field.get_mut(..value.len()) {
+        Some(head) => head.copy_from_slice(value),
+        None => return Err(TarError::FieldOverflow),
+    }
+    field[value.len()..].fill(0);
+    Ok(())
+}
+
+/// Decode a numeric header field written as octal digits or as a GNU base-256 number. Fields left empty
+/// read as ``0``.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::header::oct_to_dec;
+///
+/// assert_eq!(oct_to_dec(b"0000644\0").unwrap(), 0o644);
+/// assert_eq!(oct_to_dec(b"        ").unwrap(), 0);
+/// ```
+pub fn oct_to_dec(input: &[u8]) -> Result<usize, TarError> {
+    /* GNU base-256 numbers set the high bit of the first byte */
+    if input.first().is_some_and(|&b| b & 0x80 != 0) {
+        return base256_to_dec(input);
+    }
+
+    /* The digits may be padded with spaces and end at a NUL, a space, or the end of the field */
+    let end = input.iter().position(|&b| b == 0).unwrap_or(input.len());
+    let s = str::from_utf8(&input[..end])?.trim_matches(' ');
+
+    /* Some archivers leave fields they don't fill in empty, which reads as 0 */
+    if s.is_empty() {
+        return Ok(0);
+    }
+
+    /* Convert to usize from octal */
+    Ok(usize::from_str_radix(s, 8)?)
+}
+
+/// Convert the numeric field `input` to a signed number, as used for times. Octal digits and base-256 are
+/// read as by `oct_to_dec`, and base-256 numbers starting with ``0xff`` are negative.
+pub fn oct_to_signed(input: &[u8]) -> Result<i64, TarError> {
+    match input.first() {
+        Some(0xff) => input[1..]
+            .iter()
+            .try_fold(-1i64, |acc, &b| acc.checked_mul(256)?.checked_add(b as i64))
+            .ok_or(TarError::FieldOverflow),
+        _ => i64::try_from(oct_to_dec(input)?).map_err(|_| TarError::FieldOverflow),
+    }
+}
+
+/// Decode a GNU base-256 number. Negative numbers, with a first byte of ``0xff``, are not valid sizes or
+/// ids; times read them with `oct_to_signed`.
+fn base256_to_dec(input: &[u8]) -> Result<usize, TarError> {
+    if input[0] == 0xff {
+        return Err(TarError::FieldOverflow);
+    }
+    input[1..]
+        .iter()
+        .try_fold((input[0] & 0x7f) as usize, |acc, &b| {
+            acc.checked_mul(256)?.checked_add(b as usize)
+        })
+        .ok_or(TarError::FieldOverflow)
+}
+
+/// Returns `true` if the numeric field `input` holds no digits, only NULs and spaces.
+pub fn is_blank(input: &[u8]) -> bool {
+    input.iter().all(|&b| b == 0 || b == b' ')
+}
@@ -0,0 +1,22 @@
+//! The header and format logic of minitar: the ustar header layout, numeric field encodings and PAX
+//! records. It has no filesystem or Unix specific code, so it builds for embedded and WASM targets. The
+//! `minitar` crate re-exports everything here along with its filesystem and high level API.
+//!
+//! # Example
+//!
+//! ```
+//! use minitar_core::{dec_to_oct, OctalTerminator, TarHeader};
+//!
+//! let mut header = TarHeader::default();
+//! dec_to_oct(0o644, &mut header.file_mode, OctalTerminator::Nul).unwrap();
+//! header.update_checksum().unwrap();
+//! assert!(header.validate_checksum().unwrap());
+//! ```
+
+pub mod error;
+pub mod header;
+pub mod pax;
+
+pub use error::{TarError, TarWarning, Verbosity};
+pub use header::{dec_to_base256, dec_to_oct, set_field, FileType, OctalTerminator, TarHeader};
+pub use pax::PaxRecord;
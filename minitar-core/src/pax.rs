@@ -0,0 +1,190 @@
+use std::str;
+
+use crate::error::TarError;
+
+/// A single ``length key=value\n`` record stored in a PAX extended header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaxRecord {
+    key: String,
+    value: Vec<u8>,
+}
+
+impl PaxRecord {
+    /// Create a new record from a `key` and a `value`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar_core::PaxRecord;
+    ///
+    /// let record = PaxRecord::new("path", "some/long/name.txt");
+    /// assert_eq!(record.key(), "path");
+    /// ```
+    pub fn new<V: AsRef<[u8]>>(key: &str, value: V) -> Self {
+        PaxRecord {
+            key: key.to_string(),
+            value: value.as_ref().to_vec(),
+        }
+    }
+
+    /// Returns the key of the record.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the raw value of the record.
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// Returns the value of the record as a string, if it is valid UTF-8.
+    pub fn value_str(&self) -> Option<&str> {
+        str::from_utf8(&self.value).ok()
+    }
+
+    /// Encode the record as ``length key=value\n``, where ``length`` includes its own digits.
+    fn encode(&self) -> Vec<u8> {
+        /* ' ' + key + '=' + value + '\n' */
+        let body = self.key.len() + self.value.len() + 3;
+        let mut len = body + body.to_string().len();
+        if len.to_string().len() != body.to_string().len() {
+            len = body + len.to_string().len();
+        }
+
+        let mut out = format!("{} {}=", len, self.key).into_bytes();
+        out.extend_from_slice(&self.value);
+        out.push(b'\n');
+        out
+    }
+}
+
+/// Serialize a list of records into the data section of a PAX extended header. Records are sorted by key
+/// and timestamps are written in canonical form, so that the same records always produce the same bytes.
+/// The sort is stable, so repeated keys keep their relative order.
+pub fn encode_records(records: &[PaxRecord]) -> Vec<u8> {
+    let mut sorted = records.to_vec();
+    sorted.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut out = Vec::new();
+    for mut r in sorted {
+        if TIME_KEYS.contains(&r.key.as_str()) {
+            if let Some(v) = r.value_str() {
+                r.value = canonical_time(v).into_bytes();
+            }
+        }
+        out.extend_from_slice(&r.encode());
+    }
+    out
+}
+
+/// Keys holding timestamps as decimal seconds with an optional fraction.
+const TIME_KEYS: [&str; 3] = ["atime", "ctime", "mtime"];
+
+/// Format a decimal timestamp canonically: no leading ``+`` or redundant zeros, and no trailing zeros or
+/// ``.`` in the fraction.
+fn canonical_time(value: &str) -> String {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(d) => ("-", d),
+        None => ("", value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    if int.is_empty() && frac.is_empty()
+        || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return value.to_string();
+    }
+
+    let int = match int.trim_start_matches('0') {
+        "" => "0",
+        i => i,
+    };
+    let frac = frac.trim_end_matches('0');
+    let sign = if int == "0" && frac.is_empty() {
+        ""
+    } else {
+        sign
+    };
+
+    if frac.is_empty() {
+        format!("{}{}", sign, int)
+    } else {
+        format!("{}{}.{}", sign, int, frac)
+    }
+}
+
+/// Parse a decimal timestamp such as ``1700000000.123456789`` into whole seconds and nanoseconds. Digits
+/// beyond nanoseconds are dropped. As with ``timespec``, the nanoseconds are never negative, so
+/// ``-1.25`` is two seconds before the epoch plus 750000000 nanoseconds.
+///
+/// # Example
+///
+/// ```
+/// use minitar_core::pax::{format_time, parse_time};
+///
+/// assert_eq!(parse_time("-1.25"), Some((-2, 750_000_000)));
+/// assert_eq!(format_time(-2, 750_000_000), "-1.25");
+/// ```
+pub fn parse_time(value: &str) -> Option<(i64, u32)> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let secs: i64 = int.parse().ok()?;
+    let nanos: u32 = format!("{:0<9}", &frac[..frac.len().min(9)]).parse().ok()?;
+    match (negative, nanos) {
+        (false, _) => Some((secs, nanos)),
+        (true, 0) => Some((-secs, 0)),
+        (true, _) => Some((-secs - 1, 1_000_000_000 - nanos)),
+    }
+}
+
+/// Format a time of `secs` seconds and `nanos` nanoseconds since the epoch as a canonical decimal
+/// timestamp, the inverse of `parse_time`.
+pub fn format_time(secs: i64, nanos: u32) -> String {
+    let value = match (secs < 0, nanos) {
+        (_, 0) => secs.to_string(),
+        (false, _) => format!("{}.{:09}", secs, nanos),
+        (true, _) => format!("-{}.{:09}", -(secs + 1), 1_000_000_000 - nanos),
+    };
+    canonical_time(&value)
+}
+
+/// Parse the data section of a PAX extended header into a list of records.
+pub fn decode_records(input: &[u8]) -> Result<Vec<PaxRecord>, TarError> {
+    let mut out = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        /* Trailing padding within the header data is not a record */
+        if rest[0] == 0 {
+            break;
+        }
+
+        let space = rest
+            .iter()
+            .position(|&x| x == b' ')
+            .ok_or(TarError::InvalidPax)?;
+        let len = str::from_utf8(&rest[..space])?.parse::<usize>()?;
+        if len <= space + 1 || len > rest.len() || rest[len - 1] != b'\n' {
+            return Err(TarError::InvalidPax);
+        }
+
+        let record = &rest[space + 1..len - 1];
+        let equals = record
+            .iter()
+            .position(|&x| x == b'=')
+            .ok_or(TarError::InvalidPax)?;
+        out.push(PaxRecord {
+            key: str::from_utf8(&record[..equals])?.to_string(),
+            value: record[equals + 1..].to_vec(),
+        });
+
+        rest = &rest[len..];
+    }
+
+    Ok(out)
+}
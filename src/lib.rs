@@ -1,5 +1,9 @@
 pub mod error;
+pub mod prelude;
 pub mod tar;
 
+pub use error::{TarError, TarWarning};
+pub use tar::{create, extract, EntryInfo, FileType, TarBuilder, TarFile, TarHeader, TarNode};
+
 #[cfg(test)]
 pub mod test;
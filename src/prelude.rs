@@ -0,0 +1,18 @@
+//! Re-exports of the commonly used types, for glob importing.
+//!
+//! # Example
+//!
+//! ```
+//! use minitar::prelude::*;
+//!
+//! let data = TarFile::open("test/1.tar".to_string()).unwrap();
+//! assert_eq!(data.list()[0].file_type, FileType::Normal);
+//! ```
+
+pub use crate::error::{TarError, TarWarning};
+pub use crate::tar::{
+    ArchiveOptions, CompareOptions, Compression, DuplicatePolicy, EntryInfo, ExtractOptions,
+    FileType, HashAlgorithm, LinkPolicy, LongNamePolicy, OwnerMapping, Profile, ReadOptions,
+    SocketPolicy, SparseFormat, SpecialPolicy, SymlinkPolicy, TarBuilder, TarFile, TarHeader,
+    TarNode, TargetFormat, Transform, Verbosity, WriteOptions,
+};
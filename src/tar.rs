@@ -1,248 +1,747 @@
-use std::env;
+use std::borrow::Cow;
 use std::fs;
 use std::fs::File;
 use std::fs::Metadata;
-use std::io::BufReader;
+use std::io::{BufReader, IoSlice, Read};
+use std::iter::FromIterator;
 use std::os::unix::prelude::FileTypeExt;
 use std::str;
 use std::string::String;
+use std::sync::Arc;
+use std::time::Instant;
 
 use deku::prelude::*;
 
-use crate::error::TarError;
+use crate::error::{TarError, TarWarning};
+pub(crate) use minitar_core::header::{cstr, is_blank, oct_to_dec, oct_to_signed};
+use minitar_core::pax;
+
+mod builder;
+mod cancel;
+mod compare;
+mod compress;
+mod convert;
+mod diff;
+mod digest;
+mod extract;
+mod files_from;
+mod index;
+mod journal;
+mod lazy;
+mod list;
+mod merge;
+mod names;
+mod options;
+mod owner;
+mod patch;
+mod profile;
+mod reflink;
+mod search;
+mod signature;
+mod sparse;
+mod stats;
+mod throttle;
+mod timeout;
+mod trace;
+mod transaction;
+mod transform;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+mod walk;
+mod xattr;
+
+pub use builder::{EntryWriter, TarBuilder};
+pub use cancel::CancelToken;
+pub use compare::{CompareOptions, Difference};
+#[cfg(feature = "zstd")]
+pub use compress::zstd_dictionary_id;
+pub use compress::{create, extract, Compression};
+pub use convert::TargetFormat;
+pub use diff::ArchiveChange;
+pub use digest::HashAlgorithm;
+pub use extract::MemoryEntry;
+pub use index::TarEntryLocation;
+pub use journal::{restore_point_in_time, JournalEntry, JournalOp};
+pub use list::EntryInfo;
+pub use merge::DuplicatePolicy;
+pub use minitar_core::header::{
+    dec_to_base256, dec_to_oct, set_field, signed_to_base256, FileType, OctalTerminator, TarHeader,
+};
+pub use minitar_core::{PaxRecord, Verbosity};
+pub use names::LongNamePolicy;
+pub use options::{
+    ArchiveOptions, ExtractOptions, LinkPolicy, OwnerMapping, ReadOptions, SocketPolicy,
+    SpecialPolicy, SymlinkPolicy, WriteOptions,
+};
+pub use owner::{CachedResolver, OwnerResolver, StaticResolver, SystemResolver};
+pub use patch::HeaderPatch;
+pub use profile::Profile;
+pub use search::NameIndex;
+pub use signature::{HmacKey, Signer, Verifier};
+pub use sparse::{SparseFormat, SparseMap};
+pub use stats::{ArchiveStats, OperationStats, SkipReason};
+pub use transaction::Transaction;
+pub use transform::Transform;
 
 #[cfg(target_os = "linux")]
 use std::os::linux::fs::MetadataExt;
 #[cfg(target_os = "macos")]
 use std::os::macos::fs::MetadataExt;
 
-/// Represents the different types of files that can be encoded in a tar file.
-#[repr(u8)]
-pub enum FileType {
-    Normal = 0x30,
-    Hard = 0x31,
-    Sym = 0x32,
-    Char = 0x33,
-    Block = 0x34,
-    Dir = 0x35,
-    FIFO = 0x36,
-    Unknown = 0x00,
+/// Contains a tar representation of a file.
+#[derive(Clone, Debug, Default)]
+pub struct TarNode {
+    header: TarHeader,
+    pax: Vec<PaxRecord>,
+    sparse: Option<SparseMap>,
+    location: Option<(u64, u64)>,
+    /* The device and inode of the file the node was read from, if it has several hard links */
+    inode: Option<(u64, u64)>,
+    /* Write long names in GNU ``L`` and ``K`` headers rather than a PAX extended header */
+    gnu_long_names: bool,
+    /* The bytes following the header, without the zeros padding them to a whole block */
+    data: Vec<u8>,
+    /* Where the data is found in the archive instead, when it is read on demand */
+    lazy: Option<lazy::LazyData>,
 }
 
-/// Contains the representation of a Tar file header.
-#[derive(Clone, Copy, Debug, DekuRead, DekuWrite, PartialEq)]
-#[deku(endian = "little")]
-pub struct TarHeader {
-    file_name: [u8; 100],
-    file_mode: [u8; 8],
-    own_user: [u8; 8],
-    own_group: [u8; 8],
-    file_size: [u8; 12],
-    mod_time: [u8; 12],
-    header_checksum: [u8; 8],
-    link_indicator: [u8; 1],
-    link_name: [u8; 100],
-    ustar_magic: [u8; 6],
-    ustar_version: [u8; 2],
-    own_user_name: [u8; 32],
-    own_group_name: [u8; 32],
-    device_major: [u8; 8],
-    device_minor: [u8; 8],
-    file_prefix: [u8; 155],
-    reserved: [u8; 12],
-}
+impl TarNode {
+    /// Write out a single file within the tar to a file or something with a ``std::io::Write`` trait.
+    pub fn write<T: std::io::Write>(self, input: T) -> Result<usize, TarError> {
+        self.write_with(input, &WriteOptions::default())
+    }
+
+    /// Write out a single file within the tar in the same way as `write`, using the provided `WriteOptions`.
+    pub fn write_with<T: std::io::Write>(
+        self,
+        input: T,
+        options: &WriteOptions,
+    ) -> Result<usize, TarError> {
+        let (headers, data, padding) = self.write_parts(input, options)?;
+        Ok(headers + data + padding)
+    }
 
-impl Default for TarHeader {
-    fn default() -> TarHeader {
-        TarHeader {
-            file_name: [0; 100],
-            file_mode: [0; 8],
-            own_user: [0; 8],
-            own_group: [0; 8],
-            file_size: [0; 12],
-            mod_time: [0; 12],
-            header_checksum: [0; 8],
-            link_indicator: [0; 1],
-            link_name: [0; 100],
-            ustar_magic: [0; 6],
-            ustar_version: [0; 2],
-            own_user_name: [0; 32],
-            own_group_name: [0; 32],
-            device_major: [0; 8],
-            device_minor: [0; 8],
-            file_prefix: [0; 155],
-            reserved: [0; 12],
+    /// Write out the node in the same way as `write_with`, returning the bytes of headers, data and padding
+    /// written separately.
+    pub(crate) fn write_parts<T: std::io::Write>(
+        self,
+        mut input: T,
+        options: &WriteOptions,
+    ) -> Result<(usize, usize, usize), TarError> {
+        if self.sparse.is_some() {
+            return self
+                .encode_sparse(options.sparse_format)?
+                .write_parts(input, options);
         }
+
+        self.check_size()?;
+        let mut header = Vec::with_capacity(512);
+        self.write_header(&mut header, options)?;
+        let len = self.data_len();
+        let padding = &[0u8; 512][..padding(len)];
+
+        /* The headers, data and padding go out together, in a single call for most writers */
+        match &self.lazy {
+            /* Writers are not necessarily files, so this is not copied inside the kernel */
+            Some(lazy) => {
+                input.write_all(&header)?;
+                lazy.write_to(len, &mut input)?;
+                input.write_all(padding)?;
+            }
+            None => write_all_vectored(
+                &mut input,
+                &mut [
+                    IoSlice::new(&header),
+                    IoSlice::new(&self.data),
+                    IoSlice::new(padding),
+                ],
+            )?,
+        }
+
+        Ok((header.len(), len, padding.len()))
     }
-}
 
-impl TarHeader {
-    /// Validates that the magic value received matches the magic value required in the Tar specification.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use minitar::tar::TarHeader;
-    /// let header = TarHeader::default();
-    /// if !header.validate_magic() {
-    ///     println!("Magic value is invalid");
-    /// }
-    /// ```
-    pub fn validate_magic(self) -> bool {
-        self.ustar_magic == "ustar ".as_bytes()
+    /// Write the extended headers, if any, and the header of this node, without its data.
+    pub(crate) fn write_header<T: std::io::Write>(
+        &self,
+        input: &mut T,
+        options: &WriteOptions,
+    ) -> Result<usize, TarError> {
+        /* Headers edited since their checksum was computed are refreshed, unless that is an error */
+        let mut header = self.header;
+        if !header.validate_checksum()? {
+            if options.strict {
+                return Err(TarError::InvalidChecksum);
+            }
+            header.update_checksum()?;
+        }
+
+        let mut written = 0;
+        let mut records = self.pax.clone();
+        if self.gnu_long_names {
+            for (key, file_type) in [
+                ("path", FileType::GnuLongName),
+                ("linkpath", FileType::GnuLongLink),
+            ] {
+                if let Some(value) = self.pax_value(key) {
+                    written += write_gnu_long_name(input, value, file_type)?;
+                    records.retain(|r| r.key() != key);
+                }
+            }
+        }
+        if !records.is_empty() {
+            written += self.write_pax(input, &records)?;
+        }
+
+        input.write_all(&header.to_bytes()?)?;
+        Ok(written + 512)
     }
 
-    /// Validates the header checksum computes to the expected value.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use minitar::tar::TarHeader;
-    /// let header = TarHeader::default();
-    /// if header.validate_checksum().unwrap() {
-    ///     println!("Checksum is valid");
-    /// }
-    /// ```
-    pub fn validate_checksum(self) -> Result<bool, TarError> {
-        let mut test = self;
-        let mut new = [0x20u8; 8];
-        test.header_checksum.copy_from_slice(&[0x20; 8]);
+    /// Check that the data matches the size in the header. Readers find the next header by skipping that
+    /// many blocks, so any mismatch corrupts the rest of the archive.
+    fn check_size(&self) -> Result<(), TarError> {
+        let size = match carries_data(self.file_type()) {
+            true => self.size(),
+            false => 0,
+        };
+        if self.data_len() != self.gnu_extension_blocks() * 512 + size {
+            return Err(TarError::SizeMismatch {
+                name: self.name(),
+                size: self.size(),
+                blocks: self.data_len().div_ceil(512),
+            });
+        }
+        Ok(())
+    }
+
+    /// Write the PAX extended header holding `records` that precedes this node.
+    fn write_pax<T: std::io::Write>(
+        &self,
+        input: &mut T,
+        records: &[PaxRecord],
+    ) -> Result<usize, TarError> {
+        let records = pax::encode_records(records);
+        let name = self.name();
+        let base = name.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        let pax_name = format!("PaxHeaders.0/{}", base);
+        let pax_name = names::truncate(&pax_name, 100);
 
-        let tmp = format!("{:06o}\x00", test.calc_checksum()?);
-        new[..tmp.len()].copy_from_slice(tmp.as_bytes());
+        let header = synthetic_header(pax_name, FileType::Pax as u8, records.len(), self.mtime())?;
+        input.write_all(&header.to_bytes()?)?;
 
-        Ok(self.header_checksum == new)
+        let mut written = 512;
+        for chunk in records.chunks(512) {
+            let mut block = [0u8; 512];
+            set_field(&mut block, chunk)?;
+            input.write_all(&block)?;
+            written += block.len();
+        }
+
+        Ok(written)
     }
 
-    /// Updates the header checksum value.
+    /// Returns the header of the entry.
+    pub fn header(&self) -> &TarHeader {
+        &self.header
+    }
+
+    /// Returns the data stored for the entry, without block padding. The data of old GNU sparse entries
+    /// starts with their extension blocks, and sparse entries only store the segments that aren't holes;
+    /// `TarFile::extract_entry` writes out the file contents instead. The data of entries read with
+    /// `ReadOptions::lazy_data` is read from the archive by each call, while entries read with
+    /// `ReadOptions::mmap` borrow it from the mapping.
     ///
     /// # Example
     ///
     /// ```
-    /// use minitar::tar::TarHeader;
-    /// let mut header = TarHeader::default();
-    ///
-    /// /* Fill in header information */
+    /// use minitar::tar::TarNode;
     ///
-    /// header.update_checksum();
+    /// let node = TarNode::from_data("hello.txt".to_string(), b"hello").unwrap();
+    /// assert_eq!(&node.data().unwrap()[..], b"hello");
     /// ```
-    pub fn update_checksum(&mut self) -> Result<(), TarError> {
-        let checksum = format!("{:06o}\x00", self.calc_checksum()?);
-        self.header_checksum[..checksum.len()].copy_from_slice(checksum.as_bytes());
-        Ok(())
+    pub fn data(&self) -> Result<Cow<'_, [u8]>, TarError> {
+        match &self.lazy {
+            Some(lazy) => lazy.load(),
+            None => Ok(Cow::Borrowed(&self.data)),
+        }
     }
 
-    fn calc_checksum(self) -> Result<usize, TarError> {
-        let out = self.to_bytes()?;
-        let mut checksum = 0;
-        for i in out {
-            checksum += i as usize;
+    /// Returns the length of the data stored for the entry, without loading it.
+    fn data_len(&self) -> usize {
+        match &self.lazy {
+            Some(lazy) => lazy.len(),
+            None => self.data.len(),
         }
-        Ok(checksum)
     }
-}
 
-/// Contains a tar representation of a file.
-#[derive(Clone, Debug, Default)]
-pub struct TarNode {
-    header: TarHeader,
-    data: Vec<[u8; 512]>,
-}
+    /// Returns the header of the entry for editing. A checksum left stale by an edit is recomputed when the
+    /// node is written, unless `WriteOptions::strict` is set.
+    pub fn header_mut(&mut self) -> &mut TarHeader {
+        &mut self.header
+    }
 
-impl TarNode {
-    /// Write out a single file within the tar to a file or something with a ``std::io::Write`` trait.
-    pub fn write<T: std::io::Write>(self, mut input: T) -> Result<usize, TarError> {
-        input.write_all(&self.header.to_bytes()?)?;
-        let mut written = 512;
-        for d in self.data {
-            input.write_all(&d)?;
-            written += d.len();
+    /// Returns the path of the entry, taking PAX ``path`` and ``GNU.sparse.name`` records, GNU long names and
+    /// the ustar prefix field into account.
+    pub fn name(&self) -> String {
+        /* GNU tar stores sparse files under a made up name, keeping the real one in a record */
+        if let Some(path) = self.pax_value("GNU.sparse.name") {
+            return path.to_string();
+        }
+        if let Some(path) = self.pax_value("path") {
+            return path.to_string();
         }
 
-        Ok(written)
+        let name = cstr(&self.header.file_name);
+        if self.header.ustar_magic == *b"ustar\x00" && self.header.file_prefix[0] != 0 {
+            return format!("{}/{}", cstr(&self.header.file_prefix), name);
+        }
+
+        name
+    }
+
+    /// Rename the entry to `name`, updating the header checksum.
+    fn set_name(&mut self, name: &str) -> Result<(), TarError> {
+        if name.len() > self.header.file_name.len() {
+            return Err(TarError::NameTooLong);
+        }
+
+        set_field(&mut self.header.file_name, name.as_bytes())?;
+        self.header.file_prefix = [0; 155];
+        self.pax.retain(|r| r.key() != "path");
+        self.header.update_checksum()
+    }
+
+    /// Rename the entry to `name` in the same way as `set_name`, keeping a name too long for the header
+    /// field in a PAX ``path`` record.
+    fn set_long_name(&mut self, name: &str) -> Result<(), TarError> {
+        self.header.file_prefix = [0; 155];
+        self.pax.retain(|r| r.key() != "path");
+        self.place_long("path", name, self.header.file_name.len())?;
+        self.header.update_checksum()
+    }
+
+    /// Returns the PAX records attached to this node.
+    pub fn pax(&self) -> &[PaxRecord] {
+        &self.pax
+    }
+
+    /// Attach a PAX record to this node. It is written in the extended header preceding the entry.
+    pub fn push_pax(&mut self, record: PaxRecord) {
+        self.pax.push(record);
+    }
+
+    /// Returns the string value of the last PAX record matching `key`, if present.
+    fn pax_value(&self, key: &str) -> Option<&str> {
+        self.pax
+            .iter()
+            .rev()
+            .find(|r| r.key() == key)
+            .and_then(|r| r.value_str())
     }
 
     /// Read a TarNode in from a file or something with a ``std::io::Read`` trait.
     pub fn read<T: std::io::Read>(mut input: T) -> Result<TarNode, TarError> {
-        let mut h = vec![0u8; 512];
-        input.read_exact(&mut h)?;
+        let mut h = [0u8; 512];
+        if !read_block(&mut input, &mut h)? {
+            return Err(TarError::Truncated);
+        }
+
+        TarNode::read_with_header(&h, input)
+    }
+
+    /// Read the rest of a TarNode whose header block `h` has already been read from `input`.
+    fn read_with_header<T: std::io::Read>(h: &[u8; 512], input: T) -> Result<TarNode, TarError> {
+        TarNode::read_with_records(h, input, Vec::new(), true)
+    }
+
+    /// Read the rest of a TarNode whose header block `h` has already been read from `input`, applying the
+    /// `pax` records read from the extension headers before it. Unless `load` is set, `input` is left at
+    /// the start of data that `defers_data` allows to be read later.
+    fn read_with_records<T: std::io::Read>(
+        h: &[u8; 512],
+        mut input: T,
+        mut pax: Vec<PaxRecord>,
+        load: bool,
+    ) -> Result<TarNode, TarError> {
+        /* PAX and GNU long name headers apply to the entry that follows them, however many there are */
+        let mut block = *h;
+        loop {
+            let header = TarHeader::from_block(&block)?;
+            let size = match pax.iter().rev().find(|r| r.key() == "size") {
+                Some(r) => r
+                    .value_str()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or(TarError::InvalidPax)?,
+                None => oct_to_dec(&header.file_size)?,
+            };
+            let size = match carries_data(header.link_indicator[0]) {
+                true => size,
+                false => 0,
+            };
+            /* Old GNU sparse extension blocks come before the data, and aren't counted in its size */
+            let mut data = sparse::read_gnu_extensions(&header, &mut input)?;
+            if load || !defers_data(header.link_indicator[0]) {
+                data.append(&mut TarNode::read_data(&mut input, Some(size))?);
+            }
+
+            if let Some(mut records) = extension_records(&header, &data)? {
+                if !read_block(&mut input, &mut block)? {
+                    return Err(TarError::Truncated);
+                }
+                pax.append(&mut records);
+                continue;
+            }
+
+            return Ok(TarNode {
+                header,
+                pax,
+                sparse: None,
+                location: None,
+                inode: None,
+                gnu_long_names: false,
+                data,
+                lazy: None,
+            });
+        }
+    }
+
+    /// Returns the type flag of the entry.
+    fn file_type(&self) -> u8 {
+        self.header.link_indicator[0]
+    }
 
-        let (_, header) = TarHeader::from_bytes((&h, 0))?;
-        if header == TarHeader::default() {
-            return Err(TarError::EndOfTar);
+    /// Returns the size of the entry data in bytes, taking PAX ``size`` records into account.
+    fn size(&self) -> usize {
+        match self.pax_value("size").and_then(|v| v.parse().ok()) {
+            Some(size) => size,
+            None => oct_to_dec(&self.header.file_size).unwrap_or(0),
         }
-        if !header.validate_magic() {
-            return Err(TarError::InvalidMagic);
+    }
+
+    /// Returns the number of blocks following the header of the entry, including old GNU sparse extension
+    /// blocks, which must have been read into its data.
+    fn data_blocks(&self) -> usize {
+        match carries_data(self.file_type()) {
+            true => self.gnu_extension_blocks() + self.size().div_ceil(512),
+            false => 0,
         }
-        if !header.validate_checksum()? {
-            return Err(TarError::InvalidChecksum);
+    }
+
+    /// Returns the permission bits of the entry.
+    fn mode(&self) -> u32 {
+        oct_to_dec(&self.header.file_mode).unwrap_or(0) as u32
+    }
+
+    /// Returns the major and minor numbers of a character or block device entry.
+    fn device(&self) -> (u32, u32) {
+        (
+            oct_to_dec(&self.header.device_major).unwrap_or(0) as u32,
+            oct_to_dec(&self.header.device_minor).unwrap_or(0) as u32,
+        )
+    }
+
+    /// Returns the modification time of the entry in seconds since the epoch, taking PAX ``mtime`` records
+    /// into account.
+    fn mtime(&self) -> i64 {
+        match self.pax_value("mtime").and_then(pax::parse_time) {
+            Some((secs, _)) => secs,
+            None => oct_to_signed(&self.header.mod_time).unwrap_or(0),
+        }
+    }
+
+    /// Returns the fraction of a second of the modification time in nanoseconds, which only a PAX ``mtime``
+    /// record can hold.
+    fn mtime_nanos(&self) -> u32 {
+        match self.pax_value("mtime").and_then(pax::parse_time) {
+            Some((_, nanos)) => nanos,
+            None => 0,
         }
+    }
 
-        let chunks = (oct_to_dec(&header.file_size)? / 512) + 1;
+    /// Returns the numeric user id of the entry owner, taking PAX ``uid`` records into account.
+    fn uid(&self) -> u64 {
+        match self.pax_value("uid").and_then(|v| v.parse().ok()) {
+            Some(uid) => uid,
+            None => oct_to_dec(&self.header.own_user).unwrap_or(0) as u64,
+        }
+    }
+
+    /// Returns the numeric group id of the entry owner, taking PAX ``gid`` records into account.
+    fn gid(&self) -> u64 {
+        match self.pax_value("gid").and_then(|v| v.parse().ok()) {
+            Some(gid) => gid,
+            None => oct_to_dec(&self.header.own_group).unwrap_or(0) as u64,
+        }
+    }
+
+    /// Returns the names of the numeric header fields that were left empty and read as 0. The device
+    /// numbers are only checked for device entries, and fields overridden by PAX records are skipped.
+    fn blank_fields(&self) -> Vec<&'static str> {
+        let h = &self.header;
+        let device =
+            self.file_type() == FileType::Char as u8 || self.file_type() == FileType::Block as u8;
+        [
+            ("mode", &h.file_mode[..], true),
+            ("uid", &h.own_user[..], self.pax_value("uid").is_none()),
+            ("gid", &h.own_group[..], self.pax_value("gid").is_none()),
+            ("size", &h.file_size[..], self.pax_value("size").is_none()),
+            ("mtime", &h.mod_time[..], self.pax_value("mtime").is_none()),
+            ("devmajor", &h.device_major[..], device),
+            ("devminor", &h.device_minor[..], device),
+        ]
+        .iter()
+        .filter(|(_, field, checked)| *checked && is_blank(field))
+        .map(|(name, _, _)| *name)
+        .collect()
+    }
+
+    /// Returns the user name of the entry owner, taking PAX ``uname`` records into account.
+    fn uname(&self) -> String {
+        match self.pax_value("uname") {
+            Some(name) => name.to_string(),
+            None => cstr(&self.header.own_user_name),
+        }
+    }
+
+    /// Returns the group name of the entry owner, taking PAX ``gname`` records into account.
+    fn gname(&self) -> String {
+        match self.pax_value("gname") {
+            Some(name) => name.to_string(),
+            None => cstr(&self.header.own_group_name),
+        }
+    }
+
+    /// Returns the target of a link entry, taking PAX ``linkpath`` records into account.
+    fn link_name(&self) -> String {
+        match self.pax_value("linkpath") {
+            Some(link) => link.to_string(),
+            None => cstr(&self.header.link_name),
+        }
+    }
+
+    /// Write the exact ``file_size`` bytes of the entry data, without block padding, to `output`.
+    fn write_data<T: std::io::Write>(&self, mut output: T) -> Result<usize, TarError> {
+        if let Some((map, stored)) = self.sparse_contents()? {
+            return sparse::write_holes(&map, &stored, output);
+        }
+
+        let len = self.size().min(self.data_len());
+        match &self.lazy {
+            Some(lazy) => lazy.write_to(len, output),
+            None => {
+                output.write_all(&self.data[..len])?;
+                Ok(len)
+            }
+        }
+    }
+
+    /// Write the entry data to `file` in the same way as `write_data`. Data left in an archive file by
+    /// `ReadOptions::lazy_data` is copied from it inside the kernel where the filesystems allow.
+    fn write_data_to_file(&self, file: &File) -> Result<usize, TarError> {
+        match &self.lazy {
+            Some(lazy) if self.sparse_map()?.is_none() => {
+                lazy.copy_to_file(self.size().min(lazy.len()), file)
+            }
+            _ => self.write_data(file),
+        }
+    }
+
+    /// Create a regular file entry named `filename` holding `data`, with mode ``0644``, owned by root and
+    /// timestamped at the epoch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarNode;
+    ///
+    /// let node = TarNode::from_data("hello.txt".to_string(), b"hello").unwrap();
+    /// assert_eq!(node.name(), "hello.txt");
+    /// ```
+    pub fn from_data(filename: String, data: &[u8]) -> Result<TarNode, TarError> {
+        let header = synthetic_header(&filename, FileType::Normal as u8, data.len(), 0)?;
         Ok(TarNode {
             header,
-            data: TarNode::chunk_file(&mut input, Some(chunks))?,
+            data: data.to_vec(),
+            ..Default::default()
         })
     }
 
-    /// Open and read a file from the ``filename`` argument to a TarNode.
-    fn read_file_to_tar(filename: String) -> Result<TarNode, TarError> {
-        let header = generate_header(&filename)?;
-        if header.link_indicator[0] != FileType::Normal as u8 {
-            return Ok(TarNode {
+    /// Open and read a file from the ``filename`` argument to a TarNode. A file with several hard links that
+    /// `links` maps to the name of an entry already archived is stored as a hard link to that entry. Only
+    /// the warnings reported at the verbosity of `options` are added to `warnings`. With `defer`, the data
+    /// of a plain regular file is left in the open file, as `ReadOptions::lazy_data` leaves it in an
+    /// archive, for the caller to read.
+    fn read_file_to_tar(
+        filename: String,
+        options: &ArchiveOptions,
+        warnings: &mut Vec<TarWarning>,
+        links: &dyn Fn((u64, u64)) -> Option<String>,
+        defer: bool,
+    ) -> Result<Option<TarNode>, TarError> {
+        let mut raised = Vec::new();
+        let node = TarNode::read_file_unfiltered(filename, options, &mut raised, links, defer);
+        for warning in raised {
+            options.report(warnings, warning);
+        }
+        node
+    }
+
+    fn read_file_unfiltered(
+        filename: String,
+        options: &ArchiveOptions,
+        warnings: &mut Vec<TarWarning>,
+        links: &dyn Fn((u64, u64)) -> Option<String>,
+        defer: bool,
+    ) -> Result<Option<TarNode>, TarError> {
+        /* Sockets can't be represented in a tar file, so they are never archived */
+        let meta = file_metadata(&filename, options)?;
+        if meta.file_type().is_socket() {
+            match options.sockets {
+                SocketPolicy::Warn => warnings.push(TarWarning::SocketSkipped(filename)),
+                SocketPolicy::Ignore => (),
+                SocketPolicy::Error => return Err(TarError::SpecialFile(filename)),
+            }
+            return Ok(None);
+        }
+
+        /* FIFOs, devices and anything that can't be represented are subject to the specials policy */
+        let file_type = get_file_type(&meta);
+        if is_special(file_type) {
+            match options.specials {
+                SpecialPolicy::Error => return Err(TarError::SpecialFile(filename)),
+                SpecialPolicy::Skip => {
+                    warnings.push(TarWarning::SpecialSkipped(filename));
+                    return Ok(None);
+                }
+                SpecialPolicy::Archive if file_type == FileType::Unknown as u8 => {
+                    warnings.push(TarWarning::SpecialSkipped(filename));
+                    return Ok(None);
+                }
+                SpecialPolicy::Archive => (),
+            }
+        }
+
+        let (mut header, mut pax) = generate_header(&filename, options, warnings)?;
+
+        if file_type != FileType::Normal as u8 {
+            return Ok(Some(TarNode {
                 header,
-                data: Vec::<[u8; 512]>::new(),
+                pax,
+                sparse: None,
+                location: None,
+                inode: None,
+                gnu_long_names: false,
+                data: Vec::new(),
+                lazy: None,
+            }));
+        }
+
+        /* Later names of a file with several hard links refer back to the first, rather than storing the
+         * data again */
+        let inode = match options.hard_links && meta.st_nlink() > 1 {
+            true => Some((meta.st_dev(), meta.st_ino())),
+            false => None,
+        };
+        if let Some(target) = inode.and_then(links) {
+            let (link, record) =
+                names::fit_name(&target, 100, "linkpath", options.long_names, warnings)?;
+            set_field(&mut header.link_name, link.as_bytes())?;
+            pax.extend(record);
+            header.link_indicator[0] = FileType::Hard as u8;
+            dec_to_oct(0, &mut header.file_size, OctalTerminator::Nul)?;
+            header.update_checksum()?;
+            warnings.push(TarWarning::HardLinked {
+                name: filename,
+                target,
             });
+            return Ok(Some(TarNode {
+                header,
+                pax,
+                inode,
+                ..Default::default()
+            }));
         }
 
         let file = File::open(&filename)?;
-        let mut reader = BufReader::new(file);
-        Ok(TarNode {
+        let node = TarNode {
             header,
-            data: TarNode::chunk_file(&mut reader, None)?,
-        })
-    }
-
-    /// Read in and split a file into ``512`` byte chunks.
-    fn chunk_file<T: std::io::Read>(
-        file: &mut T,
-        max_chunks: Option<usize>,
-    ) -> Result<Vec<[u8; 512]>, TarError> {
-        /* Extract the file data from the tar file */
-        let mut out = Vec::<[u8; 512]>::new();
-        let mut n = if let Some(max) = max_chunks {
-            max
-        } else {
-            usize::MAX
+            pax,
+            inode,
+            ..Default::default()
         };
 
-        /* Carve out 512 bytes at a time */
-        let mut buf: [u8; 512] = [0; 512];
-        loop {
-            let len = file.read(&mut buf)?;
+        /* Files taking up fewer blocks on disk than their length have holes */
+        if options.sparse && meta.st_blocks() * 512 < meta.len() {
+            if let Some(map) = sparse::find_holes(&file, meta.len())? {
+                let data = sparse::read_segments(&file, &map)?;
+                return Ok(Some(node.into_sparse(map, &data)?));
+            }
+        }
+
+        if defer {
+            let lazy =
+                lazy::LazyData::new(lazy::Source::File(Arc::new(file)), 0, meta.len() as usize);
+            return Ok(Some(TarNode {
+                lazy: Some(lazy),
+                ..node
+            }));
+        }
 
-            n -= 1;
+        let mut data = Vec::with_capacity(meta.len() as usize);
+        (&file).read_to_end(&mut data)?;
+        Ok(Some(TarNode { data, ..node }))
+    }
+
+    /// Returns the name of the entry holding the data of the file with device and inode `key`, if the
+    /// archive has one.
+    fn link_target(nodes: &[TarNode], key: (u64, u64)) -> Option<String> {
+        nodes
+            .iter()
+            .find(|n| n.inode == Some(key) && n.file_type() == FileType::Normal as u8)
+            .map(|n| n.name())
+    }
 
-            /* If read len == 0, we've hit the EOF */
-            if len == 0 || n == 0 {
-                break;
+    /// Read the data of an entry from `input`. With a `size`, the whole blocks holding that many bytes are
+    /// read, failing with `TarError::Truncated` if they are cut short, and the padding is dropped; without
+    /// one everything up to the end of `input` is read.
+    fn read_data<T: std::io::Read>(
+        input: &mut T,
+        size: Option<usize>,
+    ) -> Result<Vec<u8>, TarError> {
+        let mut out = Vec::new();
+        let size = match size {
+            Some(size) => size,
+            None => {
+                input.read_to_end(&mut out)?;
+                return Ok(out);
             }
+        };
 
-            /* Save this chunk */
-            out.push(buf);
+        /* The buffer grows as blocks arrive, so a corrupt size can't allocate more than the input holds */
+        let len = (size.div_ceil(512) * 512) as u64;
+        if input.take(len).read_to_end(&mut out)? as u64 != len {
+            return Err(TarError::Truncated);
         }
+        out.truncate(size);
         Ok(out)
     }
 }
 
 /// Contains the vector of files that represent a tar file.
+///
+/// Entries keep the order they were read from the archive in, followed by any added later in the order they
+/// were added, and are written back in that order. Only `sort`, `sort_by` and calls that remove or replace
+/// entries change it, so the index of an entry in `nodes`, as reported by `EntryInfo::index` and
+/// `TarEntryLocation::index`, is stable until the archive is edited.
 #[derive(Clone, Debug, Default)]
 pub struct TarFile {
     file: Vec<TarNode>,
+    warnings: Vec<TarWarning>,
 }
 
 impl TarFile {
     /// Write out a vector of `TarNodes` to a file or something that implements ``std::io::Write`` and ``std::io::Copy``.
+    /// Returns `OperationStats` counting the entries and bytes written.
     ///
     /// # Example
     ///
@@ -255,19 +754,62 @@ impl TarFile {
     /// let out = File::create("test/2.tar".to_string()).unwrap();
     /// data.write(&out).unwrap();
     /// ```
-    pub fn write<T: std::io::Write + Copy>(self, mut input: T) -> Result<usize, TarError> {
+    pub fn write<T: std::io::Write + Copy>(self, input: T) -> Result<OperationStats, TarError> {
+        self.write_with(input, &WriteOptions::default())
+    }
+
+    /// Write out the archive in the same way as `write`, using the provided `WriteOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use minitar::tar::{TarFile, WriteOptions};
+    ///
+    /// let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    ///
+    /// let out = File::create("test/3.tar".to_string()).unwrap();
+    /// let options = WriteOptions::default().blocking_factor(1);
+    /// let stats = data.write_with(&out, &options).unwrap();
+    /// assert_eq!(stats.entries(), 1);
+    /// assert_eq!(stats.total_bytes(), 2048);
+    /// ```
+    pub fn write_with<T: std::io::Write + Copy>(
+        self,
+        input: T,
+        options: &WriteOptions,
+    ) -> Result<OperationStats, TarError> {
+        /* Check every entry up front so a bad one doesn't leave a partial archive behind */
+        for f in self.file.iter().filter(|f| f.sparse.is_none()) {
+            f.check_size()?;
+        }
+
+        trace::span!("write", entries = self.file.len());
+        let start = Instant::now();
+        let mut stats = OperationStats::default();
+        let mut throttle = throttle::Throttle::new(options.rate_limit);
+        let mut output = throttle.writer(input);
         let mut written = 0;
-        for f in self.file.clone() {
-            written += f.write(input)?;
+        let empty = self.file.is_empty();
+        for f in self.file {
+            if cancel::cancelled(&options.cancel) {
+                return Err(TarError::Cancelled);
+            }
+            let file_type = f.entry_type();
+            trace::event!(debug, name = %f.name(), ?file_type, size = f.data_len(), "writing entry");
+            let (headers, data, padding) = f.write_parts(&mut output, options)?;
+            stats.count(file_type, data as u64);
+            stats.header_bytes += headers as u64;
+            stats.padding_bytes += padding as u64;
+            written += headers + data + padding;
         }
 
-        /* Complete the write with 18 blocks of 512 ``0x00`` bytes per the specification */
-        if !self.file.is_empty() {
-            input.write_all(&[0; 9216])?;
-            written += 9216;
+        if !empty {
+            stats.padding_bytes += write_trailer(&mut output, written, options)? as u64;
         }
 
-        Ok(written)
+        stats.finish(start);
+        Ok(stats)
     }
 
     /// Create a new `TarFile` struct and initialize it with a `filename` file. This will read in the file to
@@ -281,9 +823,23 @@ impl TarFile {
     /// let data = TarFile::new("test/1.txt".to_string()).unwrap();
     /// ```
     pub fn new(filename: String) -> Result<Self, TarError> {
-        Ok(TarFile {
-            file: vec![TarNode::read_file_to_tar(filename)?],
-        })
+        TarFile::new_with(filename, &ArchiveOptions::default())
+    }
+
+    /// Create a new `TarFile` in the same way as `new`, using the provided `ArchiveOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ArchiveOptions, TarFile};
+    ///
+    /// let options = ArchiveOptions::default();
+    /// let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    /// ```
+    pub fn new_with(filename: String, options: &ArchiveOptions) -> Result<Self, TarError> {
+        let mut out = TarFile::default();
+        out.append_with(filename, options)?;
+        Ok(out)
     }
 
     /// Append another file to the `TarFile.file` vector. This adds a file to the internal representation of the tar file.
@@ -297,9 +853,157 @@ impl TarFile {
     /// data.append("test/1.txt".to_string()).unwrap();
     /// ```
     pub fn append(&mut self, filename: String) -> Result<(), TarError> {
-        self.file.push(TarNode::read_file_to_tar(filename)?);
+        self.append_with(filename, &ArchiveOptions::default())
+    }
 
-        Ok(())
+    /// Append another file in the same way as `append`, using the provided `ArchiveOptions`. Nothing is added
+    /// if the file matches an exclude pattern.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ArchiveOptions, LongNamePolicy, TarFile};
+    ///
+    /// let options = ArchiveOptions::default().long_names(LongNamePolicy::Truncate);
+    /// let mut data = TarFile::default();
+    /// data.append_with("test/1.txt".to_string(), &options).unwrap();
+    /// ```
+    pub fn append_with(
+        &mut self,
+        filename: String,
+        options: &ArchiveOptions,
+    ) -> Result<(), TarError> {
+        trace::span!("append", path = %filename);
+        if walk::is_excluded(&filename, &walk::compile_excludes(options)?) {
+            trace::event!(debug, "excluded");
+            return Ok(());
+        }
+
+        let file = &self.file;
+        let links = |key| TarNode::link_target(file, key);
+        match TarNode::read_file_to_tar(filename, options, &mut self.warnings, &links, false)? {
+            Some(node) => {
+                trace::event!(debug, name = %node.name(), size = node.data_len(), "added entry");
+                self.push_unique(node, options).map(drop)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the index in `nodes` of the first entry named `name`, or `None` if there is none. Later
+    /// entries with the same name replace it on extraction, so use `positions_of` to find them all.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.append("src/lib.rs".to_string()).unwrap();
+    /// assert_eq!(data.position_of("src/lib.rs"), Some(1));
+    /// assert_eq!(data.position_of("missing"), None);
+    /// ```
+    pub fn position_of(&self, name: &str) -> Option<usize> {
+        self.positions_of(name).next()
+    }
+
+    /// Returns the index in `nodes` of every entry named `name`, in archive order.
+    pub fn positions_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.file
+            .iter()
+            .enumerate()
+            .filter(move |(_, n)| n.journal_entry().is_none() && n.name() == name)
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the nodes of the archive, in archive order.
+    pub fn nodes(&self) -> &[TarNode] {
+        &self.file
+    }
+
+    /// Append an already built `TarNode`, such as one copied from another archive, as it is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{TarFile, TarNode};
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.push(TarNode::from_data("hello.txt".to_string(), b"hello").unwrap());
+    /// assert_eq!(data.list().len(), 2);
+    /// ```
+    pub fn push(&mut self, mut node: TarNode) {
+        /* The offsets of a node taken from another archive don't apply to this one */
+        node.location = None;
+        self.file.push(node);
+    }
+
+    /// Add the file `filename` only if the archive has no entry with that name, or if the file on disk is newer
+    /// than the stored entry, like ``tar -u``. A newer file replaces every stored entry with its name. Returns
+    /// `true` if the file was added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// assert!(!data.update("test/1.txt".to_string()).unwrap());
+    /// ```
+    pub fn update(&mut self, filename: String) -> Result<bool, TarError> {
+        self.update_with(filename, &ArchiveOptions::default())
+    }
+
+    /// Update the archive in the same way as `update`, using the provided `ArchiveOptions`.
+    pub fn update_with(
+        &mut self,
+        filename: String,
+        options: &ArchiveOptions,
+    ) -> Result<bool, TarError> {
+        /* Check the times before reading the file, so unchanged files are cheap to skip. Entries are found by
+         * the name the file would be stored under, which the options may have changed */
+        let mtime = file_metadata(&filename, options)?.st_mtime();
+        let name = stored_name(&filename, options);
+        let stored = self
+            .file
+            .iter()
+            .filter(|n| n.journal_entry().is_none() && n.name() == name)
+            .map(|n| n.mtime())
+            .max();
+        if stored.is_some_and(|stored| stored >= mtime) {
+            return Ok(false);
+        }
+
+        let file = &self.file;
+        let links = |key| TarNode::link_target(file, key);
+        let node = match TarNode::read_file_to_tar(
+            filename,
+            options,
+            &mut self.warnings,
+            &links,
+            false,
+        )? {
+            Some(n) => n,
+            None => return Ok(false),
+        };
+        self.remove_every(node.name());
+        self.file.push(node);
+        Ok(true)
+    }
+
+    /// Returns the warnings raised while building or reading the archive, such as truncated names or empty
+    /// numeric header fields.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// assert!(data.warnings().is_empty());
+    /// ```
+    pub fn warnings(&self) -> &[TarWarning] {
+        &self.warnings
     }
 
     /// Open and load an external tar file into the internal `TarFile` struct. This parses and loads up all the files
@@ -313,19 +1017,191 @@ impl TarFile {
     /// TarFile::open("test/1.tar".to_string()).unwrap();
     /// ```
     pub fn open(filename: String) -> Result<Self, TarError> {
-        let file = File::open(&filename)?;
-        let mut reader = BufReader::new(file);
-        let mut out = TarFile {
-            file: Vec::<TarNode>::new(),
-        };
+        TarFile::open_with(filename, &ReadOptions::default())
+    }
+
+    /// Open and load an external tar file in the same way as `open`, using the provided `ReadOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ReadOptions, TarFile};
+    ///
+    /// let options = ReadOptions::default().ignore_zeros(true);
+    /// TarFile::open_with("test/1.tar".to_string(), &options).unwrap();
+    /// ```
+    pub fn open_with(filename: String, options: &ReadOptions) -> Result<Self, TarError> {
+        trace::span!("open", path = %filename);
+        let archive = TarFile::open_file(File::open(&filename)?, options)?;
+        trace::event!(info, entries = archive.file.len(), "opened");
+        trace::warnings(&archive.warnings);
+        Ok(archive)
+    }
+
+    /// Read the archive in `file` in the way chosen by `options`.
+    fn open_file(file: File, options: &ReadOptions) -> Result<Self, TarError> {
+        #[cfg(feature = "mmap")]
+        if options.mmap {
+            return TarFile::open_mapped(file, options);
+        }
+        if options.lazy_data {
+            return TarFile::open_lazy(file, options);
+        }
+        TarFile::open_stream(BufReader::with_capacity(options.buffer_size, file), options)
+    }
 
-        while let Ok(t) = TarNode::read(&mut reader) {
-            out.file.push(t);
+    /// Read the headers of the archive in `file`, leaving the entry data in the file to be read on demand.
+    fn open_lazy(file: File, options: &ReadOptions) -> Result<Self, TarError> {
+        let file = Arc::new(file);
+        let source = lazy::Source::File(file.clone());
+        let mut input = timeout::TimedReader::new(
+            BufReader::with_capacity(options.buffer_size, &*file),
+            options,
+        );
+        match TarFile::read_lazy(&mut input, &source, options) {
+            Err(_) if input.timed_out => Err(TarError::Timeout),
+            result => result,
+        }
+    }
+
+    /// Map the archive in `file` into memory and read its headers from the mapping, leaving the entry data
+    /// to be borrowed from it.
+    #[cfg(feature = "mmap")]
+    fn open_mapped(file: File, options: &ReadOptions) -> Result<Self, TarError> {
+        /* SAFETY: the mapping is only read, and `ReadOptions::mmap` documents that the archive must not be
+         * changed while it is mapped */
+        let map = Arc::new(unsafe { memmap2::Mmap::map(&file)? });
+        let source = lazy::Source::Map(map.clone());
+        TarFile::read_lazy(std::io::Cursor::new(&map[..]), &source, options)
+    }
+
+    /// Load a tar archive from a stream, or anything else with a ``std::io::Read`` trait. Reads are limited
+    /// by the timeout and deadline of `options`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use minitar::tar::{ReadOptions, TarFile};
+    ///
+    /// let file = File::open("test/1.tar").unwrap();
+    /// TarFile::open_stream(file, &ReadOptions::default()).unwrap();
+    /// ```
+    pub fn open_stream<T: std::io::Read>(
+        input: T,
+        options: &ReadOptions,
+    ) -> Result<Self, TarError> {
+        TarFile::open_timed(timeout::TimedReader::new(input, options))
+    }
+
+    /// Load a tar archive from a TCP connection in the same way as `open_stream`. The timeouts of `options`
+    /// are applied to the socket, so a stalled peer fails the read with `TarError::Timeout` instead of
+    /// blocking forever.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::TcpStream;
+    /// use std::time::Duration;
+    /// use minitar::tar::{ReadOptions, TarFile};
+    ///
+    /// let stream = TcpStream::connect("127.0.0.1:9000").unwrap();
+    /// let options = ReadOptions::default()
+    ///     .timeout(Duration::from_secs(5))
+    ///     .deadline(Duration::from_secs(60));
+    /// TarFile::open_tcp(stream, &options).unwrap();
+    /// ```
+    pub fn open_tcp(stream: std::net::TcpStream, options: &ReadOptions) -> Result<Self, TarError> {
+        let socket = stream.try_clone()?;
+        let input = timeout::TimedReader::new(stream, options)
+            .on_limit(move |limit| socket.set_read_timeout(limit));
+        TarFile::open_timed(input)
+    }
+
+    /// Read an archive from `input`, reporting `TarError::Timeout` if a time limit ran out along the way.
+    fn open_timed<T: std::io::Read>(mut input: timeout::TimedReader<T>) -> Result<Self, TarError> {
+        let options = input.options.clone();
+        match TarFile::read_stream(&mut input, &options) {
+            Err(_) if input.timed_out => Err(TarError::Timeout),
+            result => result,
+        }
+    }
+
+    /// Parse every entry of `input`, recording where each one starts.
+    fn read_stream<T: std::io::Read>(input: T, options: &ReadOptions) -> Result<Self, TarError> {
+        let mut out = TarFile::default();
+
+        /* Input without any blocks is an empty archive */
+        let mut trailer = true;
+        let mut input = index::CountingReader::new(input);
+        while let Some(block) = next_header(&mut input, options, &mut trailer)? {
+            if cancel::cancelled(&options.cancel) {
+                return Err(TarError::Cancelled);
+            }
+            let header_offset = input.pos - 512;
+            let mut node = TarNode::read_with_header(&block, &mut input)?;
+            let data_offset = input.pos - (node.data.len().div_ceil(512) * 512) as u64;
+            node.location = Some((header_offset, data_offset));
+            out.push_read(node, options)?;
+        }
+
+        Ok(out)
+    }
+
+    /// Parse every entry of `input`, the contents of `source`, in the same way as `read_stream` but skipping
+    /// over the data that `defers_data` allows to be read later.
+    fn read_lazy<T: std::io::Read + std::io::Seek>(
+        input: T,
+        source: &lazy::Source,
+        options: &ReadOptions,
+    ) -> Result<Self, TarError> {
+        let mut out = TarFile::default();
+        let len = source.len()?;
+
+        let mut trailer = true;
+        let mut input = index::CountingReader::new(input);
+        while let Some(block) = next_header(&mut input, options, &mut trailer)? {
+            if cancel::cancelled(&options.cancel) {
+                return Err(TarError::Cancelled);
+            }
+            let header_offset = input.pos - 512;
+            let mut node = TarNode::read_with_records(&block, &mut input, Vec::new(), false)?;
+            if defers_data(node.file_type()) {
+                let size = node.size();
+                let blocks = (size.div_ceil(512) * 512) as u64;
+                if input.pos + blocks > len {
+                    return Err(TarError::Truncated);
+                }
+                node.lazy = Some(lazy::LazyData::new(source.clone(), input.pos, size));
+                input.skip(blocks)?;
+            }
+            let data_offset = input.pos - (node.data_len().div_ceil(512) * 512) as u64;
+            node.location = Some((header_offset, data_offset));
+            out.push_read(node, options)?;
         }
 
         Ok(out)
     }
 
+    /// Check an entry just read from an archive against `options`, then add it.
+    fn push_read(&mut self, node: TarNode, options: &ReadOptions) -> Result<(), TarError> {
+        if let Some(verifier) = &options.verifier {
+            if node.journal_entry().is_none() {
+                node.verify(verifier.as_ref())?;
+            }
+        }
+        options.profile.check(&node)?;
+        trace::event!(debug, name = %node.name(), size = node.size(), "read entry");
+        for field in node.blank_fields() {
+            self.warnings.push(TarWarning::EmptyField {
+                name: node.name(),
+                field: field.to_string(),
+            });
+        }
+        self.file.push(node);
+        Ok(())
+    }
+
     /// Remove the first file from the Tar that matches the filename and path.
     ///
     /// # Example
@@ -337,8 +1213,11 @@ impl TarFile {
     /// data.remove("test/1.tar".to_string()).unwrap();
     /// ```
     pub fn remove(&mut self, filename: String) -> Result<bool, TarError> {
+        /* A name too long for the field can't match any header */
         let mut name = [0u8; 100];
-        name[..filename.len()].copy_from_slice(filename.as_bytes());
+        if set_field(&mut name, filename.as_bytes()).is_err() {
+            return Ok(false);
+        }
         if let Some(i) = &self.file.iter().position(|x| x.header.file_name == name) {
             self.file.remove(*i);
             return Ok(true);
@@ -346,6 +1225,131 @@ impl TarFile {
 
         Ok(false)
     }
+
+    /// Remove every entry whose name matches the glob `pattern`, such as ``*.log`` or ``build/**``, and return
+    /// how many were removed. ``*`` also matches ``/``, as with GNU tar's ``--wildcards``.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// assert_eq!(data.remove_all("*.txt".to_string()).unwrap(), 1);
+    /// ```
+    pub fn remove_all(&mut self, pattern: String) -> Result<usize, TarError> {
+        let pattern = walk::compile_pattern(&pattern)?;
+        let len = self.file.len();
+        self.file
+            .retain(|n| n.journal_entry().is_some() || !pattern.matches(&n.name()));
+        Ok(len - self.file.len())
+    }
+
+    /// Remove every entry named exactly `filename`, including duplicates, and return how many were removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.append("test/1.txt".to_string()).unwrap();
+    /// assert_eq!(data.remove_every("test/1.txt".to_string()), 2);
+    /// ```
+    pub fn remove_every(&mut self, filename: String) -> usize {
+        let len = self.file.len();
+        self.file
+            .retain(|n| n.journal_entry().is_some() || n.name() != filename);
+        len - self.file.len()
+    }
+
+    /// Keep only the entries for which `keep` returns `true`, in their original order. Journal records are
+    /// always kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.retain(|entry| entry.size < 16);
+    /// assert!(data.list().is_empty());
+    /// ```
+    pub fn retain<F: FnMut(&EntryInfo) -> bool>(&mut self, mut keep: F) {
+        /* Entries are visited in order, so each is passed the index it had before any were removed */
+        let mut index = 0;
+        self.file.retain(|n| {
+            index += 1;
+            n.journal_entry().is_some() || keep(&n.info_at(index - 1))
+        });
+    }
+
+    /// Reorder the entries with the comparator `compare`, independent of the order they were added in.
+    /// The sort is stable, and journal records stay where they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.append("src/lib.rs".to_string()).unwrap();
+    /// data.sort_by(|a, b| a.size.cmp(&b.size));
+    /// assert_eq!(data.list()[0].name, "test/1.txt");
+    /// ```
+    pub fn sort_by<F: FnMut(&EntryInfo, &EntryInfo) -> std::cmp::Ordering>(
+        &mut self,
+        mut compare: F,
+    ) {
+        let slots: Vec<usize> = (0..self.file.len())
+            .filter(|&i| self.file[i].journal_entry().is_none())
+            .collect();
+        let mut entries: Vec<(EntryInfo, TarNode)> = slots
+            .iter()
+            .map(|&i| (self.file[i].info_at(i), self.file[i].clone()))
+            .collect();
+        entries.sort_by(|a, b| compare(&a.0, &b.0));
+
+        for (i, (_, node)) in slots.into_iter().zip(entries) {
+            self.file[i] = node;
+        }
+    }
+
+    /// Sort the entries by name, so archives list in the same order however they were built. Placing
+    /// related files next to each other also tends to help compression.
+    pub fn sort_by_name(&mut self) {
+        self.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+impl Extend<TarNode> for TarFile {
+    /// Add each node in the same way as `push`.
+    fn extend<I: IntoIterator<Item = TarNode>>(&mut self, iter: I) {
+        for node in iter {
+            self.push(node);
+        }
+    }
+}
+
+impl FromIterator<TarNode> for TarFile {
+    /// Collect nodes into a new archive, in iteration order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{TarFile, TarNode};
+    ///
+    /// let data: TarFile = ["a.txt", "b.txt"]
+    ///     .iter()
+    ///     .map(|n| TarNode::from_data(n.to_string(), b"hello").unwrap())
+    ///     .collect();
+    /// assert_eq!(data.list().len(), 2);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = TarNode>>(iter: I) -> Self {
+        let mut out = TarFile::default();
+        out.extend(iter);
+        out
+    }
 }
 
 fn get_file_type(meta: &Metadata) -> u8 {
@@ -360,8 +1364,6 @@ fn get_file_type(meta: &Metadata) -> u8 {
         return FileType::Char as u8;
     } else if file_type.is_block_device() {
         return FileType::Block as u8;
-    } else if file_type.is_fifo() {
-        return FileType::FIFO as u8;
     } else if file_type.is_symlink() {
         return FileType::Sym as u8;
     } else if file_type.is_file() {
@@ -371,41 +1373,231 @@ fn get_file_type(meta: &Metadata) -> u8 {
     FileType::Unknown as u8
 }
 
-fn generate_header(filename: &String) -> Result<TarHeader, TarError> {
+/// Returns `true` for file types that are not regular files, directories or links.
+fn is_special(file_type: u8) -> bool {
+    file_type == FileType::FIFO as u8
+        || file_type == FileType::Char as u8
+        || file_type == FileType::Block as u8
+        || file_type == FileType::Unknown as u8
+}
+
+/// Returns `true` if entries of `file_type` are extracted as regular files. Old archives use a NUL type
+/// flag for regular files, and old GNU sparse files have their own.
+fn is_regular(file_type: u8) -> bool {
+    file_type == FileType::Normal as u8
+        || file_type == FileType::Unknown as u8
+        || file_type == FileType::GnuSparse as u8
+}
+
+/// Returns the number of zeros that pad `len` bytes of data to a whole block.
+fn padding(len: usize) -> usize {
+    (512 - len % 512) % 512
+}
+
+/// Returns `false` if entries of `file_type` never have data blocks, whatever their size field says. Links,
+/// directories and special files are written with a size of 0 today, but older writers, including
+/// minitar 0.2, stored the size of the directory or link itself, which readers must skip no data for.
+fn carries_data(file_type: u8) -> bool {
+    ![
+        FileType::Hard,
+        FileType::Sym,
+        FileType::Char,
+        FileType::Block,
+        FileType::Dir,
+        FileType::FIFO,
+    ]
+    .iter()
+    .any(|&t| t as u8 == file_type)
+}
+
+/// Returns whether the data of entries of `file_type` can be left in the archive until it is needed.
+/// Extension headers must be decoded to read the entry they describe, and old GNU sparse entries start with
+/// extension blocks of their own, so those are always read.
+fn defers_data(file_type: u8) -> bool {
+    carries_data(file_type) && !is_extension(file_type) && file_type != FileType::GnuSparse as u8
+}
+
+/// Returns the metadata of `filename`, describing the file a symbolic link points to rather than the link
+/// itself if `options` dereference links.
+pub(crate) fn file_metadata(filename: &str, options: &ArchiveOptions) -> std::io::Result<Metadata> {
+    match options.dereference {
+        true => fs::metadata(filename),
+        false => fs::symlink_metadata(filename),
+    }
+}
+
+/// Returns the name `filename` is given by the transform and sanitizing of `options`, along with the name
+/// before sanitizing if that changed it.
+fn archive_name(filename: &str, options: &ArchiveOptions) -> (String, Option<String>) {
+    let name = match &options.transform {
+        Some(t) => t.apply(filename),
+        None => filename.to_string(),
+    };
+    match options.sanitize.then(|| names::sanitize(&name)).flatten() {
+        Some(safe) => (safe, Some(name)),
+        None => (name, None),
+    }
+}
+
+/// Returns the name the entry read from `filename` with `options` is listed under, for finding it in an
+/// archive.
+fn stored_name(filename: &str, options: &ArchiveOptions) -> String {
+    let (name, _) = archive_name(filename, options);
+    match names::fit_name(&name, 100, "path", options.long_names, &mut Vec::new()) {
+        Ok((short, None)) => short,
+        _ => name,
+    }
+}
+
+fn generate_header(
+    filename: &str,
+    options: &ArchiveOptions,
+    warnings: &mut Vec<TarWarning>,
+) -> Result<(TarHeader, Vec<PaxRecord>), TarError> {
     let mut head = TarHeader::default();
-    let meta = fs::symlink_metadata(&filename)?;
+    let mut pax = Vec::new();
+    let meta = file_metadata(filename, options)?;
 
     /* Fill in metadata */
-    head.file_name[..filename.len()].copy_from_slice(filename.as_bytes());
-    let mode = format!("{:07o}", (meta.st_mode() & 0o777));
-    head.file_mode[..mode.len()].copy_from_slice(mode.as_bytes());
-    let user = format!("{:07o}", meta.st_uid());
-    head.own_user[..user.len()].copy_from_slice(user.as_bytes());
-    let group = format!("{:07o}", meta.st_gid());
-    head.own_group[..group.len()].copy_from_slice(group.as_bytes());
-    let size = format!("{:011o}", meta.st_size());
-    head.file_size[..size.len()].copy_from_slice(size.as_bytes());
-    let mtime = format!("{:011o}", meta.st_mtime());
-    head.mod_time[..mtime.len()].copy_from_slice(mtime.as_bytes());
-
-    /* Get the file type and conditional metadata */
-    head.link_indicator[0] = get_file_type(&meta);
+    let (stored, original) = archive_name(filename, options);
+    if let Some(original) = original {
+        warnings.push(TarWarning::NameSanitized {
+            name: original.clone(),
+            stored: stored.clone(),
+        });
+        pax.push(PaxRecord::new("MINITAR.original_path", &original));
+    }
+    let (name, record) = names::fit_name(&stored, 100, "path", options.long_names, warnings)?;
+    set_field(&mut head.file_name, name.as_bytes())?;
+    pax.extend(record);
+    let file_type = get_file_type(&meta);
+    let (mut mode, mut uid, mut gid) = (meta.st_mode() & 0o777, meta.st_uid(), meta.st_gid());
+    let mut mtime = meta.st_mtime();
+    let recorded = mtime;
+    if options.reproducible {
+        /* Keep only whether the entry is executable, since umasks differ between machines */
+        mode = match file_type {
+            t if t == FileType::Sym as u8 => 0o777,
+            t if t == FileType::Dir as u8 || mode & 0o111 != 0 => 0o755,
+            _ => 0o644,
+        };
+        uid = 0;
+        gid = 0;
+        if let Some(epoch) = options.source_date_epoch {
+            mtime = mtime.min(epoch.min(i64::MAX as u64) as i64);
+        }
+    }
+    /* Forced metadata applies after normalization, so it wins over reproducible defaults */
+    mode = options.mode.unwrap_or(mode);
+    uid = options.uid.unwrap_or(uid);
+    gid = options.gid.unwrap_or(gid);
+    mtime = options.mtime.unwrap_or(mtime);
+    let nul = OctalTerminator::Nul;
+    dec_to_oct(mode as u64, &mut head.file_mode, nul)?;
+    for (key, id, field) in [
+        ("uid", uid as u64, &mut head.own_user),
+        ("gid", gid as u64, &mut head.own_group),
+    ] {
+        /* Ids beyond the octal range are stored in base-256 for GNU tar, and in a PAX record for the rest */
+        if dec_to_oct(id, field, nul).is_err() {
+            dec_to_base256(id, field)?;
+            pax.push(PaxRecord::new(key, id.to_string()));
+        }
+    }
+    /* The fraction only belongs to the file's own time, not one that was clamped or forced */
+    let nanos = match options.precise_mtime && mtime == recorded {
+        true => meta.st_mtime_nsec() as u32,
+        false => 0,
+    };
+    if !set_time(mtime, &mut head.mod_time)? || nanos != 0 {
+        pax.push(PaxRecord::new("mtime", pax::format_time(mtime, nanos)));
+    }
+
+    /* Get the file type and conditional metadata. Only regular files carry data, so the size of anything
+     * else, such as a directory, is not stored */
+    head.link_indicator[0] = file_type;
+    let size = if head.link_indicator[0] == FileType::Normal as u8 {
+        meta.st_size()
+    } else {
+        0
+    };
+    dec_to_oct(size, &mut head.file_size, nul)?;
     if head.link_indicator[0] == FileType::Sym as u8 {
-        let link = fs::read_link(&filename)?.to_str().unwrap().to_string();
-        head.link_name[..link.len()].copy_from_slice(link.as_bytes());
-    } else if head.link_indicator[0] == FileType::Block as u8 {
-        let major = format!("{:07o}", meta.st_dev());
-        head.device_major[..major.len()].copy_from_slice(major.as_bytes());
-        let minor = format!("{:07o}", meta.st_rdev());
-        head.device_minor[..minor.len()].copy_from_slice(minor.as_bytes());
+        let link = fs::read_link(filename)?.to_string_lossy().to_string();
+        let (link, record) = names::fit_name(&link, 100, "linkpath", options.long_names, warnings)?;
+        set_field(&mut head.link_name, link.as_bytes())?;
+        pax.extend(record);
+    } else if file_type == FileType::Block as u8 || file_type == FileType::Char as u8 {
+        /* The device is identified by the file's own ``st_rdev``; ``st_dev`` is the filesystem it is on */
+        let rdev = meta.st_rdev() as libc::dev_t;
+        dec_to_oct(libc::major(rdev) as u64, &mut head.device_major, nul)?;
+        dec_to_oct(libc::minor(rdev) as u64, &mut head.device_minor, nul)?;
+    }
+
+    /* Resolve the owner names from the stored ids rather than the environment, which may belong to someone
+     * else. Reproducible archives leave them empty unless they were given */
+    let lookup = !options.reproducible;
+    let user = options
+        .uname
+        .clone()
+        .or_else(|| lookup.then(|| options.resolver.user_name(uid))?);
+    if let Some(val) = user {
+        let (user, record) = names::fit_name(&val, 32, "uname", LongNamePolicy::Pax, warnings)?;
+        set_field(&mut head.own_user_name, user.as_bytes())?;
+        pax.extend(record);
+    }
+    let group = options
+        .gname
+        .clone()
+        .or_else(|| lookup.then(|| options.resolver.group_name(gid))?);
+    if let Some(val) = group {
+        let (group, record) = names::fit_name(&val, 32, "gname", LongNamePolicy::Pax, warnings)?;
+        set_field(&mut head.own_group_name, group.as_bytes())?;
+        pax.extend(record);
+    }
+
+    /* Set USTAR magic and version info */
+    head.ustar_magic = [0x75, 0x73, 0x74, 0x61, 0x72, 0x20];
+    head.ustar_version = [0x20, 0x00];
+    head.header_checksum = [0x20; 8];
+
+    /* Update the header checksum value */
+    head.update_checksum()?;
+
+    if options.xattrs {
+        pax.extend(xattr::records(filename, options.dereference)?);
+    } else if options.capabilities {
+        pax.extend(xattr::capability_record(filename, options.dereference)?);
     }
+    Ok((head, pax))
+}
 
-    /* TODO: Find better way to get username */
-    let key = "USER";
-    if let Ok(val) = env::var(key) {
-        head.own_user_name[..val.len()].copy_from_slice(val.as_bytes())
+/// Encode the time `mtime` into the header field `field`, in octal digits when it fits and in base-256 for
+/// times before 1970 or after 2242. Returns whether octal was used, as readers other than GNU tar need a
+/// PAX ``mtime`` record for anything else.
+fn set_time(mtime: i64, field: &mut [u8]) -> Result<bool, TarError> {
+    match mtime >= 0 && dec_to_oct(mtime as u64, field, OctalTerminator::Nul).is_ok() {
+        true => Ok(true),
+        false => signed_to_base256(mtime, field).map(|_| false),
     }
-    /* TODO: Find way to get groupname */
+}
+
+/// Build a header for an entry that does not exist on the filesystem.
+fn synthetic_header(
+    filename: &str,
+    file_type: u8,
+    size: usize,
+    mtime: i64,
+) -> Result<TarHeader, TarError> {
+    let mut head = TarHeader::default();
+    set_field(&mut head.file_name, filename.as_bytes()).map_err(|_| TarError::NameTooLong)?;
+    let nul = OctalTerminator::Nul;
+    dec_to_oct(0o644, &mut head.file_mode, nul)?;
+    dec_to_oct(0, &mut head.own_user, nul)?;
+    dec_to_oct(0, &mut head.own_group, nul)?;
+    dec_to_oct(size as u64, &mut head.file_size, nul)?;
+    set_time(mtime, &mut head.mod_time)?;
+    head.link_indicator[0] = file_type;
 
     /* Set USTAR magic and version info */
     head.ustar_magic = [0x75, 0x73, 0x74, 0x61, 0x72, 0x20];
@@ -418,11 +1610,170 @@ fn generate_header(filename: &String) -> Result<TarHeader, TarError> {
     Ok(head)
 }
 
-fn oct_to_dec(input: &[u8]) -> Result<usize, TarError> {
-    /* Convert the &[u8] to string and remove the null byte */
-    let mut s = str::from_utf8(input)?.to_string();
-    s.pop();
+/// Write a GNU ``L`` or ``K`` header carrying the long `name` for the entry that follows. Returns the number
+/// of bytes written.
+fn write_gnu_long_name<T: std::io::Write>(
+    output: &mut T,
+    name: &str,
+    file_type: FileType,
+) -> Result<usize, TarError> {
+    /* GNU tar counts the terminating NUL in the size */
+    let mut data = name.as_bytes().to_vec();
+    data.push(0);
+    let header = synthetic_header("././@LongLink", file_type as u8, data.len(), 0)?;
+    output.write_all(&header.to_bytes()?)?;
+
+    let mut written = 512;
+    for chunk in data.chunks(512) {
+        let mut block = [0u8; 512];
+        set_field(&mut block, chunk)?;
+        output.write_all(&block)?;
+        written += block.len();
+    }
+    Ok(written)
+}
+
+/// Complete an archive of `written` bytes with 2 blocks of 512 ``0x00`` bytes per the specification, then pad
+/// out the final record of ``blocking_factor`` blocks. Returns the number of bytes written.
+fn write_trailer<T: std::io::Write>(
+    output: &mut T,
+    written: usize,
+    options: &WriteOptions,
+) -> Result<usize, TarError> {
+    let record = options.blocking_factor.max(1) * 512;
+    let padding = 1024 + (record - (written + 1024) % record) % record;
+    output.write_all(&vec![0; padding])?;
+    Ok(padding)
+}
+
+/// Write all of `bufs` to `output`, handing them over together so writers that support it, such as files
+/// and sockets, take them in one system call. Stands in for the unstable ``Write::write_all_vectored``.
+fn write_all_vectored<T: std::io::Write>(
+    output: &mut T,
+    mut bufs: &mut [IoSlice<'_>],
+) -> Result<(), TarError> {
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match output.write_vectored(bufs) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Read a single ``512`` byte block, returning `false` if the input ended before the block started.
+fn read_block<T: std::io::Read>(input: &mut T, block: &mut [u8; 512]) -> Result<bool, TarError> {
+    let mut len = 0;
+    while len < block.len() {
+        match input.read(&mut block[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match len {
+        0 => Ok(false),
+        512 => Ok(true),
+        _ => Err(TarError::Truncated),
+    }
+}
+
+/// Read the next header block of an archive, returning `None` once the zero blocks that end the archive are
+/// reached. `trailer` tracks whether the input may validly end at the current position.
+fn next_header<T: std::io::Read>(
+    input: &mut T,
+    options: &ReadOptions,
+    trailer: &mut bool,
+) -> Result<Option<[u8; 512]>, TarError> {
+    let mut block = [0u8; 512];
+    loop {
+        if !read_block(input, &mut block)? {
+            if *trailer {
+                return Ok(None);
+            }
+            return Err(TarError::Truncated);
+        }
+
+        if block != [0u8; 512] {
+            *trailer = false;
+            return Ok(Some(block));
+        }
+        *trailer = true;
+
+        /* Zero blocks separate concatenated archives, so keep going until the real end */
+        if options.ignore_zeros {
+            continue;
+        }
+
+        /* The archive ends with two zero blocks, though a lone zero block at the end of the input is
+         * tolerated */
+        if !read_block(input, &mut block)? || block == [0u8; 512] {
+            return Ok(None);
+        }
+        *trailer = false;
+        return Ok(Some(block));
+    }
+}
+
+/// Read the next entry header of an archive along with the PAX records that apply to it, leaving `input`
+/// positioned at the start of the entry data. The returned node has no data beyond any old GNU sparse
+/// extension blocks, which are read to count them and then left in `input` as well.
+fn next_entry_header<T: std::io::Read + std::io::Seek>(
+    input: &mut T,
+    options: &ReadOptions,
+    trailer: &mut bool,
+) -> Result<Option<TarNode>, TarError> {
+    let mut pax = Vec::new();
+    while let Some(block) = next_header(input, options, trailer)? {
+        let header = TarHeader::from_block(&block)?;
+
+        /* PAX and GNU long name records are needed to name the following entry, so they are read rather
+         * than skipped */
+        if is_extension(header.link_indicator[0]) {
+            let bytes = TarNode::read_data(input, Some(oct_to_dec(&header.file_size)?))?;
+            pax.extend(extension_records(&header, &bytes)?.unwrap_or_default());
+            continue;
+        }
+
+        let data = sparse::read_gnu_extensions(&header, input)?;
+        input.seek(std::io::SeekFrom::Current(-(data.len() as i64)))?;
+        return Ok(Some(TarNode {
+            header,
+            pax,
+            data,
+            ..Default::default()
+        }));
+    }
+
+    /* PAX records without an entry to apply to */
+    if !pax.is_empty() {
+        return Err(TarError::Truncated);
+    }
+    Ok(None)
+}
+
+/// Returns whether `file_type` marks a header describing the entry that follows it rather than an entry.
+fn is_extension(file_type: u8) -> bool {
+    file_type == FileType::Pax as u8
+        || file_type == FileType::GnuLongName as u8
+        || file_type == FileType::GnuLongLink as u8
+}
+
+/// Decode the `data` of an extension header into the PAX records it amounts to, with GNU long names mapped
+/// to ``path`` and ``linkpath`` records. Returns `None` if `header` is not an extension header.
+fn extension_records(header: &TarHeader, data: &[u8]) -> Result<Option<Vec<PaxRecord>>, TarError> {
+    let key = match header.link_indicator[0] {
+        t if t == FileType::Pax as u8 => return Ok(Some(pax::decode_records(data)?)),
+        t if t == FileType::GnuLongName as u8 => "path",
+        t if t == FileType::GnuLongLink as u8 => "linkpath",
+        _ => return Ok(None),
+    };
 
-    /* Convert to usize from octal */
-    Ok(usize::from_str_radix(&s, 8)?)
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    Ok(Some(vec![PaxRecord::new(key, &data[..end])]))
 }
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::error::{TarError, TarWarning};
+use crate::tar::*;
+
+/// Writes an archive entry by entry as it is built, without holding it in memory. Each entry is complete
+/// before the next one can start, and `finish` must be called to write the end of the archive. Dropping a
+/// builder without finishing it fails a debug assertion, unless one of its methods returned an error.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use minitar::tar::TarBuilder;
+///
+/// let mut builder = TarBuilder::new(Vec::new());
+/// builder.append_file("test/1.txt".to_string()).unwrap();
+///
+/// let mut entry = builder.start_entry("hello.txt".to_string(), 5).unwrap();
+/// entry.write_all(b"hello").unwrap();
+/// let builder = entry.finish().unwrap();
+///
+/// let archive = builder.finish().unwrap();
+/// assert_eq!(archive.len(), 10240);
+/// ```
+#[must_use = "the archive is incomplete until `finish` is called"]
+#[derive(Debug)]
+pub struct TarBuilder<W: Write> {
+    output: W,
+    written: usize,
+    options: WriteOptions,
+    archive: ArchiveOptions,
+    warnings: Vec<TarWarning>,
+    /* The entry names of files with several hard links, by device and inode */
+    links: HashMap<(u64, u64), String>,
+    throttle: throttle::Throttle,
+    guard: FinishGuard,
+}
+
+/// Writes the data of an entry started with `TarBuilder::start_entry`. Exactly the declared number of bytes
+/// must be written before calling `finish` to get the builder back.
+#[must_use = "the entry is incomplete until `finish` is called"]
+#[derive(Debug)]
+pub struct EntryWriter<W: Write> {
+    builder: TarBuilder<W>,
+    name: String,
+    remaining: usize,
+}
+
+impl<W: Write> TarBuilder<W> {
+    /// Create a builder writing to `output` with the default options.
+    pub fn new(output: W) -> Self {
+        TarBuilder::with_options(output, WriteOptions::default(), ArchiveOptions::default())
+    }
+
+    /// Create a builder writing to `output`, serializing with `options` and adding files from disk with
+    /// `archive`.
+    pub fn with_options(output: W, options: WriteOptions, archive: ArchiveOptions) -> Self {
+        TarBuilder {
+            output,
+            written: 0,
+            throttle: throttle::Throttle::new(options.rate_limit),
+            options,
+            archive,
+            warnings: Vec::new(),
+            links: HashMap::new(),
+            guard: FinishGuard { armed: true },
+        }
+    }
+
+    /// Read the file `filename` from disk and write it to the archive, unless it matches an exclude pattern.
+    pub fn append_file(&mut self, filename: String) -> Result<(), TarError> {
+        let result = self.add_file(filename);
+        self.guard.check(result)
+    }
+
+    fn add_file(&mut self, filename: String) -> Result<(), TarError> {
+        if walk::is_excluded(&filename, &walk::compile_excludes(&self.archive)?) {
+            return Ok(());
+        }
+        let links = &self.links;
+        let node = TarNode::read_file_to_tar(
+            filename,
+            &self.archive,
+            &mut self.warnings,
+            &|key| links.get(&key).cloned(),
+            false,
+        )?;
+        if let Some(node) = node {
+            self.append_node(&node)?;
+            if let (Some(key), true) = (node.inode, node.file_type() == FileType::Normal as u8) {
+                self.links.entry(key).or_insert_with(|| node.name());
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a complete `TarNode`, such as one copied from another archive, to the archive.
+    pub fn append_node(&mut self, node: &TarNode) -> Result<(), TarError> {
+        let output = self.throttle.writer(&mut self.output);
+        let result = node.clone().write_with(output, &self.options);
+        self.written += self.guard.check(result)?;
+        Ok(())
+    }
+
+    /// Write the header of a regular file named `name` holding `size` bytes, and return an `EntryWriter`
+    /// for its data.
+    pub fn start_entry(mut self, name: String, size: usize) -> Result<EntryWriter<W>, TarError> {
+        let result = synthetic_header(&name, FileType::Normal as u8, size, 0)
+            .and_then(|header| Ok(self.output.write_all(&header.to_bytes()?)?));
+        self.guard.check(result)?;
+        self.written += 512;
+
+        Ok(EntryWriter {
+            builder: self,
+            name,
+            remaining: size,
+        })
+    }
+
+    /// Returns the warnings raised while adding files from disk.
+    pub fn warnings(&self) -> &[TarWarning] {
+        &self.warnings
+    }
+
+    /// Write the end of the archive and return the underlying writer.
+    pub fn finish(mut self) -> Result<W, TarError> {
+        self.guard.armed = false;
+        if self.written > 0 {
+            write_trailer(&mut self.output, self.written, &self.options)?;
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+impl<W: Write> EntryWriter<W> {
+    /// Complete the entry, padding its data to a whole block, and return the builder. Fails with
+    /// `TarError::IncompleteEntry` if fewer bytes were written than were declared.
+    pub fn finish(mut self) -> Result<TarBuilder<W>, TarError> {
+        if self.remaining > 0 {
+            /* The error already reports the broken archive */
+            self.builder.guard.armed = false;
+            return Err(TarError::IncompleteEntry(self.name));
+        }
+
+        let padding = (512 - self.builder.written % 512) % 512;
+        let result = self.builder.output.write_all(&vec![0; padding]);
+        self.builder.guard.check(result.map_err(TarError::from))?;
+        self.builder.written += padding;
+        Ok(self.builder)
+    }
+}
+
+impl<W: Write> Write for EntryWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() > self.remaining {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("more data than the declared size of {}", self.name),
+            ));
+        }
+
+        let builder = &mut self.builder;
+        let n = builder.throttle.writer(&mut builder.output).write(buf)?;
+        self.remaining -= n;
+        self.builder.written += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.builder.output.flush()
+    }
+}
+
+/// Catches builders that are dropped before the end of the archive was written.
+#[derive(Debug)]
+struct FinishGuard {
+    armed: bool,
+}
+
+impl FinishGuard {
+    /// Pass `result` through, disarming the guard if it is an error, as the error already reports the
+    /// unfinished archive to whoever drops the builder.
+    fn check<T>(&mut self, result: Result<T, TarError>) -> Result<T, TarError> {
+        if result.is_err() {
+            self.armed = false;
+        }
+        result
+    }
+}
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        /* Don't turn an unrelated panic into an abort */
+        if !std::thread::panicking() {
+            debug_assert!(
+                !self.armed,
+                "TarBuilder dropped without calling finish, the archive is truncated"
+            );
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag for stopping a long operation from another thread, such as a signal handler or a UI. Clones
+/// share the flag, so one can be passed in the options of an operation while another is kept to cancel
+/// it. The operation checks the flag before each entry and fails with `TarError::Cancelled` once it is
+/// set.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{CancelToken, ReadOptions, TarFile};
+///
+/// let token = CancelToken::new();
+/// let options = ReadOptions::default().cancel(token.clone());
+/// token.cancel();
+/// assert!(TarFile::open_with("test/1.tar".to_string(), &options).is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token that has not been cancelled.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Cancel the operations using this token or any clone of it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` once the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns `true` if the operation given `token` in its options should stop.
+pub(crate) fn cancelled(token: &Option<CancelToken>) -> bool {
+    token.as_ref().is_some_and(|t| t.is_cancelled())
+}
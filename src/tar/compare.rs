@@ -0,0 +1,182 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A way in which an entry of an archive differs from the filesystem.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// The entry does not exist on disk.
+    Missing(String),
+    /// The entry exists on disk as a different type of file.
+    Type(String),
+    /// The file sizes differ.
+    Size {
+        name: String,
+        archive: u64,
+        disk: u64,
+    },
+    /// The permission bits differ.
+    Mode {
+        name: String,
+        archive: u32,
+        disk: u32,
+    },
+    /// The modification times differ.
+    Mtime {
+        name: String,
+        archive: i64,
+        disk: i64,
+    },
+    /// The targets of a symbolic link differ.
+    LinkTarget {
+        name: String,
+        archive: String,
+        disk: String,
+    },
+    /// The file contents differ.
+    Contents(String),
+}
+
+/// Options controlling how an archive is compared with the filesystem.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::CompareOptions;
+///
+/// let options = CompareOptions::default().contents(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CompareOptions {
+    pub(crate) contents: bool,
+}
+
+impl CompareOptions {
+    /// Also compare the contents of regular files whose sizes match. This reads every file in full.
+    pub fn contents(mut self, compare: bool) -> Self {
+        self.contents = compare;
+        self
+    }
+}
+
+impl TarFile {
+    /// Check each entry of the archive against the corresponding path under `root`, and return the
+    /// differences in archive order. Journal records are not compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// assert!(data.compare(".".to_string()).unwrap().is_empty());
+    /// ```
+    pub fn compare(&self, root: String) -> Result<Vec<Difference>, TarError> {
+        self.compare_with(root, &CompareOptions::default())
+    }
+
+    /// Compare the archive with the filesystem in the same way as `compare`, using the provided
+    /// `CompareOptions`.
+    pub fn compare_with(
+        &self,
+        root: String,
+        options: &CompareOptions,
+    ) -> Result<Vec<Difference>, TarError> {
+        let root = Path::new(&root);
+        let mut out = Vec::new();
+
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            let path = extract::safe_path(root, &node.name())?;
+            node.compare_to(&path, options, &mut out)?;
+        }
+
+        Ok(out)
+    }
+}
+
+impl TarNode {
+    /// Compare this node with the filesystem object at `path`, appending any differences to `out`.
+    fn compare_to(
+        &self,
+        path: &Path,
+        options: &CompareOptions,
+        out: &mut Vec<Difference>,
+    ) -> Result<(), TarError> {
+        let info = self.info();
+        let name = info.name.clone();
+        let meta = match fs::symlink_metadata(path) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                out.push(Difference::Missing(name));
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let disk_type = get_file_type(&meta);
+        let same_type = match info.file_type {
+            FileType::Normal | FileType::GnuSparse => disk_type == FileType::Normal as u8,
+            /* Hard links are regular files on disk */
+            FileType::Hard => disk_type == FileType::Normal as u8,
+            t => disk_type == t as u8,
+        };
+        if !same_type {
+            out.push(Difference::Type(name));
+            return Ok(());
+        }
+
+        let (archive_mode, disk_mode) = (info.mode & 0o7777, meta.mode() & 0o7777);
+        if archive_mode != disk_mode {
+            out.push(Difference::Mode {
+                name: name.clone(),
+                archive: archive_mode,
+                disk: disk_mode,
+            });
+        }
+        if info.mtime != meta.mtime() {
+            out.push(Difference::Mtime {
+                name: name.clone(),
+                archive: info.mtime,
+                disk: meta.mtime(),
+            });
+        }
+
+        match info.file_type {
+            FileType::Sym => {
+                let disk = fs::read_link(path)?.to_string_lossy().to_string();
+                let archive = info.link_target.unwrap_or_default();
+                if archive != disk {
+                    out.push(Difference::LinkTarget {
+                        name,
+                        archive,
+                        disk,
+                    });
+                }
+            }
+            FileType::Normal | FileType::GnuSparse if meta.len() != info.size => {
+                out.push(Difference::Size {
+                    name,
+                    archive: info.size,
+                    disk: meta.len(),
+                });
+            }
+            FileType::Normal | FileType::GnuSparse if options.contents => {
+                let mut archive = Vec::with_capacity(info.size as usize);
+                self.write_data(&mut archive)?;
+                let mut disk = Vec::with_capacity(archive.len());
+                BufReader::new(File::open(path)?).read_to_end(&mut disk)?;
+                if archive != disk {
+                    out.push(Difference::Contents(name));
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::rc::Rc;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A compression format wrapping a whole archive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    /// A plain ``.tar`` archive.
+    None,
+    /// A gzip compressed ``.tar.gz`` or ``.tgz`` archive. Requires the ``gzip`` feature.
+    Gzip,
+    /// A zstd compressed ``.tar.zst`` or ``.tzst`` archive. Requires the ``zstd`` feature.
+    Zstd,
+}
+
+impl Compression {
+    /// Infer the compression from the extension of `path`, falling back to `Compression::None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::Compression;
+    ///
+    /// assert_eq!(Compression::from_path("release.tar.gz"), Compression::Gzip);
+    /// assert_eq!(Compression::from_path("release.tar"), Compression::None);
+    /// ```
+    pub fn from_path(path: &str) -> Compression {
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".gz") || path.ends_with(".tgz") {
+            Compression::Gzip
+        } else if path.ends_with(".zst") || path.ends_with(".tzst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wrap `input` in a decoder for this format, decompressing zstd with `dictionary` if one is given.
+    pub(crate) fn reader<'a, R: Read + 'a>(
+        self,
+        input: R,
+        dictionary: Option<&[u8]>,
+    ) -> Result<Box<dyn Read + 'a>, TarError> {
+        match (self, dictionary) {
+            (Compression::None, _) => Ok(Box::new(input)),
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, _) => Ok(Box::new(flate2::read::MultiGzDecoder::new(input))),
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, None) => Ok(Box::new(zstd::Decoder::new(input)?)),
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, Some(dict)) => Ok(Box::new(zstd::Decoder::with_dictionary(
+                BufReader::new(input),
+                dict,
+            )?)),
+            #[allow(unreachable_patterns)]
+            (format, _) => Err(TarError::UnsupportedCompression(format!("{:?}", format))),
+        }
+    }
+
+    /// Wrap `output` in an encoder for this format, compressing zstd with `dictionary` if one is given,
+    /// on `threads` threads as described in `ArchiveOptions::compression_threads`.
+    #[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn writer<W: Write>(
+        self,
+        output: W,
+        dictionary: Option<&[u8]>,
+        threads: usize,
+    ) -> Result<Encoder<W>, TarError> {
+        let threads = options::thread_count(threads);
+        match (self, dictionary) {
+            (Compression::None, _) => Ok(Encoder::Plain(output)),
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, _) if threads > 1 => {
+                Ok(Encoder::GzipMembers(GzipMembers::new(output, threads)))
+            }
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, _) => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                output,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, dictionary) => {
+                let mut encoder = match dictionary {
+                    None => zstd::Encoder::new(output, 0)?,
+                    Some(dict) => zstd::Encoder::with_dictionary(output, 0, dict)?,
+                };
+                if threads > 1 {
+                    encoder.multithread(threads as u32)?;
+                }
+                Ok(Encoder::Zstd(encoder))
+            }
+            #[allow(unreachable_patterns)]
+            (format, _) => Err(TarError::UnsupportedCompression(format!("{:?}", format))),
+        }
+    }
+}
+
+/// A writer compressing into the underlying output, which must be completed with `finish`.
+pub(crate) enum Encoder<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "gzip")]
+    GzipMembers(GzipMembers<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the end of the compressed stream and return the underlying output.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(w) => Ok(w),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.finish(),
+            #[cfg(feature = "gzip")]
+            Encoder::GzipMembers(e) => e.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::GzipMembers(e) => e.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::GzipMembers(e) => e.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.flush(),
+        }
+    }
+}
+
+/// Archive the file or directory tree `src` into the file `dest` in one call, compressing it according to
+/// the extension of `dest`. Directories are added with `TarFile::append_dir_all_with`.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{self, ArchiveOptions};
+///
+/// tar::create("src".to_string(), "target/doc-create.tar".to_string(), &ArchiveOptions::default())
+///     .unwrap();
+/// ```
+pub fn create(src: String, dest: String, options: &ArchiveOptions) -> Result<(), TarError> {
+    let mut data = TarFile::default();
+    data.append_dir_all_with(src, options)?;
+
+    #[cfg(feature = "zstd")]
+    let dictionary = options.zstd_dictionary.as_deref();
+    #[cfg(not(feature = "zstd"))]
+    let dictionary = None;
+
+    let output = Compression::from_path(&dest).writer(
+        BufWriter::new(File::create(&dest)?),
+        dictionary,
+        options.compression_threads,
+    )?;
+    let mut builder = TarBuilder::with_options(output, WriteOptions::default(), options.clone());
+    for node in data.nodes() {
+        builder.append_node(node)?;
+    }
+    builder.finish()?.finish()?.flush()?;
+    Ok(())
+}
+
+/// Extract the archive in the file `src` into the directory `dest` in one call, decompressing it according
+/// to the extension of `src`.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{self, ExtractOptions};
+///
+/// tar::extract("test/1.tar".to_string(), "target/doc-extract".to_string(), &ExtractOptions::default())
+///     .unwrap();
+/// ```
+pub fn extract(src: String, dest: String, options: &ExtractOptions) -> Result<(), TarError> {
+    #[cfg(feature = "zstd")]
+    let dictionary = options.zstd_dictionary.as_deref();
+    #[cfg(not(feature = "zstd"))]
+    let dictionary = None;
+
+    let compression = Compression::from_path(&src);
+    let mut input = BufReader::new(File::open(&src)?);
+    if compression == Compression::Zstd {
+        check_dictionary(input.fill_buf()?, dictionary)?;
+    }
+
+    let compressed = Rc::new(Cell::new(0));
+    let tripped = Rc::new(Cell::new(false));
+    let counted = Counted {
+        inner: input,
+        count: compressed.clone(),
+    };
+    let input = compression.reader(counted, dictionary)?;
+    let input: Box<dyn Read> = match (compression, options.max_ratio) {
+        (Compression::None, _) | (_, None) => input,
+        (_, Some(max)) => Box::new(RatioGuard {
+            inner: input,
+            compressed,
+            decompressed: 0,
+            max,
+            tripped: tripped.clone(),
+        }),
+    };
+    let data = match TarFile::open_stream(input, &ReadOptions::default()) {
+        Err(_) if tripped.get() => {
+            return Err(TarError::CompressionRatio(options.max_ratio.unwrap()))
+        }
+        data => data?,
+    };
+    data.extract_with(dest, options)?;
+    Ok(())
+}
+
+/// The amount of input compressed into each gzip member when compressing on several threads.
+#[cfg(feature = "gzip")]
+const MEMBER_SIZE: usize = 1 << 20;
+
+/// Compresses gzip on several threads, like ``pigz``. The input is cut into chunks of ``MEMBER_SIZE``,
+/// and once there is one for each thread they are compressed at the same time into separate gzip members,
+/// which are written to the output in order.
+#[cfg(feature = "gzip")]
+pub(crate) struct GzipMembers<W: Write> {
+    output: W,
+    threads: usize,
+    chunks: Vec<Vec<u8>>,
+    written: bool,
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> GzipMembers<W> {
+    fn new(output: W, threads: usize) -> Self {
+        GzipMembers {
+            output,
+            threads,
+            chunks: Vec::new(),
+            written: false,
+        }
+    }
+
+    /// Compress and write out every chunk gathered so far, including a partly filled last one.
+    fn compress_chunks(&mut self) -> io::Result<()> {
+        let members = std::thread::scope(|s| {
+            let workers = self
+                .chunks
+                .iter()
+                .map(|chunk| s.spawn(move || gzip_member(chunk)))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<io::Result<Vec<Vec<u8>>>>()
+        })?;
+        for member in members {
+            self.output.write_all(&member)?;
+            self.written = true;
+        }
+        self.chunks.clear();
+        Ok(())
+    }
+
+    /// Write the remaining members and return the underlying output. Empty input still gets an empty
+    /// member, so the output is a valid gzip stream.
+    fn finish(mut self) -> io::Result<W> {
+        if !self.written && self.chunks.is_empty() {
+            self.chunks.push(Vec::new());
+        }
+        self.compress_chunks()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> Write for GzipMembers<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.chunks.last().is_none_or(|c| c.len() == MEMBER_SIZE) {
+            if self.chunks.len() == self.threads {
+                self.compress_chunks()?;
+            }
+            self.chunks.push(Vec::with_capacity(MEMBER_SIZE));
+        }
+        let chunk = self.chunks.last_mut().unwrap();
+        let n = buf.len().min(MEMBER_SIZE - chunk.len());
+        chunk.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    /// Compress everything written so far, ending the current member early, and flush the output.
+    fn flush(&mut self) -> io::Result<()> {
+        self.compress_chunks()?;
+        self.output.flush()
+    }
+}
+
+/// Compress `chunk` into a complete gzip member.
+#[cfg(feature = "gzip")]
+fn gzip_member(chunk: &[u8]) -> io::Result<Vec<u8>> {
+    let capacity = chunk.len() / 2 + 64;
+    let mut encoder =
+        flate2::write::GzEncoder::new(Vec::with_capacity(capacity), flate2::Compression::default());
+    encoder.write_all(chunk)?;
+    encoder.finish()
+}
+
+/// Output allowed before the decompression ratio is enforced.
+const RATIO_SLACK: u64 = 1 << 20;
+
+/// Counts the bytes read from the underlying input.
+struct Counted<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Fails reads from a decoder once it has produced more than `max` times the `compressed` bytes it read.
+struct RatioGuard<R> {
+    inner: R,
+    compressed: Rc<Cell<u64>>,
+    decompressed: u64,
+    max: u64,
+    tripped: Rc<Cell<bool>>,
+}
+
+impl<R: Read> Read for RatioGuard<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.decompressed += n as u64;
+        let limit = self
+            .compressed
+            .get()
+            .saturating_mul(self.max)
+            .max(RATIO_SLACK);
+        if self.decompressed > limit {
+            self.tripped.set(true);
+            return Err(io::Error::other("decompression ratio exceeded"));
+        }
+        Ok(n)
+    }
+}
+
+/// Returns the ID of the trained dictionary the zstd compressed archive `src` was created with, or
+/// `None` if it was compressed without one. Requires the ``zstd`` feature.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{self, ArchiveOptions};
+///
+/// tar::create("src".to_string(), "target/doc-dict.tar.zst".to_string(), &ArchiveOptions::default())
+///     .unwrap();
+/// assert_eq!(tar::zstd_dictionary_id("target/doc-dict.tar.zst".to_string()).unwrap(), None);
+/// ```
+#[cfg(feature = "zstd")]
+pub fn zstd_dictionary_id(src: String) -> Result<Option<u32>, TarError> {
+    let mut input = BufReader::new(File::open(&src)?);
+    Ok(frame_dictionary_id(input.fill_buf()?))
+}
+
+/// Returns the dictionary ID recorded in the header of the zstd frame at the start of `frame`.
+#[cfg(feature = "zstd")]
+fn frame_dictionary_id(frame: &[u8]) -> Option<u32> {
+    zstd::zstd_safe::get_dict_id_from_frame(frame).map(|id| id.get())
+}
+
+/// Check that the zstd frame starting `frame` can be decompressed with `dictionary`, so that a missing
+/// or different dictionary gives a clear error rather than a corrupt stream.
+#[cfg(feature = "zstd")]
+fn check_dictionary(frame: &[u8], dictionary: Option<&[u8]>) -> Result<(), TarError> {
+    let supplied = dictionary.and_then(zstd::zstd_safe::get_dict_id_from_dict);
+    match frame_dictionary_id(frame) {
+        Some(id) if supplied.map(|s| s.get()) != Some(id) => Err(TarError::MissingDictionary(id)),
+        _ => Ok(()),
+    }
+}
+
+/// Without zstd support the archive is rejected when the decoder is created.
+#[cfg(not(feature = "zstd"))]
+fn check_dictionary(_frame: &[u8], _dictionary: Option<&[u8]>) -> Result<(), TarError> {
+    Ok(())
+}
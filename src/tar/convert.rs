@@ -0,0 +1,179 @@
+use crate::error::TarError;
+use crate::tar::*;
+
+/// The tar dialect to rewrite entries into with `TarFile::convert`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetFormat {
+    /// POSIX ustar. Names longer than ``100`` bytes are split into the prefix field, and entries that
+    /// need any extension, such as a name that can't be split, can't be converted.
+    Ustar,
+    /// POSIX pax. Names that don't fit the ustar fields are carried in PAX ``path`` and ``linkpath``
+    /// records, and all other PAX records are kept.
+    Pax,
+    /// GNU tar. Long names are carried in ``L`` and ``K`` headers, and the prefix field is not used.
+    Gnu,
+}
+
+impl TarFile {
+    /// Rewrite every entry into the `format` dialect: long names are moved into the mechanism the format
+    /// uses for them, the magic and version fields are set, and checksums are regenerated. Converting to
+    /// ustar or GNU folds the size, owner and time PAX records back into the header and drops the other
+    /// records. Journal records are kept as they are. Fails with `TarError::Unrepresentable` or
+    /// `TarError::NameTooLong`, leaving the archive unchanged, if an entry can't be represented.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{TarFile, TargetFormat};
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.convert(TargetFormat::Pax).unwrap();
+    /// assert!(data.nodes()[0].header().validate_checksum().unwrap());
+    /// ```
+    pub fn convert(&mut self, format: TargetFormat) -> Result<(), TarError> {
+        let converted = self
+            .file
+            .iter()
+            .map(|n| match n.journal_entry() {
+                Some(_) => Ok(n.clone()),
+                None => n.converted(format),
+            })
+            .collect::<Result<Vec<TarNode>, TarError>>()?;
+        self.file = converted;
+        Ok(())
+    }
+}
+
+impl TarNode {
+    /// Returns a copy of the node rewritten into the `format` dialect.
+    fn converted(&self, format: TargetFormat) -> Result<TarNode, TarError> {
+        let name = self.name();
+        let old_sparse = self.file_type() == FileType::GnuSparse as u8;
+        let pax_sparse = self.pax.iter().any(|r| r.key().starts_with("GNU.sparse."));
+        let unrepresentable = match format {
+            TargetFormat::Ustar => {
+                old_sparse || pax_sparse || self.sparse.is_some() || self.has_base256()
+            }
+            TargetFormat::Pax => old_sparse,
+            TargetFormat::Gnu => pax_sparse,
+        };
+        if unrepresentable {
+            return Err(TarError::Unrepresentable(name));
+        }
+
+        let mut node = self.clone();
+        let link = self.link_name();
+        if format != TargetFormat::Pax {
+            node.fold_records(format == TargetFormat::Gnu)
+                .map_err(|_| TarError::Unrepresentable(name.clone()))?;
+        }
+        node.pax
+            .retain(|r| r.key() != "path" && r.key() != "linkpath");
+        node.gnu_long_names = format == TargetFormat::Gnu;
+
+        /* Sparse PAX entries are stored under a made up name, which stays in place */
+        match format {
+            TargetFormat::Gnu => {
+                /* The old GNU sparse map lives in the prefix field */
+                if !old_sparse {
+                    node.header.file_prefix = [0; 155];
+                }
+                node.place_long("path", &name, 100)?;
+                node.header.ustar_magic = *b"ustar ";
+                node.header.ustar_version = *b" \x00";
+            }
+            _ if pax_sparse => (),
+            _ => {
+                let (prefix, rest) = match names::split_ustar(&name) {
+                    Some(split) => split,
+                    None if format == TargetFormat::Ustar => return Err(TarError::NameTooLong),
+                    None => ("", name.as_str()),
+                };
+                set_field(&mut node.header.file_prefix, prefix.as_bytes())?;
+                match prefix.is_empty() {
+                    true => node.place_long("path", &name, 100)?,
+                    false => set_field(&mut node.header.file_name, rest.as_bytes())?,
+                }
+                node.header.ustar_magic = *b"ustar\x00";
+                node.header.ustar_version = *b"00";
+            }
+        }
+
+        if !link.is_empty() {
+            if format == TargetFormat::Ustar && link.len() > 100 {
+                return Err(TarError::NameTooLong);
+            }
+            node.place_long("linkpath", &link, 100)?;
+        }
+
+        node.header.update_checksum()?;
+        Ok(node)
+    }
+
+    /// Returns `true` if a numeric header field holds a GNU base-256 number, which ustar has no room for.
+    fn has_base256(&self) -> bool {
+        let h = &self.header;
+        [
+            &h.own_user[..],
+            &h.own_group[..],
+            &h.file_size[..],
+            &h.mod_time[..],
+        ]
+        .iter()
+        .any(|field| field[0] & 0x80 != 0)
+    }
+
+    /// Store `value` in the header field for `key`, truncated to `len` bytes, adding a record under `key`
+    /// with the full value when it doesn't fit.
+    pub(crate) fn place_long(
+        &mut self,
+        key: &str,
+        value: &str,
+        len: usize,
+    ) -> Result<(), TarError> {
+        let field = match key {
+            "path" => &mut self.header.file_name[..],
+            _ => &mut self.header.link_name[..],
+        };
+        set_field(field, names::truncate(value, len).as_bytes())?;
+        if value.len() > len {
+            self.pax.push(PaxRecord::new(key, value));
+        }
+        Ok(())
+    }
+
+    /// Move the values of PAX records that have a header field into the header, then drop every record.
+    /// Numbers too large for octal digits are stored in base-256 if `base256` is set. Fails if a value
+    /// doesn't fit its field.
+    fn fold_records(&mut self, base256: bool) -> Result<(), TarError> {
+        let numeric = |value, field: &mut [u8]| match dec_to_oct(value, field, OctalTerminator::Nul)
+        {
+            Err(_) if base256 => dec_to_base256(value, field),
+            result => result,
+        };
+        for record in &self.pax {
+            let value = match record.value_str() {
+                Some(v) => v,
+                None => continue,
+            };
+            /* Times may carry a fraction, which the header has no room for */
+            let number = || value.split('.').next().unwrap_or("").parse::<u64>();
+            match record.key() {
+                "size" => numeric(number()?, &mut self.header.file_size)?,
+                "uid" => numeric(number()?, &mut self.header.own_user)?,
+                "gid" => numeric(number()?, &mut self.header.own_group)?,
+                "mtime" => match pax::parse_time(value).ok_or(TarError::InvalidPax)? {
+                    (secs, _) if secs >= 0 => numeric(secs as u64, &mut self.header.mod_time)?,
+                    (secs, _) if base256 => signed_to_base256(secs, &mut self.header.mod_time)?,
+                    _ => return Err(TarError::FieldOverflow),
+                },
+                "uname" => set_field(&mut self.header.own_user_name, value.as_bytes())?,
+                "gname" => set_field(&mut self.header.own_group_name, value.as_bytes())?,
+                _ => (),
+            }
+        }
+
+        self.pax.clear();
+        Ok(())
+    }
+}
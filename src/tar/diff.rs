@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A difference between two archives.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArchiveChange {
+    /// The entry only exists in the new archive.
+    Added(String),
+    /// The entry only exists in the old archive.
+    Removed(String),
+    /// The entry changed type.
+    Type(String),
+    /// The size of the entry changed.
+    Size { name: String, old: u64, new: u64 },
+    /// The permission bits of the entry changed.
+    Mode { name: String, old: u32, new: u32 },
+    /// The numeric `(uid, gid)` owner of the entry changed.
+    Owner {
+        name: String,
+        old: (u64, u64),
+        new: (u64, u64),
+    },
+    /// The modification time of the entry changed.
+    Mtime { name: String, old: i64, new: i64 },
+    /// The target of a link changed.
+    LinkTarget {
+        name: String,
+        old: String,
+        new: String,
+    },
+    /// The contents of the entry changed while its size stayed the same.
+    Contents(String),
+}
+
+impl TarFile {
+    /// Compare this archive with `other`, returning the changes needed to go from this archive to `other`.
+    /// Entries are matched by name; when a name appears several times the last entry is used, as it would
+    /// be on extraction. Removed and changed entries are listed in the order of this archive, followed by
+    /// added entries in the order of `other`. Journal records are not compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ArchiveChange, TarFile};
+    ///
+    /// let old = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let new = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// let changes = old.diff(&new).unwrap();
+    /// assert_eq!(changes[0], ArchiveChange::Removed("1.txt".to_string()));
+    /// ```
+    pub fn diff(&self, other: &TarFile) -> Result<Vec<ArchiveChange>, TarError> {
+        let old = self.entries_by_name();
+        let new = other.entries_by_name();
+        let lookup: HashMap<&str, &TarNode> =
+            new.iter().map(|(n, node)| (n.as_str(), *node)).collect();
+        let mut out = Vec::new();
+
+        for (name, node) in &old {
+            match lookup.get(name.as_str()) {
+                Some(other) => node.diff(other, &mut out)?,
+                None => out.push(ArchiveChange::Removed(name.clone())),
+            }
+        }
+
+        let existing: HashMap<&str, &TarNode> =
+            old.iter().map(|(n, node)| (n.as_str(), *node)).collect();
+        for (name, _) in &new {
+            if !existing.contains_key(name.as_str()) {
+                out.push(ArchiveChange::Added(name.clone()));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Returns the last entry for each name, in the order each name first appears.
+    fn entries_by_name(&self) -> Vec<(String, &TarNode)> {
+        let mut out = Vec::<(String, &TarNode)>::new();
+        let mut seen = HashMap::<String, usize>::new();
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            let name = node.name();
+            match seen.get(&name) {
+                Some(&i) => out[i].1 = node,
+                None => {
+                    seen.insert(name.clone(), out.len());
+                    out.push((name, node));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl TarNode {
+    /// Compare this node with the `other` node of the same name, appending any changes to `out`.
+    fn diff(&self, other: &TarNode, out: &mut Vec<ArchiveChange>) -> Result<(), TarError> {
+        let (old, new) = (self.info(), other.info());
+        let name = old.name.clone();
+        if old.file_type != new.file_type {
+            out.push(ArchiveChange::Type(name));
+            return Ok(());
+        }
+
+        if old.size != new.size {
+            out.push(ArchiveChange::Size {
+                name: name.clone(),
+                old: old.size,
+                new: new.size,
+            });
+        } else if old.size > 0 {
+            let mut a = Vec::with_capacity(old.size as usize);
+            self.write_data(&mut a)?;
+            let mut b = Vec::with_capacity(new.size as usize);
+            other.write_data(&mut b)?;
+            if a != b {
+                out.push(ArchiveChange::Contents(name.clone()));
+            }
+        }
+        if old.mode != new.mode {
+            out.push(ArchiveChange::Mode {
+                name: name.clone(),
+                old: old.mode,
+                new: new.mode,
+            });
+        }
+        if (old.uid, old.gid) != (new.uid, new.gid) {
+            out.push(ArchiveChange::Owner {
+                name: name.clone(),
+                old: (old.uid, old.gid),
+                new: (new.uid, new.gid),
+            });
+        }
+        if old.mtime != new.mtime {
+            out.push(ArchiveChange::Mtime {
+                name: name.clone(),
+                old: old.mtime,
+                new: new.mtime,
+            });
+        }
+        if old.link_target != new.link_target {
+            out.push(ArchiveChange::LinkTarget {
+                name,
+                old: old.link_target.unwrap_or_default(),
+                new: new.link_target.unwrap_or_default(),
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::io;
+
+use sha2::Digest;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// The hash algorithm used to compute digests of entry contents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// SHA-256, as written by ``sha256sum``.
+    #[default]
+    Sha256,
+    /// SHA-512, as written by ``sha512sum``.
+    Sha512,
+    /// BLAKE3, as written by ``b3sum``, which is several times faster than SHA-2 on large files. Requires
+    /// the ``blake3`` feature.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Returns the lowercase name of the algorithm, such as ``sha256``.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Look up an algorithm by the name returned by `name`, ignoring case. Returns `None` for unknown
+    /// algorithms and for ``blake3`` without the ``blake3`` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::HashAlgorithm;
+    ///
+    /// assert_eq!(HashAlgorithm::from_name("SHA512"), Some(HashAlgorithm::Sha512));
+    /// assert_eq!(HashAlgorithm::from_name("md5"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<HashAlgorithm> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "sha512" => Some(HashAlgorithm::Sha512),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    /// Returns the digest of `data`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::HashAlgorithm;
+    ///
+    /// assert_eq!(HashAlgorithm::Sha256.digest(b"").len(), 32);
+    /// ```
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// Returns a hasher that digests everything written to it.
+    pub(crate) fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(sha2::Sha512::new()),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+/// A hash computation in progress, fed through `std::io::Write`.
+pub(crate) enum Hasher {
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Add `data` to the digest.
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    /// Returns the digest of everything written.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TarNode {
+    /// Returns the digest of the entry contents, with the holes of sparse files filled in, computed with
+    /// `algorithm`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{HashAlgorithm, TarFile};
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let digest = data.nodes()[0].digest(HashAlgorithm::Sha256).unwrap();
+    /// assert_eq!(digest.len(), 32);
+    /// ```
+    pub fn digest(&self, algorithm: HashAlgorithm) -> Result<Vec<u8>, TarError> {
+        let mut hasher = algorithm.hasher();
+        self.write_data(&mut hasher)?;
+        Ok(hasher.finish())
+    }
+}
+
+impl TarFile {
+    /// Returns a manifest listing the digest of every regular file computed with `algorithm`, one
+    /// ``<hex digest>  <name>`` line per file as written by ``sha256sum`` and friends, so that extracted
+    /// files can be checked with ``sha256sum -c``.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{HashAlgorithm, TarFile};
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let manifest = data.manifest(HashAlgorithm::Sha256).unwrap();
+    /// assert!(manifest.ends_with("  1.txt\n"));
+    /// ```
+    pub fn manifest(&self, algorithm: HashAlgorithm) -> Result<String, TarError> {
+        let mut manifest = String::new();
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            if !is_regular(node.file_type()) {
+                continue;
+            }
+            let digest = to_hex(&node.digest(algorithm)?);
+            manifest.push_str(&format!("{}  {}\n", digest, node.name()));
+        }
+        Ok(manifest)
+    }
+}
+
+/// Format `bytes` as lowercase hexadecimal.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
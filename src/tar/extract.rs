@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use crate::error::{TarError, TarWarning};
+use crate::tar::*;
+
+/// The contents and metadata of a file extracted into memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryEntry {
+    /// The file contents.
+    pub contents: Vec<u8>,
+    /// The permission bits of the file.
+    pub mode: u32,
+    /// The modification time of the file in seconds since the epoch.
+    pub mtime: i64,
+}
+
+impl TarFile {
+    /// Extract the contents of the archive into the `dest` directory. Regular files and directories are
+    /// created with their stored permissions, less the process umask unless running as root, and hard
+    /// links are linked to the file extracted for their target. Symbolic links are created after every
+    /// other entry, and by default links pointing outside of `dest` are refused with `TarError::UnsafeLink`.
+    /// FIFOs and devices are recreated when the process has the privileges to do so. When running as root
+    /// each entry is given its stored owner as chosen by the `OwnerMapping` of the options; otherwise
+    /// entries keep the extracting user as owner. Journal records are not extracted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.extract("target/doc-extract".to_string()).unwrap();
+    /// ```
+    pub fn extract(&self, dest: String) -> Result<OperationStats, TarError> {
+        self.extract_with(dest, &ExtractOptions::default())
+    }
+
+    /// Extract the contents of the archive in the same way as `extract`, using the provided
+    /// `ExtractOptions`. Returns `OperationStats` counting the entries created and listing those skipped,
+    /// along with the warnings raised, such as a `TarWarning::SpecialSkipped` for each device that could
+    /// not be created without privileges, or a `TarWarning::OwnerNotRestored` for each entry that kept the
+    /// extracting user as owner.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ExtractOptions, TarFile};
+    ///
+    /// let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// let options = ExtractOptions::default().strip_components(1);
+    /// let stats = data.extract_with("target/doc-strip".to_string(), &options).unwrap();
+    /// assert_eq!(stats.entries(), 1);
+    /// ```
+    pub fn extract_with(
+        &self,
+        dest: String,
+        options: &ExtractOptions,
+    ) -> Result<OperationStats, TarError> {
+        trace::span!("extract", dest = %dest);
+        let start = Instant::now();
+        /* Nothing is written unless the whole archive is allowed */
+        self.check_profile(options.profile)?;
+        let dest = Path::new(&dest);
+        fs::create_dir_all(dest)?;
+
+        let umask = options.umask();
+        let mut reflinks = reflink::Reflinks::default();
+        let mut throttle = throttle::Throttle::new(options.rate_limit);
+        let mut stats = OperationStats::default();
+        let mut warnings = Vec::new();
+        let mut symlinks = Vec::new();
+        let mut dirs = Vec::new();
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        let mut pending = match options.io_uring
+            && !options.punch_holes
+            && !options.reflink
+            && options.rate_limit.is_none()
+        {
+            true => uring::Ring::new().map(PendingWrites::new),
+            false => None,
+        };
+        let mut cancelled = false;
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            cancelled = cancel::cancelled(&options.cancel);
+            if cancelled {
+                break;
+            }
+            let path = match extract_path(dest, &node.name(), options)? {
+                Some(p) => p,
+                None => {
+                    stats.skip(node.name(), SkipReason::NoName);
+                    continue;
+                }
+            };
+            if let Some(verifier) = &options.verifier {
+                node.verify(verifier.as_ref())?;
+            }
+            trace::event!(
+                debug,
+                name = %node.name(),
+                file_type = ?node.entry_type(),
+                size = node.apparent_size(),
+                "extracting entry"
+            );
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            if let Some(pending) = &mut pending {
+                if pending.defer(node, &path, options, umask, &mut warnings)? {
+                    stats.count(node.entry_type(), node.apparent_size());
+                    continue;
+                }
+            }
+
+            /* Symbolic links are created last, so their targets exist and no entry is written through one */
+            if node.file_type() == FileType::Sym as u8 {
+                symlinks.push((node, path));
+                continue;
+            }
+
+            /* Link targets are renamed in the same way as entry names, so they point into `dest` */
+            if node.file_type() == FileType::Hard as u8 {
+                let target = node.link_name();
+                match extract_path(dest, &target, options)? {
+                    Some(t) if t == path => (),
+                    Some(t) if t.is_file() => {
+                        reflinks.forget(&path);
+                        hard_link(&t, &path)?
+                    }
+                    _ => {
+                        return Err(TarError::MissingLinkTarget {
+                            name: node.name(),
+                            target,
+                        })
+                    }
+                }
+                stats.count(FileType::Hard, 0);
+                continue;
+            }
+            let created = node.extract_to(
+                &path,
+                options,
+                umask,
+                &mut reflinks,
+                &mut throttle,
+                &mut warnings,
+            )?;
+            match created {
+                true => stats.count(node.entry_type(), node.apparent_size()),
+                false if is_special(node.file_type()) => {
+                    stats.skip(node.name(), SkipReason::Special)
+                }
+                false => stats.skip(node.name(), SkipReason::Unsupported),
+            }
+            if node.file_type() == FileType::Dir as u8 {
+                dirs.push((node, path));
+            }
+        }
+
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if let Some(pending) = &mut pending {
+            pending.flush(options, umask, &mut warnings)?;
+        }
+
+        for (node, path) in symlinks {
+            cancelled = cancelled || cancel::cancelled(&options.cancel);
+            if cancelled {
+                break;
+            }
+            match node.extract_symlink(dest, &path, options, &mut warnings)? {
+                true => stats.count(FileType::Sym, 0),
+                false => stats.skip(node.name(), SkipReason::Link),
+            }
+        }
+
+        /* Directories are left writable while their contents are created, and get their final modes and
+         * times once nothing else will be written into them. Children come after their parents in the
+         * archive, so going backwards closes each directory before the one it is in */
+        for (node, path) in dirs.into_iter().rev() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(node.mode() & !umask))?;
+            set_mtime(&path, node.mtime(), node.mtime_nanos())?;
+        }
+
+        if cancelled {
+            return Err(TarError::Cancelled);
+        }
+        warnings.retain(|w| w.verbosity() <= options.verbosity);
+        stats.warnings = warnings;
+        stats.finish(start);
+        Ok(stats)
+    }
+
+    /// Locate the first entry named `name` and write its contents, without any block padding, to `output`.
+    /// Returns the number of bytes written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let mut contents = Vec::new();
+    /// data.extract_entry("1.txt".to_string(), &mut contents).unwrap();
+    /// ```
+    pub fn extract_entry<T: Write>(&self, name: String, output: &mut T) -> Result<usize, TarError> {
+        match self
+            .file
+            .iter()
+            .find(|n| n.journal_entry().is_none() && n.name() == name)
+        {
+            Some(node) => node.write_data(output),
+            None => Err(TarError::NotFound(name)),
+        }
+    }
+
+    /// Extract every regular file in the archive into memory, keyed by its sanitized path, without touching
+    /// the filesystem. Hard links get a copy of their target. When several entries share a path, the last
+    /// one wins as it would on disk.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let files = data.extract_to_memory().unwrap();
+    /// assert_eq!(files[Path::new("1.txt")].contents.len(), 21);
+    /// ```
+    pub fn extract_to_memory(&self) -> Result<HashMap<PathBuf, MemoryEntry>, TarError> {
+        let mut out = HashMap::new();
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            let file_type = node.file_type();
+            if file_type == FileType::Hard as u8 {
+                let target = safe_path(Path::new(""), &node.link_name())?;
+                match out.get(&target).cloned() {
+                    Some(entry) => out.insert(safe_path(Path::new(""), &node.name())?, entry),
+                    None => {
+                        return Err(TarError::MissingLinkTarget {
+                            name: node.name(),
+                            target: node.link_name(),
+                        })
+                    }
+                };
+                continue;
+            }
+            if !is_regular(file_type) {
+                continue;
+            }
+
+            let mut contents = Vec::with_capacity(node.size());
+            node.write_data(&mut contents)?;
+            out.insert(
+                safe_path(Path::new(""), &node.name())?,
+                MemoryEntry {
+                    contents,
+                    mode: node.mode(),
+                    mtime: node.mtime(),
+                },
+            );
+        }
+
+        Ok(out)
+    }
+}
+
+impl TarNode {
+    /// Create the filesystem object described by this node at `path`, with its stored permissions less the
+    /// bits in `umask`. Returns `false` if nothing was created, for entry types that aren't extracted and
+    /// skipped special files.
+    fn extract_to(
+        &self,
+        path: &Path,
+        options: &ExtractOptions,
+        umask: u32,
+        reflinks: &mut reflink::Reflinks,
+        throttle: &mut throttle::Throttle,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<bool, TarError> {
+        let file_type = self.file_type();
+        if file_type == FileType::Dir as u8 {
+            fs::create_dir_all(path)?;
+        } else if is_regular(file_type) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = File::create(path)?;
+            match self.sparse_contents()? {
+                Some((map, stored)) => {
+                    sparse::write_sparse_file(&file, &map, &stored, options.punch_holes)?
+                }
+                None if options.punch_holes => {
+                    let size = self.size();
+                    let map = SparseMap {
+                        real_size: size as u64,
+                        segments: vec![(0, size as u64)],
+                    };
+                    sparse::write_sparse_file(&file, &map, &self.data()?[..size], true)?
+                }
+                None if options.reflink && reflinks.clone_into(self, &file, path)? => (),
+                None if throttle.is_limited() => {
+                    self.write_data(throttle.writer(&file))?;
+                }
+                None => {
+                    self.write_data_to_file(&file)?;
+                }
+            }
+        } else if is_special(file_type) {
+            return self.extract_special(path, options, umask, warnings);
+        } else {
+            return Ok(false);
+        }
+        self.restore_metadata(path, options, umask, warnings)?;
+        Ok(true)
+    }
+
+    /// Give the file or directory extracted from this node at `path` its stored owner, extended attributes,
+    /// permissions less the bits in `umask`, and modification time, once its contents have been written.
+    fn restore_metadata(
+        &self,
+        path: &Path,
+        options: &ExtractOptions,
+        umask: u32,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<(), TarError> {
+        /* Writing to a file or changing its owner drops its capabilities, so they are set last */
+        self.restore_owner(path, options, warnings)?;
+        if options.xattrs || options.capabilities {
+            self.restore_xattrs(path, !options.xattrs, warnings)?;
+        }
+        if self.file_type() == FileType::Dir as u8 {
+            /* The final mode and time are set after the directory contents, by `extract_with` */
+            let mode = (self.mode() & !umask) | 0o700;
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            return Ok(());
+        }
+        fs::set_permissions(path, fs::Permissions::from_mode(self.mode() & !umask))?;
+        set_mtime(path, self.mtime(), self.mtime_nanos())
+    }
+
+    /// Create the FIFO or device described by this node at `path` with ``mkfifo`` or ``mknod``. Creating
+    /// devices needs privileges, so when that is refused the entry is skipped with a
+    /// `TarWarning::SpecialSkipped` and `false` is returned.
+    fn extract_special(
+        &self,
+        path: &Path,
+        options: &ExtractOptions,
+        umask: u32,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<bool, TarError> {
+        let file_type = self.file_type();
+        let kind = match file_type {
+            t if t == FileType::FIFO as u8 => libc::S_IFIFO,
+            t if t == FileType::Char as u8 => libc::S_IFCHR,
+            t if t == FileType::Block as u8 => libc::S_IFBLK,
+            _ => return Ok(false),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::symlink_metadata(path).is_ok() {
+            fs::remove_file(path)?;
+        }
+
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| TarError::UnsafePath(self.name()))?;
+        let (major, minor) = self.device();
+        let mode = (self.mode() & 0o7777) as libc::mode_t;
+        /* SAFETY: `c_path` is a valid NUL terminated string that outlives the calls */
+        let result = unsafe {
+            match file_type == FileType::FIFO as u8 {
+                true => libc::mkfifo(c_path.as_ptr(), mode),
+                false => libc::mknod(
+                    c_path.as_ptr(),
+                    kind | mode,
+                    libc::makedev(major as _, minor as _),
+                ),
+            }
+        };
+        if result != 0 {
+            let error = std::io::Error::last_os_error();
+            return match error.kind() {
+                ErrorKind::PermissionDenied => {
+                    warnings.push(TarWarning::SpecialSkipped(self.name()));
+                    Ok(false)
+                }
+                _ => Err(error.into()),
+            };
+        }
+
+        /* The mode given to mknod is reduced by the umask, which may not be the one asked for */
+        self.restore_owner(path, options, warnings)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(self.mode() & !umask))?;
+        set_mtime(path, self.mtime(), self.mtime_nanos())?;
+        Ok(true)
+    }
+
+    /// Returns the uid and gid to give this entry under the owner mapping of `options`, or `None` for an
+    /// id that is left to the extracting process.
+    fn owner(&self, options: &ExtractOptions) -> (Option<u32>, Option<u32>) {
+        let (uid, gid) = (self.uid() as u32, self.gid() as u32);
+        let user = Some(self.uname())
+            .filter(|n| !n.is_empty())
+            .and_then(|n| options.resolver.user_id(&n));
+        let group = Some(self.gname())
+            .filter(|n| !n.is_empty())
+            .and_then(|n| options.resolver.group_id(&n));
+        match options.owners {
+            OwnerMapping::Names => (user.or(Some(uid)), group.or(Some(gid))),
+            OwnerMapping::Numeric => (Some(uid), Some(gid)),
+            OwnerMapping::NamesOnly => (user, group),
+        }
+    }
+
+    /// Give the file at `path` the owner chosen by `options`, without following a symbolic link. When that
+    /// is refused, or not attempted because only root may give files away, a file left with another owner
+    /// is reported with a `TarWarning::OwnerNotRestored`.
+    fn restore_owner(
+        &self,
+        path: &Path,
+        options: &ExtractOptions,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<(), TarError> {
+        let (uid, gid) = self.owner(options);
+        if options.same_owner == Some(false) || (uid.is_none() && gid.is_none()) {
+            return Ok(());
+        }
+        /* SAFETY: geteuid has no preconditions and cannot fail */
+        if options
+            .same_owner
+            .unwrap_or(unsafe { libc::geteuid() } == 0)
+        {
+            /* Ids outside a user namespace's mapping are refused with EINVAL */
+            match std::os::unix::fs::lchown(path, uid, gid) {
+                Ok(()) => return Ok(()),
+                Err(e) if matches!(e.raw_os_error(), Some(libc::EPERM) | Some(libc::EINVAL)) => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let meta = fs::symlink_metadata(path)?;
+        let (uid, gid) = (uid.unwrap_or(meta.uid()), gid.unwrap_or(meta.gid()));
+        if (uid, gid) != (meta.uid(), meta.gid()) {
+            warnings.push(TarWarning::OwnerNotRestored {
+                name: self.name(),
+                uid,
+                gid,
+            });
+        }
+        Ok(())
+    }
+
+    /// Recreate the symbolic link described by this node at `path`, according to the link policies of
+    /// `options`. Returns `false` if the policies left it out.
+    fn extract_symlink(
+        &self,
+        dest: &Path,
+        path: &Path,
+        options: &ExtractOptions,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<bool, TarError> {
+        if options.symlinks == SymlinkPolicy::Skip {
+            return Ok(false);
+        }
+
+        /* An absolute target replaces the parent when joined */
+        let target = self.link_name();
+        let parent = path.parent().unwrap_or(dest);
+        let resolved = parent.join(&target);
+
+        /* Links created earlier in the extraction can lead a target that stays inside by its name out */
+        let escapes = escapes_dest(dest, path, &target) || leaves_dest(dest, &resolved);
+        match options.escaping_links {
+            LinkPolicy::Skip if escapes => return Ok(false),
+            LinkPolicy::Error if escapes => {
+                return Err(TarError::UnsafeLink {
+                    name: self.name(),
+                    target,
+                })
+            }
+            _ => (),
+        }
+
+        if fs::metadata(&resolved).is_err() {
+            match options.dangling_links {
+                LinkPolicy::Skip => return Ok(false),
+                LinkPolicy::Error => {
+                    return Err(TarError::MissingLinkTarget {
+                        name: self.name(),
+                        target,
+                    })
+                }
+                LinkPolicy::Allow => (),
+            }
+        }
+
+        fs::create_dir_all(parent)?;
+        if fs::symlink_metadata(path).is_ok() {
+            fs::remove_file(path)?;
+        }
+        match options.symlinks {
+            SymlinkPolicy::Copy if resolved.is_file() => {
+                fs::copy(&resolved, path)?;
+            }
+            SymlinkPolicy::Create => {
+                std::os::unix::fs::symlink(&target, path)?;
+                self.restore_owner(path, options, warnings)?;
+                set_mtime(path, self.mtime(), self.mtime_nanos())?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Returns whether the symbolic link at `path` pointing to `target` leads outside of `dest`, judging by
+/// the names alone.
+pub(crate) fn escapes_dest(dest: &Path, path: &Path, target: &str) -> bool {
+    let target = Path::new(target);
+    if target.is_absolute() {
+        return true;
+    }
+
+    /* Count how deep below `dest` the directory holding the link is */
+    let mut depth = path
+        .strip_prefix(dest)
+        .map(|p| p.components().count().saturating_sub(1))
+        .unwrap_or(0);
+    for c in target.components() {
+        match c {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Returns whether `resolved` is outside of `dest` once the symbolic links on disk are followed. The part
+/// of `resolved` that doesn't exist yet is judged by its names.
+fn leaves_dest(dest: &Path, resolved: &Path) -> bool {
+    let dest = match fs::canonicalize(dest) {
+        Ok(d) => d,
+        Err(_) => return true,
+    };
+    let components: Vec<Component> = resolved.components().collect();
+    for existing in (1..=components.len()).rev() {
+        let mut real = match fs::canonicalize(components[..existing].iter().collect::<PathBuf>()) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        for c in &components[existing..] {
+            match c {
+                Component::ParentDir => {
+                    real.pop();
+                }
+                Component::Normal(part) => real.push(part),
+                _ => (),
+            }
+        }
+        return !real.starts_with(&dest);
+    }
+    true
+}
+
+/// Join the entry `name` onto `dest`, dropping any leading ``/`` and refusing ``..`` components so that
+/// entries cannot be written outside of `dest`.
+pub(crate) fn safe_path(dest: &Path, name: &str) -> Result<PathBuf, TarError> {
+    let mut path = dest.to_path_buf();
+    for c in Path::new(name).components() {
+        match c {
+            Component::Normal(part) => path.push(part),
+            Component::ParentDir => return Err(TarError::UnsafePath(name.to_string())),
+            _ => (),
+        }
+    }
+
+    Ok(path)
+}
+
+/// Returns the path under `dest` that the entry `name` extracts to with `options`, or `None` if it is
+/// skipped.
+fn extract_path(
+    dest: &Path,
+    name: &str,
+    options: &ExtractOptions,
+) -> Result<Option<PathBuf>, TarError> {
+    let name = match strip_components(name, options.strip_components) {
+        Some(n) => n,
+        None => return Ok(None),
+    };
+    let name = match &options.transform {
+        Some(t) => t.apply(&name),
+        None => name,
+    };
+    if name.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(safe_path(dest, &name)?))
+}
+
+/// Set the modification time of `path` to `mtime` seconds and `nanos` nanoseconds since the epoch, without
+/// following a symbolic link. The access time is set to now.
+fn set_mtime(path: &Path, mtime: i64, nanos: u32) -> Result<(), TarError> {
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| TarError::UnsafePath(path.to_string_lossy().to_string()))?;
+    let times = [
+        libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_NOW,
+        },
+        libc::timespec {
+            tv_sec: mtime as libc::time_t,
+            tv_nsec: nanos as libc::c_long,
+        },
+    ];
+    /* SAFETY: `c_path` is a valid NUL terminated string and `times` holds the two entries utimensat reads */
+    let flags = libc::AT_SYMLINK_NOFOLLOW;
+    match unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), flags) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error().into()),
+    }
+}
+
+/// Create `path` as a hard link to `target`, replacing any file already there. Links can't cross
+/// filesystems, so the file is copied instead when they would.
+fn hard_link(target: &Path, path: &Path) -> Result<(), TarError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    match fs::hard_link(target, path) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            fs::copy(target, path)?;
+        }
+        r => r?,
+    }
+    Ok(())
+}
+
+/// Remove the first `count` components from `name`, returning `None` if nothing is left.
+fn strip_components(name: &str, count: usize) -> Option<String> {
+    if count == 0 {
+        return Some(name.to_string());
+    }
+
+    let parts: Vec<&str> = name
+        .split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect();
+    if parts.len() <= count {
+        return None;
+    }
+    Some(parts[count..].join("/"))
+}
+
+/// Regular files created during an extraction whose data is still to be written through io_uring, as
+/// described in `ExtractOptions::io_uring`.
+#[cfg(all(feature = "uring", target_os = "linux"))]
+struct PendingWrites<'a> {
+    ring: uring::Ring,
+    files: Vec<(&'a TarNode, PathBuf, File, Cow<'a, [u8]>)>,
+    bytes: usize,
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+impl<'a> PendingWrites<'a> {
+    fn new(ring: uring::Ring) -> Self {
+        PendingWrites {
+            ring,
+            files: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Create the regular file extracted from `node` at `path` and queue its data, writing the queue out
+    /// once it is full. Returns `false`, having queued nothing, if the entry is to be extracted as usual.
+    /// Anything that could land on a queued file, such as a hard link or a later entry with the same path,
+    /// writes the queue out first.
+    fn defer(
+        &mut self,
+        node: &'a TarNode,
+        path: &Path,
+        options: &ExtractOptions,
+        umask: u32,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<bool, TarError> {
+        let file_type = node.file_type();
+        if file_type == FileType::Hard as u8
+            || is_special(file_type)
+            || self.files.iter().any(|(_, p, _, _)| p == path)
+        {
+            self.flush(options, umask, warnings)?;
+        }
+        if !is_regular(file_type)
+            || node.size() >= uring::BATCH_BYTES
+            || node.sparse_map()?.is_some()
+        {
+            return Ok(false);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)?;
+        let data = match node.data()? {
+            Cow::Borrowed(data) => Cow::Borrowed(&data[..node.size().min(data.len())]),
+            Cow::Owned(mut data) => {
+                data.truncate(node.size());
+                Cow::Owned(data)
+            }
+        };
+        self.bytes += data.len();
+        self.files.push((node, path.to_path_buf(), file, data));
+        if self.files.len() >= uring::BATCH_FILES || self.bytes >= uring::BATCH_BYTES {
+            self.flush(options, umask, warnings)?;
+        }
+        Ok(true)
+    }
+
+    /// Write the data of every queued file in one go, then give each its metadata.
+    fn flush(
+        &mut self,
+        options: &ExtractOptions,
+        umask: u32,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<(), TarError> {
+        let writes = self
+            .files
+            .iter()
+            .map(|(_, _, file, data)| (file, &data[..]))
+            .collect::<Vec<(&File, &[u8])>>();
+        self.ring.write_all(&writes)?;
+
+        for (node, path, _, _) in self.files.drain(..) {
+            node.restore_metadata(&path, options, umask, warnings)?;
+        }
+        self.bytes = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::error::TarError;
+use crate::tar::*;
+
+impl TarFile {
+    /// Create a new `TarFile` from a list of newline separated paths, as produced by ``find`` or
+    /// ``git ls-files``, adding each file in the order listed. Empty lines are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let list = "test/1.txt\nCargo.toml\n";
+    /// let data = TarFile::from_file_list(list.as_bytes()).unwrap();
+    /// assert_eq!(data.list().len(), 2);
+    /// ```
+    pub fn from_file_list<R: Read>(reader: R) -> Result<Self, TarError> {
+        TarFile::from_file_list_with(reader, &ArchiveOptions::default())
+    }
+
+    /// Create a new `TarFile` from a list of paths in the same way as `from_file_list`, using the provided
+    /// `ArchiveOptions`.
+    pub fn from_file_list_with<R: Read>(
+        reader: R,
+        options: &ArchiveOptions,
+    ) -> Result<Self, TarError> {
+        TarFile::from_delimited_list(reader, b'\n', options)
+    }
+
+    /// Create a new `TarFile` from a list of NUL separated paths, as produced by ``find -print0``. Unlike
+    /// `from_file_list`, this handles paths that contain newlines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let list = "test/1.txt\0Cargo.toml\0";
+    /// let data = TarFile::from_null_file_list(list.as_bytes()).unwrap();
+    /// assert_eq!(data.list().len(), 2);
+    /// ```
+    pub fn from_null_file_list<R: Read>(reader: R) -> Result<Self, TarError> {
+        TarFile::from_null_file_list_with(reader, &ArchiveOptions::default())
+    }
+
+    /// Create a new `TarFile` from a list of NUL separated paths in the same way as `from_null_file_list`,
+    /// using the provided `ArchiveOptions`.
+    pub fn from_null_file_list_with<R: Read>(
+        reader: R,
+        options: &ArchiveOptions,
+    ) -> Result<Self, TarError> {
+        TarFile::from_delimited_list(reader, 0, options)
+    }
+
+    /// Add each path of a list separated by `delimiter`, skipping empty entries.
+    fn from_delimited_list<R: Read>(
+        reader: R,
+        delimiter: u8,
+        options: &ArchiveOptions,
+    ) -> Result<Self, TarError> {
+        let mut out = TarFile::default();
+        let mut reader = BufReader::new(reader);
+        let mut path = Vec::new();
+
+        loop {
+            path.clear();
+            if reader.read_until(delimiter, &mut path)? == 0 {
+                break;
+            }
+            if path.last() == Some(&delimiter) {
+                path.pop();
+            }
+            if path.is_empty() {
+                continue;
+            }
+
+            out.append_with(
+                String::from_utf8(path.clone()).map_err(|e| e.utf8_error())?,
+                options,
+            )?;
+        }
+
+        Ok(out)
+    }
+}
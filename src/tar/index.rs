@@ -0,0 +1,109 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::tar::*;
+
+/// The position of an entry within the archive it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TarEntryLocation {
+    /// The position of the entry in `TarFile::nodes`.
+    pub index: usize,
+    /// The path of the entry.
+    pub name: String,
+    /// Byte offset of the first header block of the entry, including any extended headers.
+    pub header_offset: u64,
+    /// Byte offset of the entry data.
+    pub data_offset: u64,
+    /// Length of the entry data in bytes, without block padding.
+    pub size: u64,
+}
+
+impl TarFile {
+    /// Returns the location of each entry that was read from an archive, in archive order. Entries that
+    /// were added from the filesystem have no location and are not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let index = data.index();
+    /// assert_eq!(index[0].data_offset, 512);
+    /// ```
+    pub fn index(&self) -> Vec<TarEntryLocation> {
+        self.locations().collect()
+    }
+
+    /// Returns the number of entries in the index, without building it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// assert_eq!(data.entry_count(), 1);
+    /// ```
+    pub fn entry_count(&self) -> usize {
+        self.file.iter().filter(|n| n.location.is_some()).count()
+    }
+
+    /// Returns at most `limit` entries of the index, starting at the entry numbered `offset`. Only the
+    /// requested page is built, so large archives can be listed a page at a time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// assert_eq!(data.entries_page(0, 10).len(), 1);
+    /// assert!(data.entries_page(1, 10).is_empty());
+    /// ```
+    pub fn entries_page(&self, offset: usize, limit: usize) -> Vec<TarEntryLocation> {
+        self.locations().skip(offset).take(limit).collect()
+    }
+
+    /// Iterate over the location of each entry that was read from an archive.
+    fn locations(&self) -> impl Iterator<Item = TarEntryLocation> + '_ {
+        self.file.iter().enumerate().filter_map(|(index, n)| {
+            let (header_offset, data_offset) = n.location?;
+            Some(TarEntryLocation {
+                index,
+                name: n.name(),
+                header_offset,
+                data_offset,
+                size: n.size() as u64,
+            })
+        })
+    }
+}
+
+/// Wraps a reader and counts the bytes read through it.
+pub(crate) struct CountingReader<T> {
+    inner: T,
+    pub(crate) pos: u64,
+}
+
+impl<T> CountingReader<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        CountingReader { inner, pos: 0 }
+    }
+}
+
+impl<T: Read> Read for CountingReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Seek> CountingReader<T> {
+    /// Move `len` bytes forward without reading them.
+    pub(crate) fn skip(&mut self, len: u64) -> std::io::Result<()> {
+        self.inner.seek(SeekFrom::Current(len as i64))?;
+        self.pos += len;
+        Ok(())
+    }
+}
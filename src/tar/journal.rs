@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// PAX key marking a zero-byte entry as a journal record rather than a file.
+pub(crate) const JOURNAL_KEY: &str = "MINITAR.journal";
+/// PAX key holding the new name of a renamed file.
+pub(crate) const JOURNAL_TARGET_KEY: &str = "MINITAR.journal.target";
+
+/// Represents a change to the file tree recorded in an archive, rather than a file itself.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalOp {
+    /// The file was deleted.
+    Delete,
+    /// The file was renamed to the contained path.
+    Rename(String),
+}
+
+/// A journal record read back from an archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    /// The path the change applies to.
+    pub name: String,
+    /// The recorded change.
+    pub op: JournalOp,
+    /// The time the change was recorded, in seconds since the epoch.
+    pub mtime: u64,
+}
+
+impl TarNode {
+    /// Create a zero-byte journal entry recording `op` against `filename`.
+    fn journal(filename: String, op: JournalOp) -> Result<TarNode, TarError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let header = synthetic_header(&filename, FileType::Normal as u8, 0, now)?;
+
+        let pax = match op {
+            JournalOp::Delete => vec![PaxRecord::new(JOURNAL_KEY, "delete")],
+            JournalOp::Rename(to) => vec![
+                PaxRecord::new(JOURNAL_KEY, "rename"),
+                PaxRecord::new(JOURNAL_TARGET_KEY, to),
+            ],
+        };
+
+        Ok(TarNode {
+            header,
+            pax,
+            sparse: None,
+            location: None,
+            inode: None,
+            gnu_long_names: false,
+            data: Vec::new(),
+            lazy: None,
+        })
+    }
+
+    /// Returns the journal record carried by this node, or `None` if it is a regular entry.
+    pub fn journal_entry(&self) -> Option<JournalEntry> {
+        let op = match self.pax_value(JOURNAL_KEY)? {
+            "delete" => JournalOp::Delete,
+            "rename" => JournalOp::Rename(self.pax_value(JOURNAL_TARGET_KEY)?.to_string()),
+            _ => return None,
+        };
+
+        Some(JournalEntry {
+            name: self.name(),
+            op,
+            mtime: self.mtime().max(0) as u64,
+        })
+    }
+}
+
+impl TarFile {
+    /// Record that `filename` was deleted. This adds a zero-byte entry tagged with a ``MINITAR.journal`` PAX
+    /// record, so that a sequence of archives can express deletions for point-in-time restores.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.record_delete("old.txt".to_string()).unwrap();
+    /// assert_eq!(data.journal().len(), 1);
+    /// ```
+    pub fn record_delete(&mut self, filename: String) -> Result<(), TarError> {
+        self.file
+            .push(TarNode::journal(filename, JournalOp::Delete)?);
+        Ok(())
+    }
+
+    /// Record that `from` was renamed to `to`. This adds a zero-byte journal entry in the same way as
+    /// `record_delete`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.record_rename("a.txt".to_string(), "b.txt".to_string()).unwrap();
+    /// ```
+    pub fn record_rename(&mut self, from: String, to: String) -> Result<(), TarError> {
+        self.file
+            .push(TarNode::journal(from, JournalOp::Rename(to))?);
+        Ok(())
+    }
+
+    /// Returns all journal records contained in the archive, in archive order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// assert!(data.journal().is_empty());
+    /// ```
+    pub fn journal(&self) -> Vec<JournalEntry> {
+        self.file.iter().filter_map(|n| n.journal_entry()).collect()
+    }
+
+    /// Compute the state of the file tree at `timestamp` from a chain of archives, ordered from the full
+    /// archive to the newest incremental archive. Entries modified after `timestamp` are ignored, later
+    /// archives replace earlier versions of a file, and journal records apply their deletions and renames.
+    /// Names renamed beyond the ``100`` bytes of the header field are kept in PAX ``path`` records.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let full = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// let mut delta = TarFile::default();
+    /// delta.record_delete("test/1.txt".to_string()).unwrap();
+    ///
+    /// let state = TarFile::point_in_time(&[full, delta], u64::MAX).unwrap();
+    /// assert!(state.journal().is_empty());
+    /// ```
+    pub fn point_in_time(chain: &[TarFile], timestamp: u64) -> Result<TarFile, TarError> {
+        let mut state = BTreeMap::<String, TarNode>::new();
+
+        for archive in chain {
+            /* Entries from before 1970 are older than any timestamp */
+            let older = |n: &&TarNode| n.mtime() < 0 || n.mtime() as u64 <= timestamp;
+            for node in archive.file.iter().filter(older) {
+                let name = node.name();
+                match node.journal_entry().map(|j| j.op) {
+                    Some(JournalOp::Delete) => {
+                        let removed: Vec<String> = state
+                            .keys()
+                            .filter(|k| is_within(k, &name))
+                            .cloned()
+                            .collect();
+                        for k in removed {
+                            state.remove(&k);
+                        }
+                    }
+                    Some(JournalOp::Rename(to)) => {
+                        let moved: Vec<String> = state
+                            .keys()
+                            .filter(|k| is_within(k, &name))
+                            .cloned()
+                            .collect();
+                        /* Either name may end in a slash, so the rest of each key is found without it */
+                        let from = name.trim_end_matches('/');
+                        let to = to.trim_end_matches('/');
+                        for k in moved {
+                            let rest = k.strip_prefix(from).unwrap_or_default();
+                            if let Some(mut n) = state.remove(&k) {
+                                let renamed = format!("{}{}", to, rest);
+                                n.set_long_name(&renamed)?;
+                                state.insert(renamed, n);
+                            }
+                        }
+                    }
+                    None => {
+                        state.insert(name, node.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(TarFile {
+            file: state.into_values().collect(),
+            warnings: Vec::new(),
+        })
+    }
+}
+
+/// Returns `true` if `name` is `path` itself or is contained within the directory `path`.
+fn is_within(name: &str, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    name.trim_end_matches('/') == path || name.starts_with(&format!("{}/", path))
+}
+
+/// Restore the state of the file tree at `timestamp`, computed from a chain of archives with
+/// `TarFile::point_in_time`, into the `dest` directory.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{restore_point_in_time, TarFile};
+///
+/// let full = TarFile::new("test/1.txt".to_string()).unwrap();
+/// restore_point_in_time(&[full], u64::MAX, "target/doc-restore".to_string()).unwrap();
+/// ```
+pub fn restore_point_in_time(
+    chain: &[TarFile],
+    timestamp: u64,
+    dest: String,
+) -> Result<OperationStats, TarError> {
+    TarFile::point_in_time(chain, timestamp)?.extract(dest)
+}
@@ -0,0 +1,250 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileExt, MetadataExt};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+
+use crate::error::TarError;
+use crate::tar::reflink;
+
+/// The archive that the data of entries read on demand is kept in.
+#[derive(Clone, Debug)]
+pub(crate) enum Source {
+    /// The archive file, read with positioned reads.
+    File(Arc<File>),
+    /// A memory mapping of the archive file, which the data is borrowed from.
+    #[cfg(feature = "mmap")]
+    Map(Arc<memmap2::Mmap>),
+}
+
+impl Source {
+    /// Returns the length of the archive in bytes.
+    pub(crate) fn len(&self) -> io::Result<u64> {
+        match self {
+            Source::File(file) => Ok(file.metadata()?.len()),
+            #[cfg(feature = "mmap")]
+            Source::Map(map) => Ok(map.len() as u64),
+        }
+    }
+
+    /// Copy `len` bytes of the archive starting at `offset` to the current position of `output`, inside the
+    /// kernel with ``copy_file_range`` on Linux where the filesystems allow it. Returns the number of bytes
+    /// copied, which is fewer than `len` if the archive ends first.
+    pub(crate) fn copy_to_file(&self, offset: u64, len: u64, mut output: &File) -> io::Result<u64> {
+        let mut copied = 0;
+        #[cfg(target_os = "linux")]
+        if let Some(file) = self.file() {
+            copied = copy_file_range(file, offset, output, len)?;
+        }
+
+        /* Whatever the kernel couldn't copy goes through a buffer instead */
+        let mut range = Range {
+            source: self,
+            pos: offset + copied,
+            end: offset + len,
+        };
+        Ok(copied + io::copy(&mut range, &mut output)?)
+    }
+
+    /// Returns the archive file, unless the archive is mapped.
+    fn file(&self) -> Option<&File> {
+        match self {
+            Source::File(file) => Some(file),
+            #[cfg(feature = "mmap")]
+            Source::Map(_) => None,
+        }
+    }
+
+    /// Read from the archive starting at `offset` into `buf`, returning the number of bytes read.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read_at(buf, offset),
+            #[cfg(feature = "mmap")]
+            Source::Map(map) => {
+                let start = (offset as usize).min(map.len());
+                let n = buf.len().min(map.len() - start);
+                buf[..n].copy_from_slice(&map[start..start + n]);
+                Ok(n)
+            }
+        }
+    }
+}
+
+/// The data of an entry left in the archive it was read from, as described in `ReadOptions::lazy_data`.
+#[derive(Clone, Debug)]
+pub(crate) struct LazyData {
+    source: Source,
+    offset: u64,
+    len: usize,
+}
+
+impl LazyData {
+    /// Refer to the `len` bytes of `source` starting at `offset`.
+    pub(crate) fn new(source: Source, offset: u64, len: usize) -> Self {
+        LazyData {
+            source,
+            offset,
+            len,
+        }
+    }
+
+    /// Returns the length of the data in bytes.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the data, borrowed from the mapping of a mapped archive and read into memory otherwise.
+    pub(crate) fn load(&self) -> Result<Cow<'_, [u8]>, TarError> {
+        if let Some(data) = self.mapped() {
+            return Ok(Cow::Borrowed(data));
+        }
+
+        let mut data = Vec::with_capacity(self.len);
+        self.write_to(self.len, &mut data)?;
+        Ok(Cow::Owned(data))
+    }
+
+    /// Copy the first `len` bytes of the data to `output`, failing with `TarError::Truncated` if the
+    /// archive has been cut short since it was read.
+    pub(crate) fn write_to<W: Write>(&self, len: usize, mut output: W) -> Result<usize, TarError> {
+        let len = len.min(self.len);
+        if let Some(data) = self.mapped() {
+            output.write_all(&data[..len])?;
+            return Ok(len);
+        }
+
+        let mut range = Range {
+            source: &self.source,
+            pos: self.offset,
+            end: self.offset + len as u64,
+        };
+        if io::copy(&mut range, &mut output)? != len as u64 {
+            return Err(TarError::Truncated);
+        }
+        Ok(len)
+    }
+
+    /// Copy the first `len` bytes of the data to the current position of `output` in the same way as
+    /// `write_to`, without passing it through user space where the kernel allows.
+    pub(crate) fn copy_to_file(&self, len: usize, output: &File) -> Result<usize, TarError> {
+        let len = len.min(self.len) as u64;
+        if self.source.copy_to_file(self.offset, len, output)? != len {
+            return Err(TarError::Truncated);
+        }
+        Ok(len as usize)
+    }
+
+    /// Fill the empty `output` with the data by sharing the blocks of the archive file with it, on
+    /// filesystems with reflinks. Only whole blocks can be shared, so the data must start on a block
+    /// boundary, and the partial block at its end is copied. Returns `false`, having written nothing, if
+    /// the blocks can't be shared.
+    pub(crate) fn clone_to_file(&self, mut output: &File) -> Result<bool, TarError> {
+        let file = match self.source.file() {
+            Some(file) => file,
+            None => return Ok(false),
+        };
+        let block = output.metadata()?.blksize().max(1);
+        let shared = self.len as u64 / block * block;
+        if !self.offset.is_multiple_of(block)
+            || shared == 0
+            || !reflink::clone_range(file, self.offset, shared, output)
+        {
+            return Ok(false);
+        }
+
+        let rest = self.len as u64 - shared;
+        output.seek(SeekFrom::Start(shared))?;
+        if self
+            .source
+            .copy_to_file(self.offset + shared, rest, output)?
+            != rest
+        {
+            return Err(TarError::Truncated);
+        }
+        Ok(true)
+    }
+
+    /// Returns the archive file and the offset the data starts at, unless the archive is mapped.
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub(crate) fn file_range(&self) -> Option<(&File, u64)> {
+        self.source.file().map(|file| (file, self.offset))
+    }
+
+    /// Returns the data as a slice of the mapping, if the archive is mapped.
+    fn mapped(&self) -> Option<&[u8]> {
+        match &self.source {
+            #[cfg(feature = "mmap")]
+            Source::Map(map) => {
+                let start = self.offset as usize;
+                Some(&map[start..start + self.len])
+            }
+            Source::File(_) => None,
+        }
+    }
+}
+
+/// Reads a range of an archive with positioned reads, so clones of a node sharing the file don't disturb
+/// each other.
+struct Range<'a> {
+    source: &'a Source,
+    pos: u64,
+    end: u64,
+}
+
+impl Read for Range<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (self.end - self.pos).min(buf.len() as u64) as usize;
+        let n = self.source.read_at(&mut buf[..len], self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// Copy up to `len` bytes of `input` starting at `offset` to the current position of `output` with
+/// ``copy_file_range``, stopping early at the end of `input` or when the kernel or filesystems can't copy
+/// between the files. Returns the number of bytes copied.
+#[cfg(target_os = "linux")]
+fn copy_file_range(input: &File, offset: u64, output: &File, len: u64) -> io::Result<u64> {
+    let mut off_in = offset as libc::loff_t;
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(1 << 30) as usize;
+        /* SAFETY: both descriptors stay open for the call, and `off_in` outlives it */
+        let n = unsafe {
+            libc::copy_file_range(
+                input.as_raw_fd(),
+                &mut off_in,
+                output.as_raw_fd(),
+                std::ptr::null_mut(),
+                chunk,
+                0,
+            )
+        };
+        if n > 0 {
+            copied += n as u64;
+            continue;
+        }
+        if n == 0 {
+            break;
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            /* Old kernels, copies across filesystems and files opened for appending are left to the
+             * buffered copy */
+            Some(
+                libc::ENOSYS
+                | libc::EXDEV
+                | libc::EINVAL
+                | libc::EOPNOTSUPP
+                | libc::EPERM
+                | libc::EBADF,
+            ) => break,
+            _ => return Err(err),
+        }
+    }
+    Ok(copied)
+}
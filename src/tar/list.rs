@@ -0,0 +1,244 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// Metadata describing an entry of an archive, decoded from its headers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryInfo {
+    /// The position of the entry in `TarFile::nodes`.
+    pub index: usize,
+    /// The path of the entry.
+    pub name: String,
+    /// The size of the file in bytes. For sparse files this is the apparent size, including holes.
+    pub size: u64,
+    /// The type of the entry.
+    pub file_type: FileType,
+    /// The permission bits of the entry.
+    pub mode: u32,
+    /// The numeric user id of the owner.
+    pub uid: u64,
+    /// The numeric group id of the owner.
+    pub gid: u64,
+    /// The user name of the owner, which may be empty.
+    pub uname: String,
+    /// The group name of the owner, which may be empty.
+    pub gname: String,
+    /// The modification time in seconds since the epoch, negative for times before 1970.
+    pub mtime: i64,
+    /// The fraction of a second of the modification time in nanoseconds, from a PAX ``mtime`` record.
+    pub mtime_nanos: u32,
+    /// The target of a symbolic or hard link, if the entry is one.
+    pub link_target: Option<String>,
+}
+
+impl fmt::Display for EntryInfo {
+    /// Format the entry like a line of ``tar -tvf`` output, for example
+    /// ``-rw-r--r-- user/group        21 2022-06-02 17:05 1.txt``. Times are shown in UTC.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.file_type {
+            FileType::Dir => 'd',
+            FileType::Sym => 'l',
+            FileType::Hard => 'h',
+            FileType::Char => 'c',
+            FileType::Block => 'b',
+            FileType::FIFO => 'p',
+            FileType::Normal | FileType::GnuSparse => '-',
+            _ => '?',
+        };
+
+        /* Names that are missing fall back to the numeric ids, as GNU tar does */
+        let user = match self.uname.is_empty() {
+            true => self.uid.to_string(),
+            false => self.uname.clone(),
+        };
+        let group = match self.gname.is_empty() {
+            true => self.gid.to_string(),
+            false => self.gname.clone(),
+        };
+        let owner = format!("{}/{}", user, group);
+        let size = self.size.to_string();
+        let width = 19usize.saturating_sub(owner.len()).max(size.len() + 1);
+
+        write!(
+            f,
+            "{}{} {}{:>width$} {} {}",
+            kind,
+            mode_string(self.mode),
+            owner,
+            size,
+            date_string(self.mtime),
+            self.name,
+            width = width
+        )?;
+        match (&self.link_target, self.file_type) {
+            (Some(target), FileType::Sym) => write!(f, " -> {}", target),
+            (Some(target), FileType::Hard) => write!(f, " link to {}", target),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl TarFile {
+    /// Returns the metadata of each entry in the archive, in archive order. Journal records are not
+    /// included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{FileType, TarFile};
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let entries = data.list();
+    /// assert_eq!(entries[0].name, "1.txt");
+    /// assert_eq!(entries[0].file_type, FileType::Normal);
+    /// ```
+    pub fn list(&self) -> Vec<EntryInfo> {
+        self.file
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.journal_entry().is_none())
+            .map(|(i, n)| n.info_at(i))
+            .collect()
+    }
+
+    /// List the names of the entries in an archive without loading their data. Each header is read and
+    /// the payload that follows it is skipped with ``std::io::Seek``, so listing a large archive only
+    /// touches its headers. Journal records are left out, as in `list`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use minitar::tar::TarFile;
+    ///
+    /// let file = File::open("test/1.tar").unwrap();
+    /// assert_eq!(TarFile::list_from(file).unwrap(), vec!["1.txt".to_string()]);
+    /// ```
+    pub fn list_from<R: Read + Seek>(mut input: R) -> Result<Vec<String>, TarError> {
+        let mut out = Vec::new();
+        let options = ReadOptions::default();
+
+        let mut trailer = true;
+        while let Some(node) = next_entry_header(&mut input, &options, &mut trailer)? {
+            if node.journal_entry().is_none() {
+                out.push(node.name());
+            }
+            input.seek(SeekFrom::Current((node.data_blocks() * 512) as i64))?;
+        }
+
+        Ok(out)
+    }
+}
+
+impl TarNode {
+    /// Decode the metadata of this entry, found at `index` in its archive.
+    pub(crate) fn info_at(&self, index: usize) -> EntryInfo {
+        EntryInfo {
+            index,
+            ..self.info()
+        }
+    }
+
+    /// Decode the metadata of this entry, for comparisons that don't need its position. The index is
+    /// left at ``0``.
+    pub(crate) fn info(&self) -> EntryInfo {
+        let file_type = self.entry_type();
+        let link_target = match file_type {
+            FileType::Hard | FileType::Sym => Some(self.link_name()),
+            _ => None,
+        };
+
+        EntryInfo {
+            index: 0,
+            name: self.name(),
+            size: self.apparent_size(),
+            file_type,
+            mode: self.mode(),
+            uid: self.uid(),
+            gid: self.gid(),
+            uname: self.uname(),
+            gname: self.gname(),
+            mtime: self.mtime(),
+            mtime_nanos: self.mtime_nanos(),
+            link_target,
+        }
+    }
+
+    /// Returns the type of this entry, as listed.
+    pub(crate) fn entry_type(&self) -> FileType {
+        match self.file_type() {
+            /* Old archives use a NUL type flag for regular files */
+            0x00 | 0x30 => FileType::Normal,
+            0x31 => FileType::Hard,
+            0x32 => FileType::Sym,
+            0x33 => FileType::Char,
+            0x34 => FileType::Block,
+            0x35 => FileType::Dir,
+            0x36 => FileType::FIFO,
+            0x53 => FileType::GnuSparse,
+            _ => FileType::Unknown,
+        }
+    }
+
+    /// Returns the size of the file this entry holds, which for sparse files includes their holes.
+    pub(crate) fn apparent_size(&self) -> u64 {
+        match (
+            &self.sparse,
+            self.pax_value("GNU.sparse.realsize")
+                .or_else(|| self.pax_value("GNU.sparse.size")),
+        ) {
+            (Some(map), _) => map.real_size,
+            (None, Some(real)) => real.parse().unwrap_or(self.size() as u64),
+            (None, None) => self.gnu_real_size().unwrap_or(self.size() as u64),
+        }
+    }
+}
+
+/// Render permission bits as ``rwxr-xr-x``, including the setuid, setgid and sticky bits.
+fn mode_string(mode: u32) -> String {
+    let mut out = String::with_capacity(9);
+    for (i, special) in [(6, 0o4000), (3, 0o2000), (0, 0o1000)] {
+        let bits = mode >> i;
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        let (set, unset) = match i {
+            0 => ('t', 'T'),
+            _ => ('s', 'S'),
+        };
+        out.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    out
+}
+
+/// Render seconds since the epoch as a ``YYYY-MM-DD HH:MM`` UTC date.
+fn date_string(time: i64) -> String {
+    let days = time.div_euclid(86400);
+    let secs = time.rem_euclid(86400);
+
+    /* Convert days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html */
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
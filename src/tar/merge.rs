@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{TarError, TarWarning};
+use crate::tar::*;
+
+/// What to do when an entry being added has the same name as an entry already in the archive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep the entry already in the archive and drop the new one.
+    KeepFirst,
+    /// Replace the entry already in the archive with the new one.
+    KeepLast,
+    /// Keep both entries. When extracted, the later entry overwrites the earlier one.
+    KeepBoth,
+    /// Refuse with `TarError::DuplicateEntry`, leaving the archive unchanged.
+    Error,
+}
+
+impl TarFile {
+    /// Append all entries of `other` to this archive, resolving names that exist in both according to
+    /// `policy`. Journal records are always appended.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{DuplicatePolicy, TarFile};
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let other = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.merge(other, DuplicatePolicy::Error).unwrap();
+    /// ```
+    pub fn merge(&mut self, other: TarFile, policy: DuplicatePolicy) -> Result<(), TarError> {
+        let existing: HashSet<String> = self
+            .file
+            .iter()
+            .filter(|n| n.journal_entry().is_none())
+            .map(|n| n.name())
+            .collect();
+        let is_duplicate =
+            |n: &TarNode| n.journal_entry().is_none() && existing.contains(&n.name());
+
+        match policy {
+            DuplicatePolicy::Error => {
+                if let Some(n) = other.file.iter().find(|n| is_duplicate(n)) {
+                    return Err(TarError::DuplicateEntry(n.name()));
+                }
+                self.extend(other.file);
+            }
+            DuplicatePolicy::KeepBoth => self.extend(other.file),
+            DuplicatePolicy::KeepFirst => {
+                self.extend(other.file.into_iter().filter(|n| !is_duplicate(n)));
+            }
+            DuplicatePolicy::KeepLast => {
+                let replaced: HashSet<String> = other
+                    .file
+                    .iter()
+                    .filter(|n| is_duplicate(n))
+                    .map(|n| n.name())
+                    .collect();
+                self.file
+                    .retain(|n| n.journal_entry().is_some() || !replaced.contains(&n.name()));
+                self.extend(other.file);
+            }
+        }
+
+        self.warnings.extend(other.warnings);
+        Ok(())
+    }
+
+    /// Returns the names shared by more than one entry, in the order they first appear, with the number of
+    /// entries sharing each. ``dir`` and ``./dir/`` count as the same name, since they extract to the same
+    /// path. Journal records are not checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    /// data.append("test/1.txt".to_string()).unwrap();
+    /// assert_eq!(data.duplicates(), vec![("test/1.txt".to_string(), 2)]);
+    /// ```
+    pub fn duplicates(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut order = Vec::new();
+        for node in self.file.iter().filter(|n| n.journal_entry().is_none()) {
+            let key = duplicate_key(&node.name());
+            let count = counts.entry(key.clone()).or_insert(0);
+            if *count == 1 {
+                order.push(key);
+            }
+            *count += 1;
+        }
+
+        order
+            .into_iter()
+            .map(|key| {
+                let count = counts[&key];
+                (key, count)
+            })
+            .collect()
+    }
+
+    /// Fail with `TarError::DuplicateEntry` for the first name shared by more than one entry, as found by
+    /// `duplicates`.
+    pub fn check_duplicates(&self) -> Result<(), TarError> {
+        match self.duplicates().into_iter().next() {
+            Some((name, _)) => Err(TarError::DuplicateEntry(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Add a node read from disk, resolving a name that is already in the archive according to the duplicates
+    /// policy of `options`. Keeping both entries reports a `TarWarning::DuplicateName`. Returns `false` if
+    /// the node was left out in favour of the entry already there.
+    pub(crate) fn push_unique(
+        &mut self,
+        node: TarNode,
+        options: &ArchiveOptions,
+    ) -> Result<bool, TarError> {
+        let name = node.name();
+        let exists = self
+            .file
+            .iter()
+            .any(|n| n.journal_entry().is_none() && n.name() == name);
+
+        match options.duplicates {
+            _ if !exists => (),
+            DuplicatePolicy::Error => return Err(TarError::DuplicateEntry(name)),
+            DuplicatePolicy::KeepFirst => return Ok(false),
+            DuplicatePolicy::KeepLast => {
+                self.remove_every(name);
+            }
+            DuplicatePolicy::KeepBoth => {
+                options.report(&mut self.warnings, TarWarning::DuplicateName(name))
+            }
+        }
+
+        self.file.push(node);
+        Ok(true)
+    }
+}
+
+/// Returns the path `name` extracts to, for comparing entry names.
+fn duplicate_key(name: &str) -> String {
+    let name = name.trim_start_matches("./").trim_end_matches('/');
+    match name {
+        "" => ".".to_string(),
+        n => n.to_string(),
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error::{TarError, TarWarning};
+use crate::tar::PaxRecord;
+
+/// What to do with a file or link name that does not fit in its ustar header field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LongNamePolicy {
+    /// Refuse to add the entry with `TarError::NameTooLong`.
+    Error,
+    /// Store as much of the name as fits and record a `TarWarning::NameTruncated`.
+    Truncate,
+    /// Shorten the name and add a hash of the full name, so distinct long names stay distinct.
+    HashSuffix,
+    /// Store the full name in a PAX record. This is an extension, so it is not valid for strict ustar.
+    Pax,
+}
+
+/// Fit `name` into a header field of `len` bytes according to `policy`. Returns the name to store in the
+/// field, along with a PAX record under `key` when the full name has to be carried separately.
+pub(crate) fn fit_name(
+    name: &str,
+    len: usize,
+    key: &str,
+    policy: LongNamePolicy,
+    warnings: &mut Vec<TarWarning>,
+) -> Result<(String, Option<PaxRecord>), TarError> {
+    if name.len() <= len {
+        return Ok((name.to_string(), None));
+    }
+
+    match policy {
+        LongNamePolicy::Error => Err(TarError::NameTooLong),
+        LongNamePolicy::Truncate => {
+            let stored = truncate(name, len).to_string();
+            warnings.push(TarWarning::NameTruncated {
+                name: name.to_string(),
+                stored: stored.clone(),
+            });
+            Ok((stored, None))
+        }
+        LongNamePolicy::HashSuffix => {
+            /* Keep a short extension so the renamed file is still recognizable */
+            let base = name.rsplit('/').next().unwrap_or(name);
+            let ext = match base.rfind('.') {
+                Some(i) if i > 0 && base.len() - i <= 10 => &base[i..],
+                _ => "",
+            };
+            let suffix = format!("~{:016x}{}", fnv1a(name.as_bytes()), ext);
+            let stored = format!("{}{}", truncate(name, len - suffix.len()), suffix);
+            warnings.push(TarWarning::NameTruncated {
+                name: name.to_string(),
+                stored: stored.clone(),
+            });
+            Ok((stored, None))
+        }
+        LongNamePolicy::Pax => Ok((
+            truncate(name, len).to_string(),
+            Some(PaxRecord::new(key, name)),
+        )),
+    }
+}
+
+/// Make `name` relative and free of ``..`` components, like GNU tar's "Removing leading `/'". Leading
+/// slashes are dropped and each ``..`` removes the component before it, or is dropped at the start.
+/// Returns `None` if the name is already safe.
+pub(crate) fn sanitize(name: &str) -> Option<String> {
+    let unsafe_name = name.starts_with('/') || name.split('/').any(|c| c == "..");
+    if !unsafe_name {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for component in name.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            c => parts.push(c),
+        }
+    }
+
+    /* Keep the trailing slash of directory names */
+    let mut out = parts.join("/");
+    if out.is_empty() {
+        out.push('.');
+    }
+    if name.ends_with('/') && out != "." {
+        out.push('/');
+    }
+    Some(out)
+}
+
+/// Split `name` at a ``/`` into the ``155`` byte ustar prefix and ``100`` byte name fields. Returns `None`
+/// if there is no such split.
+pub(crate) fn split_ustar(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+
+    name.match_indices('/')
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && !rest.is_empty() && rest.len() <= 100)
+}
+
+/// Truncate `name` to at most `len` bytes without splitting a UTF-8 character.
+pub(crate) fn truncate(name: &str, len: usize) -> &str {
+    let mut end = len.min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// 64-bit FNV-1a, used because its output is stable across platforms and releases.
+fn fnv1a(input: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for b in input {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
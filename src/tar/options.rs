@@ -0,0 +1,877 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::TarWarning;
+use crate::tar::{
+    owner, CancelToken, DuplicatePolicy, LongNamePolicy, OwnerResolver, Profile, SparseFormat,
+    Transform, Verbosity, Verifier,
+};
+
+/// Options controlling how an archive is parsed.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::ReadOptions;
+///
+/// let options = ReadOptions::default().ignore_zeros(true);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    pub(crate) ignore_zeros: bool,
+    pub(crate) buffer_size: usize,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) verifier: Option<Arc<dyn Verifier>>,
+    pub(crate) profile: Profile,
+    pub(crate) lazy_data: bool,
+    pub(crate) cancel: Option<CancelToken>,
+    pub(crate) rate_limit: Option<u64>,
+    #[cfg(feature = "mmap")]
+    pub(crate) mmap: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            ignore_zeros: false,
+            buffer_size: 64 * 1024,
+            timeout: None,
+            deadline: None,
+            verifier: None,
+            profile: Profile::Unrestricted,
+            lazy_data: false,
+            cancel: None,
+            rate_limit: None,
+            #[cfg(feature = "mmap")]
+            mmap: false,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Skip blocks of zeros instead of treating them as the end of the archive, and keep parsing until
+    /// the end of the input. This allows reading concatenated archives such as ``cat a.tar b.tar``.
+    pub fn ignore_zeros(mut self, ignore: bool) -> Self {
+        self.ignore_zeros = ignore;
+        self
+    }
+
+    /// Set the size in bytes of the buffer that `TarFile::open_with` reads the archive through. One buffer
+    /// is filled again and again for the whole archive, and larger ones take fewer reads to get through big
+    /// archives. Defaults to 64 KiB.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Fail with `TarError::Timeout` when a single read from the input takes longer than `timeout`. A
+    /// blocking read can only be interrupted by the input itself, so for sockets use `TarFile::open_tcp`,
+    /// which applies the limit to the socket; other inputs are checked when each read returns.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail with `TarError::Timeout` when reading the whole archive takes longer than `deadline`, so a
+    /// client that keeps trickling data can't hold a reader forever.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Check the signature of each entry with `verifier` as it is read, as described in `TarNode::verify`,
+    /// rejecting the archive if any entry is unsigned or has been tampered with.
+    pub fn verifier<V: Verifier + 'static>(mut self, verifier: V) -> Self {
+        self.verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Reject the archive with `TarError::Disallowed` if it holds an entry that `profile` doesn't allow.
+    /// Defaults to `Profile::Unrestricted`.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Only read the headers when opening an archive with `TarFile::open_with`, recording where the data of
+    /// each entry is and reading it from the archive when the entry is written, extracted or compared. This
+    /// keeps memory use low when editing an archive bigger than RAM. The archive must not be changed while
+    /// it is in use, so write the edited archive to a new file and rename it over the old one rather than
+    /// writing it in place. Archives read from a stream are always read whole. Extracting copies the data
+    /// into the extracted files inside the kernel with ``copy_file_range`` on Linux where the filesystems
+    /// allow, but writing an archive copies it through a buffer, as the writer need not be a file.
+    pub fn lazy_data(mut self, lazy: bool) -> Self {
+        self.lazy_data = lazy;
+        self
+    }
+
+    /// Stop reading the archive with `TarError::Cancelled` once `token` is cancelled. The token is checked
+    /// before each entry, and nothing is returned of the entries read so far.
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Read the archive at no more than `bytes_per_second` on average, sleeping between reads to keep to
+    /// it, so a background job doesn't starve other users of the disk or network. Up to a second of unused
+    /// allowance may be spent at full speed after a pause. Data left in the archive by `lazy_data` or
+    /// `mmap` is read when it is used, without the limit.
+    pub fn rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    /// Map the archive into memory when opening it with `TarFile::open_with`, and read its entries in the
+    /// same way as `lazy_data`, except that `TarNode::data` borrows the data from the mapping instead of
+    /// copying it. Tools that read many entries without changing them, such as indexers, then allocate
+    /// next to nothing. The archive must not be changed or truncated while it is mapped, which would
+    /// crash the process when the missing data is read.
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+}
+
+/// Options controlling how an archive is serialized.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::WriteOptions;
+///
+/// let options = WriteOptions::default().blocking_factor(1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    pub(crate) blocking_factor: usize,
+    pub(crate) sparse_format: SparseFormat,
+    pub(crate) strict: bool,
+    pub(crate) cancel: Option<CancelToken>,
+    pub(crate) rate_limit: Option<u64>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            blocking_factor: 20,
+            sparse_format: SparseFormat::Pax1_0,
+            strict: false,
+            cancel: None,
+            rate_limit: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Set the number of ``512`` byte blocks per record. The archive is padded with zeros to a whole number
+    /// of records; the default of 20 matches GNU tar, while a factor of 1 produces minimal archives.
+    pub fn blocking_factor(mut self, factor: usize) -> Self {
+        self.blocking_factor = factor;
+        self
+    }
+
+    /// Set the representation used for sparse entries. Defaults to `SparseFormat::Pax1_0`.
+    pub fn sparse_format(mut self, format: SparseFormat) -> Self {
+        self.sparse_format = format;
+        self
+    }
+
+    /// Fail with `TarError::InvalidChecksum` when a header has a stale checksum, instead of recomputing it.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Stop writing an archive with `TarError::Cancelled` once `token` is cancelled. The token is checked
+    /// before each entry, so the output ends after the last whole entry written, without the blocks that
+    /// mark the end of the archive.
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Write the archive at no more than `bytes_per_second` on average, in the same way as
+    /// `ReadOptions::rate_limit`. This applies to `TarFile::write_with` and to the entries written by a
+    /// `TarBuilder`.
+    pub fn rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limit = Some(bytes_per_second);
+        self
+    }
+}
+
+/// What to do with special files when adding them to an archive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecialPolicy {
+    /// Archive FIFOs and devices. Other special files that tar can't represent are skipped with a
+    /// `TarWarning::SpecialSkipped`.
+    Archive,
+    /// Skip all special files with a `TarWarning::SpecialSkipped`.
+    Skip,
+    /// Refuse to add special files with `TarError::SpecialFile`.
+    Error,
+}
+
+/// What to do with sockets when adding them to an archive. Tar has no way to represent a socket, so they
+/// are never archived.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SocketPolicy {
+    /// Skip sockets with a `TarWarning::SocketSkipped`, like GNU tar.
+    Warn,
+    /// Skip sockets silently.
+    Ignore,
+    /// Refuse to add sockets with `TarError::SpecialFile`.
+    Error,
+}
+
+/// Options controlling how files are added to an archive.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{ArchiveOptions, LongNamePolicy};
+///
+/// let options = ArchiveOptions::default().long_names(LongNamePolicy::Pax);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ArchiveOptions {
+    pub(crate) long_names: LongNamePolicy,
+    pub(crate) specials: SpecialPolicy,
+    pub(crate) sockets: SocketPolicy,
+    pub(crate) uname: Option<String>,
+    pub(crate) gname: Option<String>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) mtime: Option<i64>,
+    pub(crate) resolver: Arc<dyn OwnerResolver>,
+    pub(crate) excludes: Vec<String>,
+    pub(crate) duplicates: DuplicatePolicy,
+    pub(crate) transform: Option<Transform>,
+    pub(crate) sanitize: bool,
+    pub(crate) reproducible: bool,
+    pub(crate) source_date_epoch: Option<u64>,
+    pub(crate) hard_links: bool,
+    pub(crate) dereference: bool,
+    pub(crate) verbosity: Verbosity,
+    pub(crate) sparse: bool,
+    pub(crate) xattrs: bool,
+    pub(crate) capabilities: bool,
+    pub(crate) precise_mtime: bool,
+    #[cfg(feature = "gitignore")]
+    pub(crate) gitignore: bool,
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub(crate) io_uring: bool,
+    pub(crate) compression_threads: usize,
+    pub(crate) read_threads: usize,
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd_dictionary: Option<Vec<u8>>,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> ArchiveOptions {
+        ArchiveOptions {
+            long_names: LongNamePolicy::Error,
+            specials: SpecialPolicy::Archive,
+            sockets: SocketPolicy::Warn,
+            uname: None,
+            gname: None,
+            uid: None,
+            gid: None,
+            mode: None,
+            mtime: None,
+            resolver: owner::system(),
+            excludes: Vec::new(),
+            duplicates: DuplicatePolicy::KeepBoth,
+            transform: None,
+            sanitize: true,
+            reproducible: false,
+            source_date_epoch: None,
+            hard_links: true,
+            dereference: false,
+            verbosity: Verbosity::Normal,
+            sparse: false,
+            xattrs: false,
+            capabilities: false,
+            precise_mtime: false,
+            #[cfg(feature = "gitignore")]
+            gitignore: false,
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            io_uring: false,
+            compression_threads: 1,
+            read_threads: 1,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+        }
+    }
+}
+
+impl ArchiveOptions {
+    /// Set what happens to file and link names that don't fit in the ``100`` byte ustar fields. Defaults
+    /// to `LongNamePolicy::Error`.
+    pub fn long_names(mut self, policy: LongNamePolicy) -> Self {
+        self.long_names = policy;
+        self
+    }
+
+    /// Set what happens to FIFOs, character and block devices, and other special files. Defaults to
+    /// `SpecialPolicy::Archive`.
+    pub fn specials(mut self, policy: SpecialPolicy) -> Self {
+        self.specials = policy;
+        self
+    }
+
+    /// Set what happens to sockets. Defaults to `SocketPolicy::Warn`.
+    pub fn sockets(mut self, policy: SocketPolicy) -> Self {
+        self.sockets = policy;
+        self
+    }
+
+    /// Store `name` as the user name of every entry instead of looking it up from the owner's uid.
+    pub fn uname(mut self, name: String) -> Self {
+        self.uname = Some(name);
+        self
+    }
+
+    /// Store `name` as the group name of every entry instead of looking it up from the owner's gid.
+    pub fn gname(mut self, name: String) -> Self {
+        self.gname = Some(name);
+        self
+    }
+
+    /// Store `uid` as the owner of every entry, like ``--owner``. The user name is looked up from `uid`
+    /// unless `uname` is also set.
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Store `gid` as the group of every entry, like ``--group``. The group name is looked up from `gid`
+    /// unless `gname` is also set.
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Store `mode` as the permission bits of every entry, like ``--mode``. Only the low twelve bits, the
+    /// permissions and the setuid, setgid and sticky bits, are kept.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode & 0o7777);
+        self
+    }
+
+    /// Store `mtime`, in seconds since the epoch, as the modification time of every entry, like ``--mtime``.
+    /// This takes precedence over the clamping of reproducible archives.
+    pub fn mtime(mut self, mtime: i64) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Look up the user and group names of owners with `resolver` instead of the system databases. Wrap it
+    /// in a `CachedResolver` if its lookups are slow.
+    pub fn resolver<R: OwnerResolver + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Set what happens when a file is added under a name that is already in the archive. Defaults to
+    /// `DuplicatePolicy::KeepBoth`, which records a `TarWarning::DuplicateName`.
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Rewrite the name each file is stored under with `transform`. Files are still read from their
+    /// original paths, and excludes match the original paths.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Strip leading slashes and ``..`` components from stored names, so that adding ``/etc/passwd`` or
+    /// ``../secrets`` can't make an extraction write outside its destination. Each rewritten name records a
+    /// `TarWarning::NameSanitized` and keeps its original path in a ``MINITAR.original_path`` PAX record.
+    /// Defaults to on; turn it off for the equivalent of ``--absolute-names``.
+    pub fn sanitize_names(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Produce bit-identical archives from identical inputs, for reproducible builds. Owners are stored as
+    /// uid and gid 0 with empty names unless `uname` or `gname` are set, modes are normalized to ``0755``
+    /// for directories and executables and ``0644`` for everything else, and modification times are
+    /// clamped to ``SOURCE_DATE_EPOCH`` if it is set in the environment when this is called.
+    /// `TarFile::append_dir_all` already adds entries in sorted order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::ArchiveOptions;
+    ///
+    /// let options = ArchiveOptions::default()
+    ///     .reproducible(true)
+    ///     .source_date_epoch(1_600_000_000);
+    /// ```
+    pub fn reproducible(mut self, reproducible: bool) -> Self {
+        self.reproducible = reproducible;
+        if reproducible && self.source_date_epoch.is_none() {
+            self.source_date_epoch = std::env::var("SOURCE_DATE_EPOCH")
+                .ok()
+                .and_then(|v| v.trim().parse().ok());
+        }
+        self
+    }
+
+    /// Store later names of a file with several hard links as hard link entries referring to the first name
+    /// added, instead of storing its data again. Defaults to on.
+    pub fn hard_links(mut self, hard_links: bool) -> Self {
+        self.hard_links = hard_links;
+        self
+    }
+
+    /// Follow symbolic links, like ``--dereference``, storing the content and metadata of the file each
+    /// link points to instead of the link itself. Directory trees are walked through linked directories,
+    /// skipping any directory already visited with a `TarWarning::FilesystemLoop`. A link that points
+    /// nowhere fails with `TarError::Io`.
+    pub fn dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Set which warnings are recorded while adding files. Defaults to `Verbosity::Normal`.
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Record `warning` if it is reported at the configured verbosity.
+    pub(crate) fn report(&self, warnings: &mut Vec<TarWarning>, warning: TarWarning) {
+        if warning.verbosity() <= self.verbosity {
+            warnings.push(warning);
+        }
+    }
+
+    /// Clamp the modification times of reproducible archives to `epoch`, overriding ``SOURCE_DATE_EPOCH``.
+    pub fn source_date_epoch(mut self, epoch: u64) -> Self {
+        self.source_date_epoch = Some(epoch);
+        self
+    }
+
+    /// Store regular files with holes, such as virtual machine images, as sparse entries holding only the
+    /// regions with data, like ``--sparse``. The holes are found with ``SEEK_DATA`` and ``SEEK_HOLE``.
+    /// Entries are written in the `SparseFormat` of the `WriteOptions`. Defaults to `false`.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Store the extended attributes of each file, such as ``user.*`` and ``security.selinux``, in
+    /// ``SCHILY.xattr.*`` PAX records as GNU tar and bsdtar do. Supported on Linux and macOS. Defaults to
+    /// `false`.
+    pub fn xattrs(mut self, xattrs: bool) -> Self {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Store the file capabilities of executables such as ``ping``, held in the ``security.capability``
+    /// extended attribute, even when `xattrs` is off, so container image layers keep them. Defaults to
+    /// `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::ArchiveOptions;
+    ///
+    /// let options = ArchiveOptions::default().capabilities(true);
+    /// ```
+    pub fn capabilities(mut self, capabilities: bool) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Keep the nanoseconds of modification times in a PAX ``mtime`` record, which extraction restores, for
+    /// build systems that compare precise timestamps. Each entry with a fraction then takes an extra PAX
+    /// header. Defaults to `false`.
+    pub fn precise_mtime(mut self, precise: bool) -> Self {
+        self.precise_mtime = precise;
+        self
+    }
+
+    /// Skip files ignored by ``.gitignore`` and ``.ignore`` files when archiving directories with
+    /// `TarFile::append_dir_all_with`, as git would. Requires the ``gitignore`` feature.
+    #[cfg(feature = "gitignore")]
+    pub fn gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    /// Read the regular files found by `TarFile::append_dir_all_with` through io_uring, submitting the
+    /// reads of up to 64 files at once so the kernel can overlap them instead of waiting on each in turn.
+    /// This mostly helps trees of many small files on storage with deep queues, such as NVMe drives and
+    /// network filesystems. Files of 16 MiB or more are read as usual, and so is everything where the
+    /// kernel doesn't support io_uring or it is disabled. Requires the ``uring`` feature and Linux.
+    /// Defaults to `false`.
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub fn io_uring(mut self, io_uring: bool) -> Self {
+        self.io_uring = io_uring;
+        self
+    }
+
+    /// Stat and read the files found by `TarFile::append_dir_all_with` on `threads` worker threads, or one
+    /// per CPU with ``0``, while entries are still added in the same order as on one thread. Files with
+    /// several hard links are read in order as they are reached, so later names can refer back to the
+    /// first. This speeds up archiving trees of many small files, where the time goes on waiting for each
+    /// file in turn. Directories walked with `gitignore` are read on one thread, and `io_uring` isn't used
+    /// with more than one. Defaults to ``1``.
+    pub fn read_threads(mut self, threads: usize) -> Self {
+        self.read_threads = threads;
+        self
+    }
+
+    /// Compress archives written by `tar::create` on `threads` threads, or one per CPU with ``0``. Gzip
+    /// input is cut into 1 MiB chunks that are compressed at the same time and written in order as
+    /// separate gzip members, like ``pigz``, which every gzip decoder reads back as one stream at the cost
+    /// of slightly larger output. Zstd uses its own multithreaded compression, whose output is the same
+    /// format. Defaults to ``1``.
+    pub fn compression_threads(mut self, threads: usize) -> Self {
+        self.compression_threads = threads;
+        self
+    }
+
+    /// Compress ``.tar.zst`` archives written by `tar::create` with a trained zstd `dictionary`, which
+    /// improves the ratio for archives of many small, similar files such as JSON documents. The
+    /// dictionary ID is recorded in the zstd frame, and the same dictionary is needed to extract the
+    /// archive. Requires the ``zstd`` feature.
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.zstd_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Skip files matching the glob `pattern`, such as ``*.o`` or ``target/**``. The pattern may match the
+    /// whole path or any part of it that starts at a path component. May be called several times.
+    pub fn exclude(mut self, pattern: String) -> Self {
+        self.excludes.push(pattern);
+        self
+    }
+}
+
+/// How symbolic links are recreated on extraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Create symbolic links.
+    Create,
+    /// Copy the file the link points to in place of the link, for filesystems and platforms such as
+    /// Windows where creating symbolic links isn't possible. Links to directories are skipped.
+    Copy,
+    /// Don't extract symbolic links.
+    Skip,
+}
+
+/// What to do with a symbolic link that is dangling or points outside the destination directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkPolicy {
+    /// Extract the link anyway.
+    Allow,
+    /// Leave the link out of the extraction.
+    Skip,
+    /// Stop extracting with an error.
+    Error,
+}
+
+/// How the owner of an extracted entry is chosen, when the process has the privileges to change it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OwnerMapping {
+    /// Give entries the local user and group with their stored names, falling back to the stored ids for
+    /// names that don't exist on this system, like ``tar``.
+    Names,
+    /// Give entries their stored uid and gid and ignore the names, like ``--numeric-owner``.
+    Numeric,
+    /// Give entries the local user and group with their stored names, and leave the owner of entries whose
+    /// names don't exist here to the extracting process.
+    NamesOnly,
+}
+
+/// Options controlling how an archive is extracted.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::ExtractOptions;
+///
+/// let options = ExtractOptions::default().strip_components(1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    pub(crate) strip_components: usize,
+    pub(crate) transform: Option<Transform>,
+    pub(crate) symlinks: SymlinkPolicy,
+    pub(crate) dangling_links: LinkPolicy,
+    pub(crate) escaping_links: LinkPolicy,
+    pub(crate) verifier: Option<Arc<dyn Verifier>>,
+    pub(crate) punch_holes: bool,
+    pub(crate) reflink: bool,
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub(crate) io_uring: bool,
+    pub(crate) xattrs: bool,
+    pub(crate) capabilities: bool,
+    pub(crate) owners: OwnerMapping,
+    pub(crate) same_owner: Option<bool>,
+    pub(crate) same_permissions: Option<bool>,
+    pub(crate) resolver: Arc<dyn OwnerResolver>,
+    pub(crate) profile: Profile,
+    pub(crate) max_ratio: Option<u64>,
+    pub(crate) cancel: Option<CancelToken>,
+    pub(crate) rate_limit: Option<u64>,
+    pub(crate) verbosity: Verbosity,
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd_dictionary: Option<Vec<u8>>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            strip_components: 0,
+            transform: None,
+            symlinks: SymlinkPolicy::Create,
+            dangling_links: LinkPolicy::Allow,
+            escaping_links: LinkPolicy::Error,
+            verifier: None,
+            punch_holes: false,
+            reflink: false,
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            io_uring: false,
+            xattrs: false,
+            capabilities: false,
+            owners: OwnerMapping::Names,
+            same_owner: None,
+            same_permissions: None,
+            resolver: owner::system(),
+            profile: Profile::Unrestricted,
+            max_ratio: None,
+            cancel: None,
+            rate_limit: None,
+            verbosity: Verbosity::Normal,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Remove the first `count` components from each entry name, like ``--strip-components``, so that
+    /// ``pkg-1.2.3/src/main.rs`` is extracted as ``src/main.rs`` with a count of 1. Entries with no
+    /// components left are skipped.
+    pub fn strip_components(mut self, count: usize) -> Self {
+        self.strip_components = count;
+        self
+    }
+
+    /// Rewrite each entry name with `transform` before extracting it, after any components were stripped.
+    /// Entries whose name becomes empty are skipped.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Set how symbolic links are recreated. Defaults to `SymlinkPolicy::Create`.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Set what happens to symbolic links whose target is not in the extraction. Defaults to
+    /// `LinkPolicy::Allow`, as links may point at files that exist on the machine they are used on.
+    pub fn dangling_links(mut self, policy: LinkPolicy) -> Self {
+        self.dangling_links = policy;
+        self
+    }
+
+    /// Set what happens to symbolic links with an absolute target or a target that climbs out of the
+    /// destination directory with ``..``. Defaults to `LinkPolicy::Error`, failing with
+    /// `TarError::UnsafeLink`, since later entries could otherwise be written through the link.
+    pub fn escaping_links(mut self, policy: LinkPolicy) -> Self {
+        self.escaping_links = policy;
+        self
+    }
+
+    /// Seek over runs of zeros that cover whole filesystem blocks instead of writing them, so that regular
+    /// files are extracted as sparse files. The holes of sparse entries are always preserved; this also
+    /// finds them in plain entries, such as disk images archived without sparse support. Defaults to
+    /// `false`.
+    pub fn punch_holes(mut self, punch: bool) -> Self {
+        self.punch_holes = punch;
+        self
+    }
+
+    /// On filesystems with reflinks, such as btrfs and XFS, extract regular files by sharing blocks rather
+    /// than writing them: a file with the same contents as one extracted earlier is cloned from it, and
+    /// data left in the archive by `ReadOptions::lazy_data` is cloned from the archive when it starts on a
+    /// block boundary. Large extractions with many duplicates then mostly write metadata, at the cost of
+    /// hashing each file. Files are written as usual where blocks can't be shared. Has no effect with
+    /// `punch_holes`. Defaults to `false`.
+    pub fn reflink(mut self, reflink: bool) -> Self {
+        self.reflink = reflink;
+        self
+    }
+
+    /// Write regular files through io_uring, submitting the writes of up to 64 files at once and setting
+    /// their owners, modes and times once the batch has been written, so the kernel can overlap the writes
+    /// instead of waiting on each in turn. Files of 16 MiB or more and sparse files are written as usual,
+    /// and so is everything where the kernel doesn't support io_uring or it is disabled. Has no effect with
+    /// `punch_holes` or `reflink`. Requires the ``uring`` feature and Linux. Defaults to `false`.
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub fn io_uring(mut self, io_uring: bool) -> Self {
+        self.io_uring = io_uring;
+        self
+    }
+
+    /// Refuse to extract anything if the archive holds an entry that `profile` doesn't allow, failing with
+    /// `TarError::Disallowed`. Defaults to `Profile::Unrestricted`.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Restore the extended attributes stored in ``SCHILY.xattr.*`` PAX records. Attributes that can't be
+    /// set, such as ``trusted.*`` ones without root, are reported with `TarWarning::XattrSkipped`.
+    /// Supported on Linux and macOS. Defaults to `false`.
+    pub fn xattrs(mut self, xattrs: bool) -> Self {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Restore stored file capabilities, the ``security.capability`` extended attribute, even when
+    /// `xattrs` is off. Setting them needs ``CAP_SETFCAP``, usually root; without it they are reported
+    /// with `TarWarning::XattrSkipped`. Defaults to `false`.
+    pub fn capabilities(mut self, capabilities: bool) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Set how the owner of each entry is chosen. Defaults to `OwnerMapping::Names`.
+    pub fn owners(mut self, mapping: OwnerMapping) -> Self {
+        self.owners = mapping;
+        self
+    }
+
+    /// Give each entry its stored owner, like ``--same-owner``, or leave every entry to the extracting user,
+    /// like ``--no-same-owner``. Defaults to on when running as root. Entries whose owner could not be
+    /// restored, because it was refused or not attempted without root, are reported with a
+    /// `TarWarning::OwnerNotRestored` unless this was turned off.
+    pub fn same_owner(mut self, same_owner: bool) -> Self {
+        self.same_owner = Some(same_owner);
+        self
+    }
+
+    /// Give entries exactly their stored permissions, like ``--preserve-permissions``, or clear the bits set
+    /// in the process umask from them, as ``tar`` does for ordinary users. Defaults to on when running as
+    /// root.
+    pub fn same_permissions(mut self, same_permissions: bool) -> Self {
+        self.same_permissions = Some(same_permissions);
+        self
+    }
+
+    /// Returns the permission bits to clear from stored modes: none when permissions are kept exactly,
+    /// otherwise the process umask.
+    pub(crate) fn umask(&self) -> u32 {
+        /* SAFETY: geteuid has no preconditions and cannot fail */
+        match self
+            .same_permissions
+            .unwrap_or(unsafe { libc::geteuid() } == 0)
+        {
+            true => 0,
+            false => process_umask(),
+        }
+    }
+
+    /// Set which warnings are recorded while extracting. Defaults to `Verbosity::Normal`.
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Map stored owner names to ids with `resolver` instead of the system databases.
+    pub fn resolver<R: OwnerResolver + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Check the signature of each entry with `verifier` just before it is extracted, as described in
+    /// `TarNode::verify`, stopping at the first entry that is unsigned or has been tampered with. Entries
+    /// left out of the extraction aren't checked.
+    pub fn verifier<V: Verifier + 'static>(mut self, verifier: V) -> Self {
+        self.verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Stop decompressing archives passed to `tar::extract` once they expand to more than `ratio` times
+    /// the compressed bytes read so far, failing with `TarError::CompressionRatio`, to defend against
+    /// decompression bombs. The first MiB is always allowed, as tar padding alone compresses very well.
+    /// Defaults to no limit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::ExtractOptions;
+    ///
+    /// let options = ExtractOptions::default().max_compression_ratio(100);
+    /// ```
+    pub fn max_compression_ratio(mut self, ratio: u64) -> Self {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    /// Stop extracting with `TarError::Cancelled` once `token` is cancelled. The token is checked before
+    /// each entry. Entries extracted before then are complete, with their metadata, and directories still
+    /// get their final modes and times, but symbolic links, which are created last, are only created if
+    /// every other entry was extracted first.
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Write the data of regular files at no more than `bytes_per_second` on average, in the same way as
+    /// `ReadOptions::rate_limit`. Files are then written through a buffer rather than copied or cloned
+    /// inside the kernel, except for sparse files and with `punch_holes` or `reflink`, and `io_uring` is
+    /// not used.
+    pub fn rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    /// Decompress ``.tar.zst`` archives with the trained zstd `dictionary` they were created with by
+    /// `tar::create`. Requires the ``zstd`` feature.
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.zstd_dictionary = Some(dictionary);
+        self
+    }
+}
+
+/// Returns the number of threads to use for a `threads` option, where ``0`` means one per CPU.
+pub(crate) fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Returns the umask of the process. Linux reports it in ``/proc/self/status``; elsewhere it can only be
+/// read by setting it, which briefly changes it for every thread.
+fn process_umask() -> u32 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let reported = status
+        .lines()
+        .find_map(|l| l.strip_prefix("Umask:"))
+        .and_then(|m| u32::from_str_radix(m.trim(), 8).ok());
+    reported.unwrap_or_else(|| {
+        /* SAFETY: umask cannot fail, and the previous mask is restored straight away */
+        unsafe {
+            let mask = libc::umask(0o022);
+            libc::umask(mask);
+            mask as u32
+        }
+    })
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+#[cfg(feature = "nss")]
+use std::ffi::{CStr, CString};
+use std::fmt::Debug;
+#[cfg(not(feature = "nss"))]
+use std::fs;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Maps numeric owner ids to the user and group names stored in entry headers.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{ArchiveOptions, StaticResolver};
+///
+/// let resolver = StaticResolver::default()
+///     .user(1000, "builder".to_string())
+///     .group(1000, "builders".to_string());
+/// let options = ArchiveOptions::default().resolver(resolver);
+/// ```
+pub trait OwnerResolver: Debug + Send + Sync {
+    /// Returns the name of the user `uid`, if it has one.
+    fn user_name(&self, uid: u32) -> Option<String>;
+
+    /// Returns the name of the group `gid`, if it has one.
+    fn group_name(&self, gid: u32) -> Option<String>;
+
+    /// Returns the uid of the user called `name`, used to map owners back to ids on extraction. Resolvers
+    /// that only name ids find no users.
+    fn user_id(&self, _name: &str) -> Option<u32> {
+        None
+    }
+
+    /// Returns the gid of the group called `name`. Resolvers that only name ids find no groups.
+    fn group_id(&self, _name: &str) -> Option<u32> {
+        None
+    }
+}
+
+/// Resolves names from the user and group databases of the running system. With the ``nss`` feature the
+/// lookups go through ``getpwuid_r`` and ``getgrgid_r``, which also find users from NSS sources such as
+/// LDAP, sssd or systemd; otherwise ``/etc/passwd`` and ``/etc/group`` are read.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemResolver;
+
+#[cfg(not(feature = "nss"))]
+impl OwnerResolver for SystemResolver {
+    fn user_name(&self, uid: u32) -> Option<String> {
+        lookup("/etc/passwd", uid)
+    }
+
+    fn group_name(&self, gid: u32) -> Option<String> {
+        lookup("/etc/group", gid)
+    }
+
+    fn user_id(&self, name: &str) -> Option<u32> {
+        lookup_id("/etc/passwd", name)
+    }
+
+    fn group_id(&self, name: &str) -> Option<u32> {
+        lookup_id("/etc/group", name)
+    }
+}
+
+/* SAFETY: the records and buffers passed are valid for the sizes given, and `nss_lookup` only reads the
+ * record once the call reports it was found */
+#[cfg(feature = "nss")]
+impl OwnerResolver for SystemResolver {
+    fn user_name(&self, uid: u32) -> Option<String> {
+        nss_lookup(
+            |pwd, buf, len, result| unsafe { libc::getpwuid_r(uid, pwd, buf, len, result) },
+            |pwd: &libc::passwd| unsafe { c_name(pwd.pw_name) },
+        )
+    }
+
+    fn group_name(&self, gid: u32) -> Option<String> {
+        nss_lookup(
+            |grp, buf, len, result| unsafe { libc::getgrgid_r(gid, grp, buf, len, result) },
+            |grp: &libc::group| unsafe { c_name(grp.gr_name) },
+        )
+    }
+
+    fn user_id(&self, name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        nss_lookup(
+            |pwd, buf, len, result| unsafe {
+                libc::getpwnam_r(name.as_ptr(), pwd, buf, len, result)
+            },
+            |pwd: &libc::passwd| pwd.pw_uid,
+        )
+    }
+
+    fn group_id(&self, name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        nss_lookup(
+            |grp, buf, len, result| unsafe {
+                libc::getgrnam_r(name.as_ptr(), grp, buf, len, result)
+            },
+            |grp: &libc::group| grp.gr_gid,
+        )
+    }
+}
+
+/// Resolves names from fixed tables, for tests and for archiving files owned by another system's users,
+/// such as a container image. Ids missing from the tables have no name.
+#[derive(Clone, Debug, Default)]
+pub struct StaticResolver {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl StaticResolver {
+    /// Name the user `uid` as `name`.
+    pub fn user(mut self, uid: u32, name: String) -> Self {
+        self.users.insert(uid, name);
+        self
+    }
+
+    /// Name the group `gid` as `name`.
+    pub fn group(mut self, gid: u32, name: String) -> Self {
+        self.groups.insert(gid, name);
+        self
+    }
+}
+
+impl OwnerResolver for StaticResolver {
+    fn user_name(&self, uid: u32) -> Option<String> {
+        self.users.get(&uid).cloned()
+    }
+
+    fn group_name(&self, gid: u32) -> Option<String> {
+        self.groups.get(&gid).cloned()
+    }
+
+    fn user_id(&self, name: &str) -> Option<u32> {
+        self.users
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(id, _)| *id)
+    }
+
+    fn group_id(&self, name: &str) -> Option<u32> {
+        self.groups
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(id, _)| *id)
+    }
+}
+
+/// Remembers the answers of another resolver, including ids without a name, so each id is only looked up
+/// once. Most trees have few distinct owners, while a lookup may read a whole database.
+#[derive(Debug, Default)]
+pub struct CachedResolver<R: OwnerResolver> {
+    inner: R,
+    users: Mutex<HashMap<u32, Option<String>>>,
+    groups: Mutex<HashMap<u32, Option<String>>>,
+    user_ids: Mutex<HashMap<String, Option<u32>>>,
+    group_ids: Mutex<HashMap<String, Option<u32>>>,
+}
+
+impl<R: OwnerResolver> CachedResolver<R> {
+    /// Cache the lookups made through `inner`.
+    pub fn new(inner: R) -> Self {
+        CachedResolver {
+            inner,
+            users: Mutex::new(HashMap::new()),
+            groups: Mutex::new(HashMap::new()),
+            user_ids: Mutex::new(HashMap::new()),
+            group_ids: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<R: OwnerResolver> OwnerResolver for CachedResolver<R> {
+    fn user_name(&self, uid: u32) -> Option<String> {
+        let mut users = self.users.lock().unwrap_or_else(|e| e.into_inner());
+        users
+            .entry(uid)
+            .or_insert_with(|| self.inner.user_name(uid))
+            .clone()
+    }
+
+    fn group_name(&self, gid: u32) -> Option<String> {
+        let mut groups = self.groups.lock().unwrap_or_else(|e| e.into_inner());
+        groups
+            .entry(gid)
+            .or_insert_with(|| self.inner.group_name(gid))
+            .clone()
+    }
+
+    fn user_id(&self, name: &str) -> Option<u32> {
+        let mut user_ids = self.user_ids.lock().unwrap_or_else(|e| e.into_inner());
+        *user_ids
+            .entry(name.to_string())
+            .or_insert_with(|| self.inner.user_id(name))
+    }
+
+    fn group_id(&self, name: &str) -> Option<u32> {
+        let mut group_ids = self.group_ids.lock().unwrap_or_else(|e| e.into_inner());
+        *group_ids
+            .entry(name.to_string())
+            .or_insert_with(|| self.inner.group_id(name))
+    }
+}
+
+/// Returns the resolver used by default, which caches the system databases for the life of the process.
+pub(crate) fn system() -> Arc<dyn OwnerResolver> {
+    static SYSTEM: OnceLock<Arc<CachedResolver<SystemResolver>>> = OnceLock::new();
+    SYSTEM
+        .get_or_init(|| Arc::new(CachedResolver::new(SystemResolver)))
+        .clone()
+}
+
+/// Call the reentrant ``get*_r`` lookup `get`, growing the string buffer while it is too small, and
+/// return the field that `read` takes from the record found.
+#[cfg(feature = "nss")]
+fn nss_lookup<T, F, R, V>(get: F, read: R) -> Option<V>
+where
+    F: Fn(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+    R: Fn(&T) -> V,
+{
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        let mut record = std::mem::MaybeUninit::<T>::uninit();
+        let mut result = std::ptr::null_mut();
+        match get(
+            record.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        ) {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            /* SAFETY: a non-null result means the record was filled in, with strings held in `buf` */
+            0 if !result.is_null() => return Some(read(unsafe { record.assume_init_ref() })),
+            _ => return None,
+        }
+    }
+}
+
+/// Copy the NUL terminated name at `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must point to a valid NUL terminated string.
+#[cfg(feature = "nss")]
+unsafe fn c_name(ptr: *const libc::c_char) -> String {
+    CStr::from_ptr(ptr).to_string_lossy().to_string()
+}
+
+/// Find the name on the line of a colon separated database such as ``/etc/passwd`` whose third field is `id`.
+#[cfg(not(feature = "nss"))]
+fn lookup(path: &str, id: u32) -> Option<String> {
+    let db = fs::read_to_string(path).ok()?;
+    db.lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.split(':').collect::<Vec<&str>>())
+        .find(|f| f.len() > 2 && f[2].parse() == Ok(id))
+        .map(|f| f[0].to_string())
+}
+
+/// Find the id in the third field of the line of a colon separated database whose first field is `name`.
+#[cfg(not(feature = "nss"))]
+fn lookup_id(path: &str, name: &str) -> Option<u32> {
+    let db = fs::read_to_string(path).ok()?;
+    db.lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.split(':').collect::<Vec<&str>>())
+        .find(|f| f.len() > 2 && f[0] == name)
+        .and_then(|f| f[2].parse().ok())
+}
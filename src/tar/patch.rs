@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom, Write};
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A set of metadata changes that can be written directly into the headers of an archive on disk.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::HeaderPatch;
+///
+/// let patch = HeaderPatch::default().mode(0o600).mtime(0);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HeaderPatch {
+    pub(crate) name: Option<String>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) uid: Option<u64>,
+    pub(crate) gid: Option<u64>,
+    pub(crate) mtime: Option<i64>,
+}
+
+impl HeaderPatch {
+    /// Rename the entry. The new name must have the same length as the name stored in the header.
+    pub fn name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set the permission bits of the entry.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the numeric user id of the entry owner.
+    pub fn uid(mut self, uid: u64) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Set the numeric group id of the entry owner.
+    pub fn gid(mut self, gid: u64) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Set the modification time of the entry in seconds since the epoch. Times before 1970 are stored in
+    /// base-256.
+    pub fn mtime(mut self, mtime: i64) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Apply the patch to `header`, failing if a change does not fit in the header or would be overridden by
+    /// one of the `pax` records of the entry.
+    fn apply(&self, header: &mut TarHeader, pax: &[PaxRecord]) -> Result<(), TarError> {
+        let name = cstr(&header.file_name);
+        let overridden = |key: &str| pax.iter().any(|r| r.key() == key);
+        let unpatchable = || TarError::NotPatchable(name.clone());
+
+        if let Some(new) = &self.name {
+            if overridden("path") || new.len() != name.len() || header.file_prefix[0] != 0 {
+                return Err(unpatchable());
+            }
+            set_field(&mut header.file_name, new.as_bytes()).map_err(|_| unpatchable())?;
+        }
+        if let Some(mode) = self.mode {
+            dec_to_oct(mode as u64, &mut header.file_mode, OctalTerminator::Nul)
+                .map_err(|_| unpatchable())?;
+        }
+        for (value, field, key) in [
+            (self.uid, &mut header.own_user, "uid"),
+            (self.gid, &mut header.own_group, "gid"),
+        ] {
+            if let Some(value) = value {
+                if overridden(key) {
+                    return Err(unpatchable());
+                }
+                /* Ids too large for octal digits can only go in base-256, as no record can be added */
+                dec_to_oct(value, field, OctalTerminator::Nul)
+                    .or_else(|_| dec_to_base256(value, field))
+                    .map_err(|_| unpatchable())?;
+            }
+        }
+        if let Some(mtime) = self.mtime {
+            if overridden("mtime") {
+                return Err(unpatchable());
+            }
+            set_time(mtime, &mut header.mod_time).map_err(|_| unpatchable())?;
+        }
+
+        header.update_checksum()
+    }
+}
+
+impl TarFile {
+    /// Apply `patch` to every entry named `name` in the uncompressed archive `filename`, overwriting only
+    /// their header blocks and leaving the rest of the file untouched. Returns the number of entries patched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{HeaderPatch, TarFile};
+    ///
+    /// std::fs::copy("test/1.tar", "target/doc-patch.tar").unwrap();
+    /// let patch = HeaderPatch::default().mode(0o600);
+    /// TarFile::patch_in_place("target/doc-patch.tar".to_string(), "1.txt".to_string(), &patch).unwrap();
+    /// ```
+    pub fn patch_in_place(
+        filename: String,
+        name: String,
+        patch: &HeaderPatch,
+    ) -> Result<usize, TarError> {
+        let mut file = File::options().read(true).write(true).open(&filename)?;
+
+        /* Find the headers to patch before writing anything, so a failure leaves the archive untouched */
+        let mut found = Vec::new();
+        let mut input = BufReader::new(&file);
+        let mut trailer = true;
+        while let Some(node) = next_entry_header(&mut input, &ReadOptions::default(), &mut trailer)?
+        {
+            if node.name() == name {
+                let offset = input.stream_position()? - 512;
+                let mut header = node.header;
+                patch.apply(&mut header, &node.pax)?;
+                found.push((offset, header));
+            }
+            input.seek(SeekFrom::Current((node.data_blocks() * 512) as i64))?;
+        }
+
+        if found.is_empty() {
+            return Err(TarError::NotFound(name));
+        }
+        for (offset, header) in &found {
+            file.seek(SeekFrom::Start(*offset))?;
+            file.write_all(&header.to_bytes()?)?;
+        }
+        file.sync_all()?;
+
+        Ok(found.len())
+    }
+}
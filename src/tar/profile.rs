@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use crate::error::TarError;
+use crate::tar::extract::{escapes_dest, safe_path};
+use crate::tar::*;
+
+/// A security profile restricting the kinds of entries an archive may hold, for services handling
+/// untrusted uploads. Archives with a disallowed entry are rejected as a whole with
+/// `TarError::Disallowed`.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{Profile, ReadOptions, TarFile};
+///
+/// let options = ReadOptions::default().profile(Profile::RegularFilesOnly);
+/// let data = TarFile::open_with("test/1.tar".to_string(), &options).unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Profile {
+    /// Allow every kind of entry.
+    #[default]
+    Unrestricted,
+    /// Reject FIFOs, devices, entries of unknown types, setuid and setgid files, and links with an
+    /// absolute target or one that climbs out of the archive with ``..``.
+    NoSpecials,
+    /// Allow only regular files and directories, without setuid or setgid bits.
+    RegularFilesOnly,
+}
+
+impl Profile {
+    /// Check that `node` is allowed by the profile. Journal records are always allowed.
+    pub fn check(self, node: &TarNode) -> Result<(), TarError> {
+        if self == Profile::Unrestricted || node.journal_entry().is_some() {
+            return Ok(());
+        }
+
+        let disallowed = |reason: &str| {
+            Err(TarError::Disallowed {
+                name: node.name(),
+                reason: reason.to_string(),
+            })
+        };
+        let file_type = node.file_type();
+        let link = file_type == FileType::Sym as u8 || file_type == FileType::Hard as u8;
+        if file_type == FileType::Char as u8 || file_type == FileType::Block as u8 {
+            return disallowed("device");
+        }
+        if file_type == FileType::FIFO as u8 {
+            return disallowed("FIFO");
+        }
+        if !is_regular(file_type) && !link && file_type != FileType::Dir as u8 {
+            return disallowed("unknown entry type");
+        }
+        if node.mode() & 0o6000 != 0 {
+            return disallowed("setuid or setgid");
+        }
+
+        match self {
+            Profile::RegularFilesOnly if link => disallowed("link"),
+            _ if link && link_escapes(node) => disallowed("link target outside the archive"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns `true` if the target of the link `node` is absolute or leads above the top of the archive.
+fn link_escapes(node: &TarNode) -> bool {
+    let target = node.link_name();
+    let path = match safe_path(Path::new(""), &node.name()) {
+        Ok(p) => p,
+        Err(_) => return true,
+    };
+    /* Hard link targets are names within the archive rather than paths relative to the link */
+    match node.file_type() == FileType::Hard as u8 {
+        true => safe_path(Path::new(""), &target).is_err() || Path::new(&target).is_absolute(),
+        false => escapes_dest(Path::new(""), &path, &target),
+    }
+}
+
+impl TarFile {
+    /// Check every entry against `profile`, failing on the first one that is not allowed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{Profile, TarFile};
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.check_profile(Profile::NoSpecials).unwrap();
+    /// ```
+    pub fn check_profile(&self, profile: Profile) -> Result<(), TarError> {
+        self.file.iter().try_for_each(|n| profile.check(n))
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// The size and SHA-256 digest of the contents of a file.
+type Contents = (usize, Vec<u8>);
+
+/// Remembers the files written so far during an extraction by their contents, so later entries with the
+/// same contents can share their blocks, as described in `ExtractOptions::reflink`.
+#[derive(Debug, Default)]
+pub(crate) struct Reflinks {
+    files: HashMap<Contents, PathBuf>,
+    paths: HashMap<PathBuf, Contents>,
+}
+
+impl Reflinks {
+    /// Fill the empty `file` being extracted to `path` with the contents of `node` by cloning an earlier
+    /// file with the same contents, or else the blocks of the archive that the data was left in. Returns
+    /// `false`, having written nothing, if the filesystem can't share blocks between them.
+    pub(crate) fn clone_into(
+        &mut self,
+        node: &TarNode,
+        file: &File,
+        path: &Path,
+    ) -> Result<bool, TarError> {
+        if node.sparse_map()?.is_some() {
+            return Ok(false);
+        }
+
+        let contents = (node.size(), node.digest(HashAlgorithm::Sha256)?);
+        /* A file being replaced by an entry with the same contents has already been emptied */
+        let earlier = self.files.get(&contents).filter(|p| *p != path).cloned();
+        self.forget(path);
+        self.paths.insert(path.to_path_buf(), contents.clone());
+        self.files.insert(contents, path.to_path_buf());
+
+        if let Some(earlier) = earlier {
+            if let Ok(source) = File::open(earlier) {
+                if clone_file(&source, file) {
+                    return Ok(true);
+                }
+            }
+        }
+        match &node.lazy {
+            Some(lazy) => lazy.clone_to_file(file),
+            None => Ok(false),
+        }
+    }
+
+    /// Stop cloning from `path`, which is about to be replaced.
+    pub(crate) fn forget(&mut self, path: &Path) {
+        if let Some(contents) = self.paths.remove(path) {
+            if self.files.get(&contents).map(|p| p.as_path()) == Some(path) {
+                self.files.remove(&contents);
+            }
+        }
+    }
+}
+
+/// Make `output` share the blocks of the whole of `input` with ``FICLONE``, returning `false` if the
+/// filesystem can't.
+#[cfg(target_os = "linux")]
+fn clone_file(input: &File, output: &File) -> bool {
+    /* SAFETY: both descriptors stay open for the call */
+    unsafe { libc::ioctl(output.as_raw_fd(), libc::FICLONE, input.as_raw_fd()) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clone_file(_input: &File, _output: &File) -> bool {
+    false
+}
+
+/// Make the start of `output` share the `len` bytes of `input` starting at `offset` with ``FICLONERANGE``,
+/// returning `false` if the filesystem can't. Only whole filesystem blocks can be shared, so `offset` and
+/// `len` must be multiples of the block size.
+#[cfg(target_os = "linux")]
+pub(crate) fn clone_range(input: &File, offset: u64, len: u64, output: &File) -> bool {
+    let range = libc::file_clone_range {
+        src_fd: input.as_raw_fd() as i64,
+        src_offset: offset,
+        src_length: len,
+        dest_offset: 0,
+    };
+    /* SAFETY: the descriptors stay open for the call, and `range` outlives it */
+    unsafe { libc::ioctl(output.as_raw_fd(), libc::FICLONERANGE, &range) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn clone_range(_input: &File, _offset: u64, _len: u64, _output: &File) -> bool {
+    false
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A sorted index of entry names for fast lookups in archives with many entries.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::NameIndex;
+///
+/// let index = NameIndex::open("test/1.tar".to_string()).unwrap();
+/// assert_eq!(index.find_prefix("1.")[0].0, "1.txt");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct NameIndex {
+    /// Each name with the position of its entry in the archive, sorted by name.
+    names: Vec<(String, usize)>,
+}
+
+impl NameIndex {
+    /// Build an index from the names of the entries of an archive, in archive order.
+    pub fn new(names: Vec<String>) -> Self {
+        let mut names: Vec<(String, usize)> = names.into_iter().zip(0..).collect();
+        names.sort();
+        NameIndex { names }
+    }
+
+    /// Build an index of the archive in the file `filename`. Only the headers are read, so the entry data
+    /// is never loaded.
+    pub fn open(filename: String) -> Result<Self, TarError> {
+        let file = BufReader::new(File::open(filename)?);
+        Ok(NameIndex::new(TarFile::list_from(file)?))
+    }
+
+    /// Returns the number of names in the index.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if the index holds no names.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the names starting with `prefix` along with their entry positions, in sorted order.
+    pub fn find_prefix(&self, prefix: &str) -> &[(String, usize)] {
+        let start = self.names.partition_point(|(n, _)| n.as_str() < prefix);
+        let len = self.names[start..].partition_point(|(n, _)| n.starts_with(prefix));
+        &self.names[start..start + len]
+    }
+
+    /// Returns the names containing the characters of `pattern` in order, ignoring case, along with their
+    /// entry positions. The closest matches, where the characters are found nearest to each other, come
+    /// first.
+    pub fn find_fuzzy(&self, pattern: &str) -> Vec<(String, usize)> {
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let mut matches: Vec<(usize, &(String, usize))> = self
+            .names
+            .iter()
+            .filter_map(|n| Some((fuzzy_span(&n.0, &pattern)?, n)))
+            .collect();
+
+        /* The sort is stable, so names with the same span stay in sorted order */
+        matches.sort_by_key(|m| m.0);
+        matches.into_iter().map(|m| m.1.clone()).collect()
+    }
+}
+
+impl TarFile {
+    /// Build a `NameIndex` of the entries of this archive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// assert_eq!(data.name_index().find_fuzzy("txt").len(), 1);
+    /// ```
+    pub fn name_index(&self) -> NameIndex {
+        NameIndex::new(self.file.iter().map(|n| n.name()).collect())
+    }
+}
+
+/// Returns the length of the shortest stretch of `name` containing the lowercase `pattern` as a
+/// subsequence, or `None` if it doesn't match.
+fn fuzzy_span(name: &str, pattern: &[char]) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut best = None;
+    for start in (0..name.len()).filter(|&i| name[i] == pattern[0]) {
+        let mut p = 1;
+        let mut end = start + 1;
+        while p < pattern.len() && end < name.len() {
+            if name[end] == pattern[p] {
+                p += 1;
+            }
+            end += 1;
+        }
+        if p < pattern.len() {
+            break;
+        }
+        best = Some(best.map_or(end - start, |b: usize| b.min(end - start)));
+    }
+    best
+}
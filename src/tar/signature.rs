@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::error::TarError;
+use crate::tar::digest::to_hex;
+use crate::tar::*;
+
+/// PAX record holding the hex encoded signature of an entry.
+const SIGNATURE_KEY: &str = "MINITAR.signature";
+/// PAX record naming the key that made the signature.
+const SIGNATURE_KEY_ID: &str = "MINITAR.signature.key";
+/// PAX record naming the `HashAlgorithm` of the signed content digest.
+const SIGNATURE_DIGEST: &str = "MINITAR.signature.digest";
+
+/// Signs the description of an entry, for storing in its ``MINITAR.signature`` PAX record.
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Returns the name of the signing key, stored with each signature so verifiers can pick the
+    /// matching key.
+    fn key_id(&self) -> String;
+
+    /// Returns the signature of `message`.
+    fn sign(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// Checks the signatures made by a `Signer`.
+pub trait Verifier: fmt::Debug + Send + Sync {
+    /// Returns `true` if `signature` is a valid signature of `message` by the key named `key_id`.
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Signs and verifies entries with HMAC-SHA256 and a shared secret key.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::{HashAlgorithm, HmacKey, TarFile};
+///
+/// let key = HmacKey::new("release".to_string(), b"secret".to_vec());
+/// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+/// data.sign(&key, HashAlgorithm::Sha256).unwrap();
+/// data.verify(&key).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct HmacKey {
+    id: String,
+    key: Vec<u8>,
+}
+
+impl HmacKey {
+    /// Create a key named `id` holding the secret `key`.
+    pub fn new(id: String, key: Vec<u8>) -> Self {
+        HmacKey { id, key }
+    }
+
+    /// Returns the HMAC-SHA256 of `message`, as described in RFC 2104.
+    fn mac(&self, message: &[u8]) -> Vec<u8> {
+        let mut block = [0u8; 64];
+        match self.key.len() > block.len() {
+            true => block[..32].copy_from_slice(&HashAlgorithm::Sha256.digest(&self.key)),
+            false => block[..self.key.len()].copy_from_slice(&self.key),
+        }
+
+        let mut inner = HashAlgorithm::Sha256.hasher();
+        inner.update(&block.map(|b| b ^ 0x36));
+        inner.update(message);
+        let mut outer = HashAlgorithm::Sha256.hasher();
+        outer.update(&block.map(|b| b ^ 0x5c));
+        outer.update(&inner.finish());
+        outer.finish()
+    }
+}
+
+/// The secret is left out, so keys can't leak into logs.
+impl fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HmacKey").field("id", &self.id).finish()
+    }
+}
+
+impl Signer for HmacKey {
+    fn key_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.mac(message)
+    }
+}
+
+impl Verifier for HmacKey {
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> bool {
+        /* Compare every byte, so the time taken doesn't reveal how much of a forgery was right */
+        let expected = self.mac(message);
+        key_id == self.id
+            && expected.len() == signature.len()
+            && expected
+                .iter()
+                .zip(signature)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+impl TarNode {
+    /// Sign the entry with `signer`, storing the signature in ``MINITAR.signature`` PAX records and
+    /// replacing any earlier signature. The signature covers the name, type, mode and link target of the
+    /// entry along with the digest of its contents computed with `algorithm`, so each file can be
+    /// authenticated on its own, even when only part of the archive is extracted.
+    pub fn sign(&mut self, signer: &dyn Signer, algorithm: HashAlgorithm) -> Result<(), TarError> {
+        let key_id = signer.key_id();
+        let signature = signer.sign(&self.signed_message(algorithm)?);
+
+        self.pax.retain(|r| !r.key().starts_with(SIGNATURE_KEY));
+        self.pax
+            .push(PaxRecord::new(SIGNATURE_DIGEST, algorithm.name()));
+        self.pax.push(PaxRecord::new(SIGNATURE_KEY_ID, key_id));
+        self.pax
+            .push(PaxRecord::new(SIGNATURE_KEY, to_hex(&signature)));
+        Ok(())
+    }
+
+    /// Check the signature of the entry with `verifier`. Fails with `TarError::Unsigned` if the entry has
+    /// no signature, and with `TarError::InvalidSignature` if the entry was changed after it was signed or
+    /// the signing key isn't trusted by `verifier`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{HashAlgorithm, HmacKey, TarFile};
+    /// use minitar::TarError;
+    ///
+    /// let key = HmacKey::new("release".to_string(), b"secret".to_vec());
+    /// let mut node = TarFile::open("test/1.tar".to_string()).unwrap().nodes()[0].clone();
+    /// node.sign(&key, HashAlgorithm::Sha256).unwrap();
+    /// node.verify(&key).unwrap();
+    ///
+    /// let other = HmacKey::new("release".to_string(), b"guess".to_vec());
+    /// assert!(matches!(node.verify(&other), Err(TarError::InvalidSignature(_))));
+    /// ```
+    pub fn verify(&self, verifier: &dyn Verifier) -> Result<(), TarError> {
+        let name = self.name();
+        let signature = match self.pax_value(SIGNATURE_KEY) {
+            Some(s) => s,
+            None => return Err(TarError::Unsigned(name)),
+        };
+        let key_id = self.pax_value(SIGNATURE_KEY_ID).unwrap_or("");
+        let algorithm = self
+            .pax_value(SIGNATURE_DIGEST)
+            .and_then(HashAlgorithm::from_name)
+            .ok_or_else(|| TarError::InvalidSignature(name.clone()))?;
+        let signature =
+            from_hex(signature).ok_or_else(|| TarError::InvalidSignature(name.clone()))?;
+
+        match verifier.verify(key_id, &self.signed_message(algorithm)?, &signature) {
+            true => Ok(()),
+            false => Err(TarError::InvalidSignature(name)),
+        }
+    }
+
+    /// Returns the description of the entry that is signed: the NUL separated name, type, mode, link
+    /// target and content digest. Names can't hold NULs, so distinct entries can't share a description.
+    fn signed_message(&self, algorithm: HashAlgorithm) -> Result<Vec<u8>, TarError> {
+        let digest = to_hex(&self.digest(algorithm)?);
+        let message = format!(
+            "minitar-entry-v1\0{}\0{}\0{:o}\0{}\0{}:{}",
+            self.name(),
+            self.file_type(),
+            self.mode(),
+            self.link_name(),
+            algorithm.name(),
+            digest
+        );
+        Ok(message.into_bytes())
+    }
+}
+
+impl TarFile {
+    /// Sign every entry with `signer` in the same way as `TarNode::sign`. Journal records are left
+    /// unsigned.
+    pub fn sign(&mut self, signer: &dyn Signer, algorithm: HashAlgorithm) -> Result<(), TarError> {
+        for node in self.file.iter_mut().filter(|n| n.journal_entry().is_none()) {
+            node.sign(signer, algorithm)?;
+        }
+        Ok(())
+    }
+
+    /// Check the signature of every entry with `verifier` in the same way as `TarNode::verify`, failing on
+    /// the first entry that is unsigned or whose signature doesn't match. Journal records are skipped.
+    pub fn verify(&self, verifier: &dyn Verifier) -> Result<(), TarError> {
+        self.file
+            .iter()
+            .filter(|n| n.journal_entry().is_none())
+            .try_for_each(|n| n.verify(verifier))
+    }
+}
+
+/// Decode lowercase or uppercase hexadecimal.
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,574 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// The on-disk representation used when writing sparse entries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SparseFormat {
+    /// Old GNU sparse headers, storing the map in the header (type flag ``S``) and extension blocks.
+    OldGnu,
+    /// PAX ``GNU.sparse.*`` version 1.0, storing the map at the head of the entry data. This is the
+    /// default of modern GNU tar.
+    Pax1_0,
+}
+
+/// Describes which regions of a sparse file contain data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparseMap {
+    /// The apparent size of the file, including holes.
+    pub real_size: u64,
+    /// The `(offset, length)` of each region of the file that contains data, in ascending order.
+    pub segments: Vec<(u64, u64)>,
+}
+
+/// Offset of the old GNU sparse fields within the ustar prefix field.
+const GNU_SPARSE_OFFSET: usize = 386 - 345;
+const GNU_ISEXTENDED_OFFSET: usize = 482 - 345;
+const GNU_REALSIZE_OFFSET: usize = 483 - 345;
+
+impl TarNode {
+    /// Create a sparse entry named `filename`. The `data` holds the contents of each segment of `map`
+    /// back-to-back; the holes between them are not stored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{SparseMap, TarNode};
+    ///
+    /// let map = SparseMap {
+    ///     real_size: 1 << 20,
+    ///     segments: vec![(0, 4), ((1 << 20) - 4, 4)],
+    /// };
+    /// TarNode::from_sparse("disk.img".to_string(), map, b"headtail").unwrap();
+    /// ```
+    pub fn from_sparse(filename: String, map: SparseMap, data: &[u8]) -> Result<TarNode, TarError> {
+        check_map(&map, data.len())?;
+        let header = synthetic_header(&filename, FileType::Normal as u8, 0, 0)?;
+        TarNode {
+            header,
+            ..Default::default()
+        }
+        .into_sparse(map, data)
+    }
+
+    /// Turn a regular file node into a sparse one holding the segments of `map`, whose contents are in
+    /// `data`.
+    pub(crate) fn into_sparse(mut self, map: SparseMap, data: &[u8]) -> Result<TarNode, TarError> {
+        let (map, data) = align_segments(map, data);
+        dec_to_oct(
+            data.len() as u64,
+            &mut self.header.file_size,
+            OctalTerminator::Nul,
+        )?;
+        self.header.update_checksum()?;
+        self.data = data;
+        self.sparse = Some(map);
+        Ok(self)
+    }
+
+    /// Returns the sparse map of the entry, if it is a sparse file. Entries read from archives are decoded
+    /// from old GNU sparse headers and the ``GNU.sparse.*`` PAX records of versions 0.0, 0.1 and 1.0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let data = TarFile::open("test/corpus/gnu-sparse.tar".to_string()).unwrap();
+    /// let map = data.nodes()[0].sparse_map().unwrap().unwrap();
+    /// assert_eq!(map.real_size, 1 << 20);
+    /// ```
+    pub fn sparse_map(&self) -> Result<Option<SparseMap>, TarError> {
+        match &self.sparse {
+            Some(map) => Ok(Some(map.clone())),
+            None => Ok(self.decode_sparse()?.map(|(map, _)| map)),
+        }
+    }
+
+    /// Returns the sparse map of the entry along with the contents of its segments, whether it was created
+    /// as a sparse node or read from a sparse archive. Returns `None` if the entry isn't sparse.
+    pub(crate) fn sparse_contents(&self) -> Result<Option<(SparseMap, Vec<u8>)>, TarError> {
+        match &self.sparse {
+            Some(map) => Ok(Some((map.clone(), self.data.clone()))),
+            None => self.decode_sparse(),
+        }
+    }
+
+    /// Decode the sparse map of an entry read from a GNU or PAX sparse archive, along with the contents of
+    /// its segments. Returns `None` if the entry isn't sparse.
+    pub(crate) fn decode_sparse(&self) -> Result<Option<(SparseMap, Vec<u8>)>, TarError> {
+        /* Old GNU sparse entries are always read into memory, while the data of others is loaded once the
+         * entry is known to be sparse */
+        let (segments, real_size, skip, loaded) = if self.file_type() == FileType::GnuSparse as u8 {
+            let mut segments =
+                read_sparse_entries(&self.header.file_prefix[GNU_SPARSE_OFFSET..], 4)?;
+            let extensions = self.gnu_extension_blocks();
+            for block in self.data[..extensions * 512].chunks(512) {
+                segments.extend(read_sparse_entries(block, 21)?);
+            }
+            (segments, self.gnu_real_size(), extensions * 512, None)
+        } else {
+            match (
+                self.pax_value("GNU.sparse.major"),
+                self.pax_value("GNU.sparse.map"),
+            ) {
+                (Some("1"), _) => {
+                    let data = self.data()?;
+                    let (segments, skip) = read_data_map(&data)?;
+                    (
+                        segments,
+                        self.pax_number("GNU.sparse.realsize"),
+                        skip,
+                        Some(data),
+                    )
+                }
+                (_, Some(map)) => {
+                    let numbers = map
+                        .split(',')
+                        .map(|n| n.parse::<u64>().map_err(|_| TarError::InvalidSparseMap))
+                        .collect::<Result<Vec<u64>, TarError>>()?;
+                    if numbers.len() % 2 != 0 {
+                        return Err(TarError::InvalidSparseMap);
+                    }
+                    let segments = numbers.chunks(2).map(|p| (p[0], p[1])).collect();
+                    (segments, self.pax_number("GNU.sparse.size"), 0, None)
+                }
+                _ if self.pax_value("GNU.sparse.offset").is_some() => {
+                    let values = |key| {
+                        self.pax.iter().filter(move |r| r.key() == key).map(|r| {
+                            r.value_str()
+                                .and_then(|v| v.parse::<u64>().ok())
+                                .ok_or(TarError::InvalidSparseMap)
+                        })
+                    };
+                    let offsets = values("GNU.sparse.offset").collect::<Result<Vec<u64>, _>>()?;
+                    let lengths = values("GNU.sparse.numbytes").collect::<Result<Vec<u64>, _>>()?;
+                    if offsets.len() != lengths.len() {
+                        return Err(TarError::InvalidSparseMap);
+                    }
+                    let segments = offsets.into_iter().zip(lengths).collect();
+                    (segments, self.pax_number("GNU.sparse.size"), 0, None)
+                }
+                _ => return Ok(None),
+            }
+        };
+
+        let map = SparseMap {
+            real_size: real_size.ok_or(TarError::InvalidSparseMap)?,
+            segments,
+        };
+        /* The size of old GNU entries leaves out their extension blocks, while the PAX map is part of it */
+        let len = match self.file_type() == FileType::GnuSparse as u8 {
+            true => Some(self.size()),
+            false => self.size().checked_sub(skip),
+        }
+        .ok_or(TarError::InvalidSparseMap)?;
+        let data = match loaded {
+            Some(data) => data,
+            None => self.data()?,
+        };
+        let stored: Vec<u8> = data
+            .get(skip..)
+            .unwrap_or_default()
+            .iter()
+            .copied()
+            .take(len)
+            .collect();
+        check_map(&map, stored.len())?;
+        Ok(Some((map, stored)))
+    }
+
+    /// Returns the PAX record `key` parsed as a number.
+    fn pax_number(&self, key: &str) -> Option<u64> {
+        self.pax_value(key).and_then(|v| v.parse().ok())
+    }
+
+    /// Encode a sparse node into the plain node that is written to the archive in the given `format`.
+    pub(crate) fn encode_sparse(&self, format: SparseFormat) -> Result<TarNode, TarError> {
+        let map = match &self.sparse {
+            Some(m) => m,
+            None => return Ok(self.clone()),
+        };
+
+        match format {
+            SparseFormat::Pax1_0 => self.encode_pax_sparse(map),
+            SparseFormat::OldGnu => self.encode_gnu_sparse(map),
+        }
+    }
+
+    /// Store the map as decimal numbers at the head of the data, described by ``GNU.sparse.*`` records.
+    fn encode_pax_sparse(&self, map: &SparseMap) -> Result<TarNode, TarError> {
+        let mut text = format!("{}\n", map.segments.len());
+        for (offset, length) in &map.segments {
+            text.push_str(&format!("{}\n{}\n", offset, length));
+        }
+        let mut data = text.into_bytes();
+        data.resize(data.len().div_ceil(512) * 512, 0);
+        data.extend_from_slice(&self.data);
+
+        /* GNU tar names the entry ``dir/GNUSparseFile.0/file`` so tars without sparse support don't
+         * overwrite the real file */
+        let name = self.name();
+        let (dir, base) = match name.rsplit_once('/') {
+            Some((d, b)) => (format!("{}/", d), b),
+            None => (String::new(), name.as_str()),
+        };
+        let stored_name = format!("{}GNUSparseFile.0/{}", dir, base);
+
+        let mut header = self.header;
+        set_field(
+            &mut header.file_name,
+            names::truncate(&stored_name, 100).as_bytes(),
+        )?;
+        dec_to_oct(
+            data.len() as u64,
+            &mut header.file_size,
+            OctalTerminator::Nul,
+        )?;
+        header.link_indicator[0] = FileType::Normal as u8;
+
+        /* Readers only honor the sparse records on POSIX ustar headers */
+        header.ustar_magic = *b"ustar\x00";
+        header.ustar_version = *b"00";
+        header.update_checksum()?;
+
+        let mut pax = self.pax.clone();
+        pax.retain(|r| r.key() != "path");
+        pax.push(PaxRecord::new("GNU.sparse.major", "1"));
+        pax.push(PaxRecord::new("GNU.sparse.minor", "0"));
+        pax.push(PaxRecord::new("GNU.sparse.name", &name));
+        pax.push(PaxRecord::new(
+            "GNU.sparse.realsize",
+            map.real_size.to_string(),
+        ));
+
+        Ok(TarNode {
+            header,
+            pax,
+            sparse: None,
+            location: None,
+            inode: None,
+            gnu_long_names: false,
+            data,
+            lazy: None,
+        })
+    }
+
+    /// Returns the size of the file an old GNU sparse entry expands to, as recorded in its header.
+    pub(crate) fn gnu_real_size(&self) -> Option<u64> {
+        if self.file_type() != FileType::GnuSparse as u8 {
+            return None;
+        }
+
+        let field = &self.header.file_prefix[GNU_REALSIZE_OFFSET..GNU_REALSIZE_OFFSET + 12];
+        match is_blank(field) {
+            true => None,
+            false => oct_to_dec(field).ok().map(|size| size as u64),
+        }
+    }
+
+    /// Returns the number of old GNU sparse extension blocks at the head of the data, which are not counted
+    /// in the header size.
+    pub(crate) fn gnu_extension_blocks(&self) -> usize {
+        if self.file_type() != FileType::GnuSparse as u8
+            || self.header.file_prefix[GNU_ISEXTENDED_OFFSET] == 0
+        {
+            return 0;
+        }
+
+        let mut blocks = self.data.chunks_exact(512);
+        match blocks.position(|block| block[504] == 0) {
+            Some(last) => last + 1,
+            None => self.data.len() / 512,
+        }
+    }
+
+    /// Store the map in the old GNU header fields, spilling over into extension blocks.
+    fn encode_gnu_sparse(&self, map: &SparseMap) -> Result<TarNode, TarError> {
+        let mut header = self.header;
+        header.link_indicator[0] = FileType::GnuSparse as u8;
+        dec_to_oct(
+            map.real_size,
+            &mut header.file_prefix[GNU_REALSIZE_OFFSET..GNU_REALSIZE_OFFSET + 12],
+            OctalTerminator::Nul,
+        )?;
+
+        let (head, rest) = map.segments.split_at(map.segments.len().min(4));
+        write_sparse_entries(&mut header.file_prefix[GNU_SPARSE_OFFSET..], head)?;
+        header.file_prefix[GNU_ISEXTENDED_OFFSET] = !rest.is_empty() as u8;
+        header.update_checksum()?;
+
+        /* Each extension block holds 21 entries followed by its own ``isextended`` flag */
+        let mut data = Vec::new();
+        let mut chunks = rest.chunks(21).peekable();
+        while let Some(chunk) = chunks.next() {
+            let mut block = [0u8; 512];
+            write_sparse_entries(&mut block, chunk)?;
+            block[504] = chunks.peek().is_some() as u8;
+            data.extend_from_slice(&block);
+        }
+        data.extend_from_slice(&self.data);
+
+        Ok(TarNode {
+            header,
+            pax: self.pax.clone(),
+            sparse: None,
+            location: None,
+            inode: None,
+            gnu_long_names: self.gnu_long_names,
+            data,
+            lazy: None,
+        })
+    }
+}
+
+/// Read the old GNU sparse extension blocks following `header` from `input`, for as long as each one says
+/// another follows. Returns no blocks for any other entry.
+pub(crate) fn read_gnu_extensions<T: io::Read>(
+    header: &TarHeader,
+    input: &mut T,
+) -> Result<Vec<u8>, TarError> {
+    let mut blocks = Vec::new();
+    let mut extended = header.link_indicator[0] == FileType::GnuSparse as u8
+        && header.file_prefix[GNU_ISEXTENDED_OFFSET] != 0;
+    while extended {
+        let mut block = [0u8; 512];
+        if !read_block(input, &mut block)? {
+            return Err(TarError::Truncated);
+        }
+        extended = block[504] != 0;
+        blocks.extend_from_slice(&block);
+    }
+    Ok(blocks)
+}
+
+/// Read the map that version 1.0 of the PAX sparse format stores as decimal lines at the head of the
+/// data, returning the segments and the number of bytes of whole blocks the map takes up.
+fn read_data_map(data: &[u8]) -> Result<(Vec<(u64, u64)>, usize), TarError> {
+    let mut numbers = Vec::<u64>::new();
+    let mut line = String::new();
+    let mut count = None;
+    for (i, &byte) in data.iter().enumerate() {
+        if byte != b'\n' {
+            line.push(byte as char);
+            continue;
+        }
+        let n = line.parse().map_err(|_| TarError::InvalidSparseMap)?;
+        line.clear();
+        match count {
+            None => count = Some(n),
+            Some(_) => numbers.push(n),
+        }
+        if count.map(|c| numbers.len() as u64 == c * 2) == Some(true) {
+            let segments = numbers.chunks(2).map(|p| (p[0], p[1])).collect();
+            return Ok((segments, (i + 1).div_ceil(512) * 512));
+        }
+    }
+    Err(TarError::InvalidSparseMap)
+}
+
+/// GNU tar reads the data of each segment as whole blocks, so grow every segment but the last to a multiple
+/// of ``512`` bytes by taking in zeros from the following hole, merging segments that end up touching.
+fn align_segments(map: SparseMap, data: &[u8]) -> (SparseMap, Vec<u8>) {
+    let mut segments = Vec::<(u64, u64)>::new();
+    let mut out = Vec::<u8>::with_capacity(data.len());
+    let mut rest = data;
+
+    for (i, &(offset, length)) in map.segments.iter().enumerate() {
+        let (chunk, remaining) = rest.split_at(length as usize);
+        rest = remaining;
+
+        /* Fill the gap to the end of the previous segment with zeros when merging */
+        match segments.last_mut() {
+            Some(last) if last.0 + last.1 >= offset => {
+                out.resize(out.len() + (offset - last.0 - last.1) as usize, 0);
+                last.1 = offset + length - last.0;
+            }
+            _ => segments.push((offset, length)),
+        }
+        out.extend_from_slice(chunk);
+
+        /* Padding to a whole block stops at the next segment, which is then merged into this one */
+        let last = segments.last_mut().unwrap();
+        if let Some(&(next, _)) = map.segments.get(i + 1) {
+            let end = (last.0 + last.1.div_ceil(512) * 512)
+                .min(map.real_size)
+                .min(next);
+            out.resize(out.len() + (end - last.0 - last.1) as usize, 0);
+            last.1 = end - last.0;
+        }
+    }
+
+    /* A trailing hole is marked by an empty segment at the end of the file */
+    if segments.last().map(|s| s.0 + s.1).unwrap_or(0) < map.real_size {
+        segments.push((map.real_size, 0));
+    }
+
+    (
+        SparseMap {
+            real_size: map.real_size,
+            segments,
+        },
+        out,
+    )
+}
+
+/// Check that the segments of `map` are in order, within the file, and hold `stored` bytes in total.
+fn check_map(map: &SparseMap, stored: usize) -> Result<(), TarError> {
+    /* The numbers come from the archive, so sums that overflow are as invalid as any others */
+    let ends = map
+        .segments
+        .iter()
+        .map(|s| s.0.checked_add(s.1).ok_or(TarError::InvalidSparseMap))
+        .collect::<Result<Vec<u64>, TarError>>()?;
+    let total = map
+        .segments
+        .iter()
+        .try_fold(0u64, |total, s| total.checked_add(s.1));
+    if total != Some(stored as u64) || ends.iter().any(|&end| end > map.real_size) {
+        return Err(TarError::InvalidSparseMap);
+    }
+    if ends
+        .iter()
+        .zip(map.segments.iter().skip(1))
+        .any(|(&end, next)| end > next.0)
+    {
+        return Err(TarError::InvalidSparseMap);
+    }
+    Ok(())
+}
+
+/// Write the file described by `map` to `output`, taking the contents of the segments from `stored` and
+/// filling the holes with zeros.
+pub(crate) fn write_holes<T: std::io::Write>(
+    map: &SparseMap,
+    stored: &[u8],
+    mut output: T,
+) -> Result<usize, TarError> {
+    let zeros = [0u8; 512];
+    let mut written = 0u64;
+    let mut pos = 0usize;
+    for &(offset, length) in map.segments.iter().chain([(map.real_size, 0)].iter()) {
+        while written < offset {
+            let len = (offset - written).min(zeros.len() as u64) as usize;
+            output.write_all(&zeros[..len])?;
+            written += len as u64;
+        }
+        output.write_all(&stored[pos..pos + length as usize])?;
+        pos += length as usize;
+        written += length;
+    }
+
+    Ok(written as usize)
+}
+
+/// Write the file described by `map` into the empty `file`, seeking over the holes so that they take up
+/// no space on filesystems supporting sparse files. With `punch`, runs of zeros within the segments that
+/// cover whole filesystem blocks are seeked over as well.
+pub(crate) fn write_sparse_file(
+    file: &File,
+    map: &SparseMap,
+    stored: &[u8],
+    punch: bool,
+) -> io::Result<()> {
+    file.set_len(map.real_size)?;
+    let block = match punch {
+        true => file.metadata()?.st_blksize().max(512),
+        false => u64::MAX,
+    };
+
+    let mut pos = 0usize;
+    for &(offset, length) in &map.segments {
+        let mut segment = &stored[pos..pos + length as usize];
+        pos += length as usize;
+
+        /* Split the segment at filesystem block boundaries, leaving out the blocks that are all zeros */
+        let mut offset = offset;
+        while !segment.is_empty() {
+            let len = (block - offset % block).min(segment.len() as u64) as usize;
+            let (chunk, rest) = segment.split_at(len);
+            if !punch || chunk.iter().any(|&b| b != 0) {
+                file.write_all_at(chunk, offset)?;
+            }
+            offset += len as u64;
+            segment = rest;
+        }
+    }
+    Ok(())
+}
+
+/// Read up to `count` `(offset, numbytes)` pairs of ``12`` byte octal fields, stopping at the first empty
+/// entry.
+fn read_sparse_entries(fields: &[u8], count: usize) -> Result<Vec<(u64, u64)>, TarError> {
+    let mut segments = Vec::new();
+    for entry in fields.chunks(24).take(count) {
+        if entry[0] == 0 {
+            break;
+        }
+        let offset = oct_to_dec(&entry[..12]).map_err(|_| TarError::InvalidSparseMap)?;
+        let length = oct_to_dec(&entry[12..]).map_err(|_| TarError::InvalidSparseMap)?;
+        segments.push((offset as u64, length as u64));
+    }
+    Ok(segments)
+}
+
+/// Write `(offset, numbytes)` pairs as ``12`` byte octal fields.
+fn write_sparse_entries(out: &mut [u8], segments: &[(u64, u64)]) -> Result<(), TarError> {
+    for (i, (offset, length)) in segments.iter().enumerate() {
+        let entry = &mut out[i * 24..i * 24 + 24];
+        dec_to_oct(*offset, &mut entry[..12], OctalTerminator::Nul)?;
+        dec_to_oct(*length, &mut entry[12..], OctalTerminator::Nul)?;
+    }
+    Ok(())
+}
+
+/// Find the regions of `file`, which is `len` bytes long, that hold data with ``SEEK_DATA`` and
+/// ``SEEK_HOLE``. Returns `None` if the file has no holes or the filesystem can't report them.
+pub(crate) fn find_holes(file: &File, len: u64) -> io::Result<Option<SparseMap>> {
+    let fd = file.as_raw_fd();
+    let mut segments = Vec::new();
+    let mut offset = 0;
+
+    while offset < len {
+        /* SAFETY: `fd` belongs to `file`, which stays open for the calls */
+        let data = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+        if data < 0 {
+            match io::Error::last_os_error().raw_os_error() {
+                /* There is no data past `offset`, so the file ends in a hole */
+                Some(libc::ENXIO) => break,
+                Some(libc::EINVAL) => return Ok(None),
+                _ => return Err(io::Error::last_os_error()),
+            }
+        }
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (data, hole) = (data as u64, (hole as u64).min(len));
+        segments.push((data, hole - data));
+        offset = hole;
+    }
+
+    if segments.len() == 1 && segments[0] == (0, len) {
+        return Ok(None);
+    }
+    Ok(Some(SparseMap {
+        real_size: len,
+        segments,
+    }))
+}
+
+/// Read the contents of each segment of `map` from `file` back-to-back.
+pub(crate) fn read_segments(file: &File, map: &SparseMap) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for &(offset, length) in &map.segments {
+        let start = data.len();
+        data.resize(start + length as usize, 0);
+        file.read_exact_at(&mut data[start..], offset)?;
+    }
+    Ok(data)
+}
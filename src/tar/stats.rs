@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::error::TarWarning;
+use crate::tar::*;
+
+/// A summary of the contents of an archive, for getting a quick picture of an unfamiliar tarball.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveStats {
+    /// The number of entries, not counting journal records.
+    pub entries: usize,
+    /// The number of entries of each type, in the order each type first appears.
+    pub by_type: Vec<(FileType, usize)>,
+    /// The number of regular files with each extension, most common first. Files without an extension
+    /// are counted under ``""``.
+    pub by_extension: Vec<(String, usize)>,
+    /// The sum of the file sizes. Sparse files count with their apparent size.
+    pub total_size: u64,
+    /// The number of bytes the entries take up in the archive, including headers and block padding.
+    pub stored_size: u64,
+    /// The largest entries, largest first.
+    pub largest: Vec<EntryInfo>,
+}
+
+/// What an operation did, returned by `TarFile::write_with`, `TarFile::extract_with` and
+/// `TarFile::append_dir_all_with`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OperationStats {
+    /// The number of entries written, extracted or added of each type, in the order each type first
+    /// appears.
+    pub by_type: Vec<(FileType, usize)>,
+    /// The bytes of entry data. Writing and adding count the data as stored in the archive, while
+    /// extracting counts the size of the files created.
+    pub data_bytes: u64,
+    /// The bytes of headers written, including extended headers. Only writing counts these.
+    pub header_bytes: u64,
+    /// The zeros written to pad data to whole blocks, and the archive to whole records after the blocks
+    /// marking its end. Only writing counts these.
+    pub padding_bytes: u64,
+    /// How long the operation took.
+    pub duration: Duration,
+    /// The entries left out and why, in the order they were reached.
+    pub skipped: Vec<(String, SkipReason)>,
+    /// The warnings raised by the operation.
+    pub warnings: Vec<TarWarning>,
+}
+
+/// Why an entry was left out of an operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The path matched an exclude pattern. Paths skipped by ``.gitignore`` files are not listed.
+    Excluded,
+    /// The file is a socket, which tar can't store.
+    Socket,
+    /// The FIFO or device was not archived, or could not be created without privileges.
+    Special,
+    /// The directory leads back to one of its ancestors.
+    FilesystemLoop,
+    /// An entry of the same name was already in the archive and `DuplicatePolicy::KeepFirst` kept it.
+    Duplicate,
+    /// Nothing was left of the name after `strip_components` or a transform.
+    NoName,
+    /// The link was skipped by `ExtractOptions::symlinks` or `ExtractOptions::links`.
+    Link,
+    /// The entry is of a type that is not extracted.
+    Unsupported,
+}
+
+impl OperationStats {
+    /// Returns the number of entries written, extracted or added.
+    pub fn entries(&self) -> usize {
+        self.by_type.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Returns the number of bytes written to the archive, counting headers, data and padding.
+    pub fn total_bytes(&self) -> u64 {
+        self.header_bytes + self.data_bytes + self.padding_bytes
+    }
+
+    /// Count an entry of `file_type` holding `data` bytes.
+    pub(crate) fn count(&mut self, file_type: FileType, data: u64) {
+        match self.by_type.iter_mut().find(|(t, _)| *t == file_type) {
+            Some((_, count)) => *count += 1,
+            None => self.by_type.push((file_type, 1)),
+        }
+        self.data_bytes += data;
+    }
+
+    /// Record that the entry `name` was left out for `reason`.
+    pub(crate) fn skip(&mut self, name: String, reason: SkipReason) {
+        trace::event!(debug, name = %name, ?reason, "skipped entry");
+        self.skipped.push((name, reason));
+    }
+
+    /// Record how long the operation begun at `start` took, and report the outcome and warnings to
+    /// ``tracing``.
+    pub(crate) fn finish(&mut self, start: Instant) {
+        self.duration = start.elapsed();
+        trace::event!(
+            info,
+            entries = self.entries(),
+            data_bytes = self.data_bytes,
+            total_bytes = self.total_bytes(),
+            skipped = self.skipped.len(),
+            duration = ?self.duration,
+            "finished"
+        );
+        trace::warnings(&self.warnings);
+    }
+}
+
+impl TarFile {
+    /// Returns a summary of the archive, listing the `top` largest entries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{FileType, TarFile};
+    ///
+    /// let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// let stats = data.stats(5);
+    /// assert_eq!(stats.by_type, vec![(FileType::Normal, 1)]);
+    /// assert_eq!(stats.by_extension, vec![("txt".to_string(), 1)]);
+    /// assert_eq!(stats.stored_size, 1024);
+    /// ```
+    pub fn stats(&self, top: usize) -> ArchiveStats {
+        let nodes: Vec<&TarNode> = self
+            .file
+            .iter()
+            .filter(|n| n.journal_entry().is_none())
+            .collect();
+        let infos: Vec<EntryInfo> = nodes.iter().map(|n| n.info()).collect();
+
+        let mut by_type = Vec::<(FileType, usize)>::new();
+        let mut extensions = HashMap::<String, usize>::new();
+        for info in &infos {
+            match by_type.iter_mut().find(|(t, _)| *t == info.file_type) {
+                Some((_, count)) => *count += 1,
+                None => by_type.push((info.file_type, 1)),
+            }
+            if matches!(info.file_type, FileType::Normal | FileType::GnuSparse) {
+                *extensions.entry(extension(&info.name)).or_insert(0) += 1;
+            }
+        }
+        let mut by_extension: Vec<(String, usize)> = extensions.into_iter().collect();
+        by_extension.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut largest = infos.clone();
+        largest.sort_by_key(|e| std::cmp::Reverse(e.size));
+        largest.truncate(top);
+
+        ArchiveStats {
+            entries: infos.len(),
+            by_type,
+            by_extension,
+            total_size: infos.iter().map(|i| i.size).sum(),
+            stored_size: nodes.iter().map(|n| n.stored_size()).sum(),
+            largest,
+        }
+    }
+}
+
+impl TarNode {
+    /// Returns the number of bytes the node takes up when written, including its extended headers.
+    fn stored_size(&self) -> u64 {
+        let headers = self
+            .write_header(&mut std::io::sink(), &WriteOptions::default())
+            .unwrap_or(512);
+        (headers + self.data_len().div_ceil(512) * 512) as u64
+    }
+}
+
+impl fmt::Display for ArchiveStats {
+    /// Format the summary as a short report of the types, extensions and largest entries.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} entries", self.entries)?;
+        writeln!(
+            f,
+            "{} bytes of data, {} bytes stored",
+            self.total_size, self.stored_size
+        )?;
+
+        writeln!(f, "\nBy type:")?;
+        for (file_type, count) in &self.by_type {
+            writeln!(f, "{:>8}  {:?}", count, file_type)?;
+        }
+
+        writeln!(f, "\nBy extension:")?;
+        for (ext, count) in &self.by_extension {
+            let ext = match ext.is_empty() {
+                true => "(none)".to_string(),
+                false => format!(".{}", ext),
+            };
+            writeln!(f, "{:>8}  {}", count, ext)?;
+        }
+
+        writeln!(f, "\nLargest entries:")?;
+        for entry in &self.largest {
+            writeln!(f, "{:>12}  {}", entry.size, entry.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OperationStats {
+    /// Format the statistics as a one line summary, such as ``3 entries, 10240 bytes (512 data, 1536 headers,
+    /// 8192 padding) in 1.2ms, 1 skipped``.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} entries, ", self.entries())?;
+        match self.total_bytes() == self.data_bytes {
+            true => write!(f, "{} bytes", self.data_bytes)?,
+            false => write!(
+                f,
+                "{} bytes ({} data, {} headers, {} padding)",
+                self.total_bytes(),
+                self.data_bytes,
+                self.header_bytes,
+                self.padding_bytes
+            )?,
+        }
+        write!(f, " in {:?}", self.duration)?;
+        if !self.skipped.is_empty() {
+            write!(f, ", {} skipped", self.skipped.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the extension of the last component of `name`. Dot files like ``.bashrc`` have none.
+fn extension(name: &str) -> String {
+    let base = name.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    match base.rfind('.') {
+        Some(i) if i > 0 => base[i + 1..].to_string(),
+        _ => String::new(),
+    }
+}
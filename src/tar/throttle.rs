@@ -0,0 +1,94 @@
+use std::io::{self, IoSlice, Write};
+use std::time::{Duration, Instant};
+
+/// Credit built up while idle that may be spent at full speed, so a pause doesn't allow a long burst.
+const BURST: Duration = Duration::from_secs(1);
+
+/// Paces the bytes moved by an operation to a rate limit, such as `ReadOptions::rate_limit`, by sleeping
+/// whenever it gets ahead.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    rate: Option<u64>,
+    start: Instant,
+    moved: u64,
+}
+
+impl Throttle {
+    /// Pace to `rate` bytes per second, or not at all without one.
+    pub(crate) fn new(rate: Option<u64>) -> Self {
+        Throttle {
+            rate,
+            start: Instant::now(),
+            moved: 0,
+        }
+    }
+
+    /// Returns `true` if there is a limit to pace to.
+    pub(crate) fn is_limited(&self) -> bool {
+        self.rate.is_some()
+    }
+
+    /// Returns how many of `len` bytes to move at once, so that each step only takes a fraction of a second
+    /// and the pace stays even.
+    pub(crate) fn chunk(&self, len: usize) -> usize {
+        match self.rate {
+            Some(rate) => len.min((rate / 10).clamp(512, 1 << 16) as usize),
+            None => len,
+        }
+    }
+
+    /// Account for `n` bytes having been moved, sleeping until they fit within the rate.
+    pub(crate) fn consume(&mut self, n: usize) {
+        let rate = match self.rate {
+            Some(rate) => rate.max(1),
+            None => return,
+        };
+        let due = |moved: u64| Duration::from_secs_f64(moved as f64 / rate as f64);
+        if self.start.elapsed() > due(self.moved) + BURST {
+            self.start = Instant::now();
+            self.moved = 0;
+        }
+
+        self.moved += n as u64;
+        let elapsed = self.start.elapsed();
+        if let Some(wait) = due(self.moved).checked_sub(elapsed) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Wrap `output` so writes to it are paced by this throttle.
+    pub(crate) fn writer<W: Write>(&mut self, output: W) -> Throttled<'_, W> {
+        Throttled {
+            inner: output,
+            throttle: self,
+        }
+    }
+}
+
+/// A writer whose writes are paced by a `Throttle`.
+pub(crate) struct Throttled<'a, W> {
+    inner: W,
+    throttle: &'a mut Throttle,
+}
+
+impl<W: Write> Write for Throttled<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(&buf[..self.throttle.chunk(buf.len())])?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if !self.throttle.is_limited() {
+            return self.inner.write_vectored(bufs);
+        }
+        match bufs.iter().find(|b| !b.is_empty()) {
+            Some(buf) => self.write(buf),
+            None => Ok(0),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+use crate::tar::throttle::Throttle;
+use crate::tar::ReadOptions;
+
+/// Told the time left for each read, for inputs that can stop a blocked read themselves.
+type LimitHook = Box<dyn FnMut(Option<Duration>) -> io::Result<()>>;
+
+/// Applies the timeout, deadline and rate limit of `ReadOptions` to the reads of an input.
+pub(crate) struct TimedReader<R> {
+    inner: R,
+    pub(crate) options: ReadOptions,
+    deadline: Option<Instant>,
+    throttle: Throttle,
+    on_limit: Option<LimitHook>,
+    pub(crate) timed_out: bool,
+}
+
+impl<R: Read> TimedReader<R> {
+    pub(crate) fn new(inner: R, options: &ReadOptions) -> Self {
+        TimedReader {
+            inner,
+            options: options.clone(),
+            deadline: options.deadline.map(|d| Instant::now() + d),
+            throttle: Throttle::new(options.rate_limit),
+            on_limit: None,
+            timed_out: false,
+        }
+    }
+
+    /// Call `f` with the time limit of each read before it starts.
+    pub(crate) fn on_limit<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(Option<Duration>) -> io::Result<()> + 'static,
+    {
+        self.on_limit = Some(Box::new(f));
+        self
+    }
+
+    fn expire(&mut self) -> io::Error {
+        self.timed_out = true;
+        io::Error::from(ErrorKind::TimedOut)
+    }
+
+    /// Read into `buf`, failing once the timeout or deadline runs out.
+    fn read_timed(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.options.timeout.is_none() && self.deadline.is_none() {
+            return self.inner.read(buf);
+        }
+
+        let start = Instant::now();
+        let remaining = self.deadline.map(|d| d.saturating_duration_since(start));
+        if remaining == Some(Duration::ZERO) {
+            return Err(self.expire());
+        }
+        let limit = match (self.options.timeout, remaining) {
+            (Some(t), Some(r)) => Some(t.min(r)),
+            (t, r) => t.or(r),
+        };
+        if let Some(f) = &mut self.on_limit {
+            f(limit)?;
+        }
+
+        match self.inner.read(buf) {
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Err(self.expire())
+            }
+            Ok(_) if limit.is_some_and(|l| start.elapsed() > l) => Err(self.expire()),
+            result => result,
+        }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    /* Time spent waiting for the rate limit counts towards the deadline, but not the timeout of a read */
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.throttle.chunk(buf.len());
+        let n = self.read_timed(&mut buf[..len])?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for TimedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
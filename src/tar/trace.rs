@@ -0,0 +1,31 @@
+use minitar_core::TarWarning;
+
+/// Enter a ``tracing`` span at the info level for the rest of the enclosing block. Without the ``tracing``
+/// feature this expands to nothing, and the fields are never evaluated.
+macro_rules! span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($($arg)+).entered();
+    };
+}
+
+/// Emit a ``tracing`` event at `level`, one of ``trace``, ``debug``, ``info``, ``warn`` or ``error``.
+/// Without the ``tracing`` feature this expands to nothing, and the fields are never evaluated.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+pub(crate) use event;
+pub(crate) use span;
+
+/// Emit a warning event for each of `warnings`.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn warnings(warnings: &[TarWarning]) {
+    #[cfg(feature = "tracing")]
+    for warning in warnings {
+        tracing::warn!("{}", warning);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A set of edits staged against an archive on disk. Nothing is written until `commit`, which streams the
+/// archive through the edits into a temporary file and then replaces the original in a single rename, so
+/// the archive is never left half-edited. Dropping the transaction or calling `rollback` discards the edits.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::TarFile;
+///
+/// std::fs::copy("test/1.tar", "target/doc-transaction.tar").unwrap();
+/// let mut tx = TarFile::transaction("target/doc-transaction.tar".to_string());
+/// tx.rename("1.txt".to_string(), "one.txt".to_string()).unwrap();
+/// tx.append("test/1.txt".to_string()).unwrap();
+/// tx.commit().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Transaction {
+    path: String,
+    removes: HashSet<String>,
+    renames: Vec<(String, String)>,
+    appends: TarFile,
+}
+
+impl TarFile {
+    /// Start a `Transaction` editing the archive in the file `filename`.
+    pub fn transaction(filename: String) -> Transaction {
+        Transaction {
+            path: filename,
+            removes: HashSet::new(),
+            renames: Vec::new(),
+            appends: TarFile::default(),
+        }
+    }
+
+    /// Remove every entry named `name` from the archive in the file `filename` without loading it, by
+    /// streaming it into a temporary file that then replaces the original. Returns `false`, leaving the
+    /// archive untouched, if there was no such entry. To remove several names in one pass, use
+    /// `delete_from_disk`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// std::fs::copy("test/1.tar", "target/doc-remove.tar").unwrap();
+    /// assert!(TarFile::remove_from_disk("target/doc-remove.tar".to_string(), "1.txt".to_string()).unwrap());
+    /// ```
+    pub fn remove_from_disk(filename: String, name: String) -> Result<bool, TarError> {
+        let mut tx = TarFile::transaction(filename);
+        tx.remove(name);
+        let (_, removed) = tx.apply(&ReadOptions::default(), &WriteOptions::default(), false)?;
+        Ok(removed > 0)
+    }
+
+    /// Remove every entry whose name is in `names` from the archive in the file `filename`, like
+    /// ``tar --delete``. The archive is streamed block by block into a temporary file that then replaces
+    /// the original, so memory use does not depend on the size of the archive or its entries. Returns the
+    /// number of entries removed, leaving the archive untouched if there were none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// std::fs::copy("test/1.tar", "target/doc-delete.tar").unwrap();
+    /// let names = vec!["1.txt".to_string(), "2.txt".to_string()];
+    /// assert_eq!(TarFile::delete_from_disk("target/doc-delete.tar".to_string(), &names).unwrap(), 1);
+    /// ```
+    pub fn delete_from_disk(filename: String, names: &[String]) -> Result<usize, TarError> {
+        let mut tx = TarFile::transaction(filename);
+        for name in names {
+            tx.remove(name.clone());
+        }
+        let (_, removed) = tx.apply(&ReadOptions::default(), &WriteOptions::default(), false)?;
+        Ok(removed)
+    }
+}
+
+impl Transaction {
+    /// Stage the removal of every entry named `name`.
+    pub fn remove(&mut self, name: String) {
+        self.removes.insert(name);
+    }
+
+    /// Stage renaming every entry named `from` to `to`. Renames are applied in the order they were staged.
+    pub fn rename(&mut self, from: String, to: String) -> Result<(), TarError> {
+        if to.len() > 100 {
+            return Err(TarError::NameTooLong);
+        }
+
+        self.renames.push((from, to));
+        Ok(())
+    }
+
+    /// Stage appending the file `filename` to the end of the archive. The file is read immediately.
+    pub fn append(&mut self, filename: String) -> Result<(), TarError> {
+        self.appends.append(filename)
+    }
+
+    /// Stage appending the file `filename` in the same way as `append`, using the provided `ArchiveOptions`.
+    pub fn append_with(
+        &mut self,
+        filename: String,
+        options: &ArchiveOptions,
+    ) -> Result<(), TarError> {
+        self.appends.append_with(filename, options)
+    }
+
+    /// Returns the warnings raised while staging appends.
+    pub fn warnings(&self) -> &[TarWarning] {
+        self.appends.warnings()
+    }
+
+    /// Discard all staged edits, leaving the archive untouched.
+    pub fn rollback(self) {}
+
+    /// Apply the staged edits to the archive. Returns the size of the new archive in bytes.
+    pub fn commit(self) -> Result<usize, TarError> {
+        self.commit_with(&ReadOptions::default(), &WriteOptions::default())
+    }
+
+    /// Apply the staged edits in the same way as `commit`, using the provided options to read the original
+    /// archive and write the new one.
+    pub fn commit_with(self, read: &ReadOptions, write: &WriteOptions) -> Result<usize, TarError> {
+        Ok(self.apply(read, write, true)?.0)
+    }
+
+    /// Rewrite the archive with the staged edits, returning the size of the new archive and the number of
+    /// entries removed. Unless `always` is set, the original is only replaced if entries were removed.
+    fn apply(
+        &self,
+        read: &ReadOptions,
+        write: &WriteOptions,
+        always: bool,
+    ) -> Result<(usize, usize), TarError> {
+        let tmp = format!("{}.minitar-tmp", self.path);
+        match self.rewrite(&tmp, read, write) {
+            Ok((written, removed)) if always || removed > 0 => {
+                fs::rename(&tmp, &self.path)?;
+                Ok((written, removed))
+            }
+            Ok(result) => {
+                fs::remove_file(&tmp)?;
+                Ok(result)
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+
+    /// Stream the original archive through the staged edits into the file `tmp`.
+    fn rewrite(
+        &self,
+        tmp: &str,
+        read: &ReadOptions,
+        write: &WriteOptions,
+    ) -> Result<(usize, usize), TarError> {
+        let file = Arc::new(File::open(&self.path)?);
+        let source = lazy::Source::File(file.clone());
+        let mut input = BufReader::new(&*file);
+        let mut output = BufWriter::new(File::create(tmp)?);
+
+        let mut written = 0;
+        let mut removed = 0;
+        let mut trailer = true;
+        while let Some(mut node) = next_entry_header(&mut input, read, &mut trailer)? {
+            /* The entry data is copied through rather than loaded, so entries of any size can be handled */
+            let len = (node.data_blocks() * 512) as u64;
+            let mut name = node.name();
+            if self.removes.contains(&name) {
+                removed += 1;
+                if io::copy(&mut (&mut input).take(len), &mut io::sink())? != len {
+                    return Err(TarError::Truncated);
+                }
+                continue;
+            }
+            for (from, to) in &self.renames {
+                if name == *from {
+                    node.set_name(to)?;
+                    name = to.clone();
+                }
+            }
+
+            /* Kept entries are copied from file to file, inside the kernel where possible */
+            written += node.write_header(&mut output, write)?;
+            output.flush()?;
+            let offset = input.stream_position()?;
+            if source.copy_to_file(offset, len, output.get_ref())? != len {
+                return Err(TarError::Truncated);
+            }
+            input.seek(SeekFrom::Start(offset + len))?;
+            written += len as usize;
+        }
+
+        for node in &self.appends.file {
+            written += node.clone().write_with(&mut output, write)?;
+        }
+        if written > 0 {
+            written += write_trailer(&mut output, written, write)?;
+        }
+
+        /* Make sure the new archive is on disk before it replaces the original */
+        output.flush()?;
+        output.get_ref().sync_all()?;
+        Ok((written, removed))
+    }
+}
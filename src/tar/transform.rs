@@ -0,0 +1,92 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::TarError;
+use crate::tar::*;
+
+/// A rewrite of entry names, like ``tar --transform``. It can be applied when adding files with
+/// `ArchiveOptions::transform`, when extracting with `ExtractOptions::transform`, or to the entries of a
+/// loaded archive with `TarFile::transform`.
+///
+/// # Example
+///
+/// ```
+/// use minitar::tar::Transform;
+///
+/// let transform = Transform::prefix("package/".to_string());
+/// assert_eq!(transform.apply("src/main.rs"), "package/src/main.rs");
+/// ```
+#[derive(Clone)]
+pub struct Transform(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Transform {
+    /// Rewrite each name with the callback `f`. An empty result on extraction skips the entry.
+    pub fn new<F>(f: F) -> Transform
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Transform(Arc::new(f))
+    }
+
+    /// Put `prefix` in front of every name.
+    pub fn prefix(prefix: String) -> Transform {
+        Transform::new(move |name| format!("{}{}", prefix, name))
+    }
+
+    /// Replace the leading directory `from` with `to` in every name below or equal to it, leaving other
+    /// names unchanged. Only whole path components are matched, so ``src`` does not match ``srcs/a.rs``.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::Transform;
+    ///
+    /// let transform = Transform::replace_dir("src".to_string(), "lib".to_string());
+    /// assert_eq!(transform.apply("src/main.rs"), "lib/main.rs");
+    /// assert_eq!(transform.apply("srcs/main.rs"), "srcs/main.rs");
+    /// ```
+    pub fn replace_dir(from: String, to: String) -> Transform {
+        let from = from.trim_end_matches('/').to_string();
+        let to = to.trim_end_matches('/').to_string();
+        Transform::new(move |name| match name.strip_prefix(from.as_str()) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", to, rest),
+            _ => name.to_string(),
+        })
+    }
+
+    /// Returns the transformed `name`.
+    pub fn apply(&self, name: &str) -> String {
+        (self.0)(name)
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Transform")
+    }
+}
+
+impl TarFile {
+    /// Rename every entry with `transform`, updating the header checksums. Journal records are left
+    /// unchanged. Fails with `TarError::NameTooLong`, leaving the archive unchanged, if a new name does not
+    /// fit in the header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{TarFile, Transform};
+    ///
+    /// let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    /// data.transform(&Transform::prefix("package/".to_string())).unwrap();
+    /// assert_eq!(data.list()[0].name, "package/1.txt");
+    /// ```
+    pub fn transform(&mut self, transform: &Transform) -> Result<(), TarError> {
+        let mut nodes = self.file.clone();
+        for node in nodes.iter_mut().filter(|n| n.journal_entry().is_none()) {
+            node.set_name(&transform.apply(&node.name()))?;
+        }
+
+        self.file = nodes;
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use io_uring::{opcode, types, IoUring};
+
+/// The number of reads or writes kept in flight at once.
+const DEPTH: u32 = 64;
+
+/// The number of files gathered before their reads or writes are submitted together.
+pub(crate) const BATCH_FILES: usize = 64;
+
+/// The number of bytes gathered before the reads or writes of a batch are submitted together. Files bigger
+/// than this are read and written on their own, without the ring.
+pub(crate) const BATCH_BYTES: usize = 16 << 20;
+
+/// An io_uring instance that submits the reads or writes of many files at once, as described in
+/// `ArchiveOptions::io_uring` and `ExtractOptions::io_uring`.
+pub(crate) struct Ring {
+    ring: IoUring,
+}
+
+/// A read or write of a whole buffer, resubmitted until it completes.
+struct Op {
+    fd: RawFd,
+    offset: u64,
+    buf: *mut u8,
+    len: usize,
+    done: usize,
+    write: bool,
+}
+
+impl Ring {
+    /// Set up a ring, returning `None` where the kernel doesn't support io_uring or it is disabled, so
+    /// callers fall back to plain reads and writes.
+    pub(crate) fn new() -> Option<Ring> {
+        IoUring::new(DEPTH).ok().map(|ring| Ring { ring })
+    }
+
+    /// Read up to `len` bytes from each of `files`, starting at `offset`. Each buffer is cut short where
+    /// its file ends first.
+    pub(crate) fn read_all(&mut self, files: &[(&File, u64, usize)]) -> io::Result<Vec<Vec<u8>>> {
+        let mut bufs = files
+            .iter()
+            .map(|(_, _, len)| vec![0; *len])
+            .collect::<Vec<Vec<u8>>>();
+        let mut ops = files
+            .iter()
+            .zip(bufs.iter_mut())
+            .map(|((file, offset, _), buf)| Op {
+                fd: file.as_raw_fd(),
+                offset: *offset,
+                buf: buf.as_mut_ptr(),
+                len: buf.len(),
+                done: 0,
+                write: false,
+            })
+            .collect::<Vec<Op>>();
+
+        if let Err(err) = self.run(&mut ops) {
+            /* The kernel may still be reading into the buffers, so they are never freed */
+            std::mem::forget(bufs);
+            return Err(err);
+        }
+        for (buf, op) in bufs.iter_mut().zip(ops) {
+            buf.truncate(op.done);
+        }
+        Ok(bufs)
+    }
+
+    /// Write the whole of each buffer to the start of its file.
+    pub(crate) fn write_all(&mut self, files: &[(&File, &[u8])]) -> io::Result<()> {
+        let mut ops = files
+            .iter()
+            .map(|(file, data)| Op {
+                fd: file.as_raw_fd(),
+                offset: 0,
+                buf: data.as_ptr() as *mut u8,
+                len: data.len(),
+                done: 0,
+                write: true,
+            })
+            .collect::<Vec<Op>>();
+        self.run(&mut ops)
+    }
+
+    /// Submit `ops`, keeping up to ``DEPTH`` in flight and resubmitting the rest of any that completed
+    /// short, until every one has finished. After an operation fails nothing more is submitted, and its
+    /// error is returned once the operations in flight have completed. A failure of the ring itself is
+    /// returned at once, leaving the operations in flight to the kernel.
+    fn run(&mut self, ops: &mut [Op]) -> io::Result<()> {
+        let mut queue = (0..ops.len())
+            .rev()
+            .filter(|i| ops[*i].len > 0)
+            .collect::<Vec<usize>>();
+        let mut in_flight = 0;
+        let mut error = None;
+
+        loop {
+            while error.is_none() && in_flight < DEPTH as usize {
+                let i = match queue.pop() {
+                    Some(i) => i,
+                    None => break,
+                };
+                let op = &ops[i];
+                /* Huge buffers are moved a gigabyte at a time */
+                let len = (op.len - op.done).min(1 << 30) as u32;
+                let fd = types::Fd(op.fd);
+                /* SAFETY: the buffer stays alive and unmoved until the operation completes below */
+                let buf = unsafe { op.buf.add(op.done) };
+                let entry = match op.write {
+                    true => opcode::Write::new(fd, buf, len)
+                        .offset(op.offset + op.done as u64)
+                        .build(),
+                    false => opcode::Read::new(fd, buf, len)
+                        .offset(op.offset + op.done as u64)
+                        .build(),
+                }
+                .user_data(i as u64);
+                /* SAFETY: the entry refers to a descriptor and buffer that outlive the operation */
+                if unsafe { self.ring.submission().push(&entry) }.is_err() {
+                    queue.push(i);
+                    break;
+                }
+                in_flight += 1;
+            }
+            if in_flight == 0 {
+                break;
+            }
+
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => (),
+                Err(err) if is_retryable(&err) => continue,
+                Err(err) => return Err(err),
+            }
+            for cqe in self.ring.completion() {
+                in_flight -= 1;
+                let i = cqe.user_data() as usize;
+                let op = &mut ops[i];
+                let res = cqe.result();
+                if res < 0 {
+                    let err = io::Error::from_raw_os_error(-res);
+                    match is_retryable(&err) {
+                        true => queue.push(i),
+                        false => error = error.or(Some(err)),
+                    }
+                } else if res == 0 {
+                    /* A read of nothing is the end of the file, while a write of nothing would repeat */
+                    if op.write {
+                        error = error.or_else(|| Some(io::ErrorKind::WriteZero.into()));
+                    }
+                } else {
+                    op.done += res as usize;
+                    if op.done < op.len {
+                        queue.push(i);
+                    }
+                }
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns `true` if an operation or submission failed for a reason that goes away when it is retried.
+fn is_retryable(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EINTR | libc::EAGAIN | libc::EBUSY)
+    )
+}
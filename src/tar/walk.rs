@@ -0,0 +1,418 @@
+use std::fs;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+use std::fs::File;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::error::{TarError, TarWarning};
+use crate::tar::*;
+
+impl TarFile {
+    /// Append the directory `dirname` and everything below it. Entries are added in sorted order for
+    /// reproducible archives, and symbolic links are stored rather than followed unless the options
+    /// dereference them. Returns `OperationStats` counting the entries added and listing those skipped,
+    /// along with the warnings raised.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::TarFile;
+    ///
+    /// let mut data = TarFile::default();
+    /// let stats = data.append_dir_all("src".to_string()).unwrap();
+    /// assert_eq!(stats.entries(), data.list().len());
+    /// ```
+    pub fn append_dir_all(&mut self, dirname: String) -> Result<OperationStats, TarError> {
+        self.append_dir_all_with(dirname, &ArchiveOptions::default())
+    }
+
+    /// Append a directory tree in the same way as `append_dir_all`, using the provided `ArchiveOptions`.
+    /// Excluded paths are skipped, along with everything below excluded directories.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{ArchiveOptions, TarFile};
+    ///
+    /// let options = ArchiveOptions::default().exclude("*.tar".to_string());
+    /// let mut data = TarFile::default();
+    /// data.append_dir_all_with("test".to_string(), &options).unwrap();
+    /// ```
+    pub fn append_dir_all_with(
+        &mut self,
+        dirname: String,
+        options: &ArchiveOptions,
+    ) -> Result<OperationStats, TarError> {
+        trace::span!("append", path = %dirname);
+        let start = Instant::now();
+        let raised = self.warnings.len();
+        let mut stats = OperationStats::default();
+        self.walk(dirname, options, &mut stats)?;
+        stats.warnings = self.warnings[raised..].to_vec();
+        stats.finish(start);
+        Ok(stats)
+    }
+
+    /// Add the directory tree at `dirname` for `append_dir_all_with`, counting what was added and skipped
+    /// in `stats`.
+    fn walk(
+        &mut self,
+        dirname: String,
+        options: &ArchiveOptions,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let excludes = compile_excludes(options)?;
+        let root = match dirname.trim_end_matches('/') {
+            "" => "/".to_string(),
+            d => d.to_string(),
+        };
+
+        #[cfg(feature = "gitignore")]
+        if options.gitignore {
+            return self.append_dir_ignoring(root, options, excludes, stats);
+        }
+
+        let threads = options::thread_count(options.read_threads);
+        let mut batch = Vec::new();
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        let mut reads = match options.io_uring && threads == 1 {
+            true => uring::Ring::new().map(PendingReads::new),
+            false => None,
+        };
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        let defer = reads.is_some();
+        #[cfg(not(all(feature = "uring", target_os = "linux")))]
+        let defer = false;
+
+        /* Each path is queued with the directories above it, to spot links that lead back up the tree */
+        let mut pending = vec![(root, Vec::<(u64, u64)>::new())];
+
+        while let Some((path, mut ancestors)) = pending.pop() {
+            if is_excluded(&path, &excludes) {
+                stats.skip(path, SkipReason::Excluded);
+                continue;
+            }
+            if options.dereference {
+                let meta = fs::metadata(&path)?;
+                if meta.is_dir() {
+                    let key = (meta.dev(), meta.ino());
+                    if ancestors.contains(&key) {
+                        stats.skip(path.clone(), SkipReason::FilesystemLoop);
+                        options.report(&mut self.warnings, TarWarning::FilesystemLoop(path));
+                        continue;
+                    }
+                    ancestors.push(key);
+                }
+            }
+            if threads > 1 {
+                let meta = file_metadata(&path, options)?;
+                if meta.is_dir() {
+                    pending.extend(children(&path)?.map(|c| (c, ancestors.clone())));
+                }
+                let linked = options.hard_links && !meta.is_dir() && meta.nlink() > 1;
+                batch.push((path, linked));
+                if batch.len() >= threads * 16 {
+                    self.read_parallel(&mut batch, options, threads, stats)?;
+                }
+                continue;
+            }
+            let file = &self.file;
+            let links = |key| TarNode::link_target(file, key);
+            let node = match TarNode::read_file_to_tar(
+                path.clone(),
+                options,
+                &mut self.warnings,
+                &links,
+                defer,
+            )? {
+                Some(n) => n,
+                None => {
+                    stats.skip(path.clone(), unread_reason(&path, options));
+                    continue;
+                }
+            };
+
+            if node.file_type() == FileType::Dir as u8 {
+                pending.extend(children(&path)?.map(|c| (c, ancestors.clone())));
+            }
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            if let Some(reads) = &mut reads {
+                reads.push(self, node, options, stats)?;
+                continue;
+            }
+            self.push_walked(node, options, stats)?;
+        }
+
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if let Some(reads) = &mut reads {
+            reads.flush(self, options, stats)?;
+        }
+        self.read_parallel(&mut batch, options, threads, stats)
+    }
+
+    /// Add `node`, found by a directory walk, and count it in `stats`, or record it as skipped if an entry
+    /// of the same name was kept instead.
+    fn push_walked(
+        &mut self,
+        node: TarNode,
+        options: &ArchiveOptions,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let (name, file_type, len) = (node.name(), node.entry_type(), node.data_len());
+        trace::event!(debug, name = %name, ?file_type, size = len, "added entry");
+        match self.push_unique(node, options)? {
+            true => stats.count(file_type, len as u64),
+            false => stats.skip(name, SkipReason::Duplicate),
+        }
+        Ok(())
+    }
+
+    /// Read the entries for the paths in `batch` on `threads` worker threads, and add them in the order of
+    /// the batch. Paths marked as having several hard links are read on this thread as they are reached,
+    /// so they can refer back to an entry added before them.
+    fn read_parallel(
+        &mut self,
+        batch: &mut Vec<(String, bool)>,
+        options: &ArchiveOptions,
+        threads: usize,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let next = &AtomicUsize::new(0);
+        let paths = &batch[..];
+        let mut read = std::thread::scope(|s| {
+            let workers = (0..threads.min(paths.len()))
+                .map(|_| {
+                    s.spawn(move || {
+                        let mut out = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let path = match paths.get(i) {
+                                Some((_, true)) => continue,
+                                Some((path, false)) => path,
+                                None => return out,
+                            };
+                            let mut warnings = Vec::new();
+                            let node = TarNode::read_file_to_tar(
+                                path.clone(),
+                                options,
+                                &mut warnings,
+                                &|_| None,
+                                false,
+                            );
+                            out.push((i, node, warnings));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Vec<_>>()
+        });
+        read.sort_by_key(|(i, _, _)| *i);
+
+        let mut read = read.into_iter().peekable();
+        for (i, (path, _)) in batch.drain(..).enumerate() {
+            let node = match read.next_if(|(j, _, _)| *j == i) {
+                Some((_, node, warnings)) => {
+                    self.warnings.extend(warnings);
+                    node?
+                }
+                None => {
+                    let file = &self.file;
+                    let links = |key| TarNode::link_target(file, key);
+                    let path = path.clone();
+                    TarNode::read_file_to_tar(path, options, &mut self.warnings, &links, false)?
+                }
+            };
+            match node {
+                Some(node) => self.push_walked(node, options, stats)?,
+                None => stats.skip(path.clone(), unread_reason(&path, options)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Walk the directory tree at `root` with the ``ignore`` crate, skipping files ignored by ``.gitignore``
+    /// and ``.ignore`` files as well as the `excludes`. The walker drops those paths itself, so they aren't
+    /// recorded in `stats`.
+    #[cfg(feature = "gitignore")]
+    fn append_dir_ignoring(
+        &mut self,
+        root: String,
+        options: &ArchiveOptions,
+        excludes: Vec<glob::Pattern>,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let walker = ignore::WalkBuilder::new(&root)
+            .standard_filters(false)
+            .git_ignore(true)
+            .git_exclude(true)
+            .ignore(true)
+            .parents(true)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .follow_links(options.dereference)
+            .filter_entry(move |e| !is_excluded(&e.path().to_string_lossy(), &excludes))
+            .build();
+
+        for entry in walker {
+            let entry = match entry {
+                Err(e) => match loop_path(&e) {
+                    Some(path) => {
+                        stats.skip(path.clone(), SkipReason::FilesystemLoop);
+                        options.report(&mut self.warnings, TarWarning::FilesystemLoop(path));
+                        continue;
+                    }
+                    None => return Err(std::io::Error::other(e.to_string()).into()),
+                },
+                Ok(entry) => entry,
+            };
+            let path = entry.path().to_string_lossy().to_string();
+            let file = &self.file;
+            let links = |key| TarNode::link_target(file, key);
+            match TarNode::read_file_to_tar(
+                path.clone(),
+                options,
+                &mut self.warnings,
+                &links,
+                false,
+            )? {
+                Some(node) => self.push_walked(node, options, stats)?,
+                None => stats.skip(path.clone(), unread_reason(&path, options)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Entries found by a directory walk whose data is still to be read through io_uring, as described in
+/// `ArchiveOptions::io_uring`.
+#[cfg(all(feature = "uring", target_os = "linux"))]
+struct PendingReads {
+    ring: uring::Ring,
+    nodes: Vec<TarNode>,
+    bytes: usize,
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+impl PendingReads {
+    fn new(ring: uring::Ring) -> Self {
+        PendingReads {
+            ring,
+            nodes: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Queue `node` to be added to `archive` once its data has been read, reading the queue once it is
+    /// full. A file with several hard links is read straight away, so that later names find it in the
+    /// archive and refer back to it.
+    fn push(
+        &mut self,
+        archive: &mut TarFile,
+        node: TarNode,
+        options: &ArchiveOptions,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let linked = node.inode.is_some();
+        self.bytes += node.data_len();
+        self.nodes.push(node);
+        if linked || self.nodes.len() >= uring::BATCH_FILES || self.bytes >= uring::BATCH_BYTES {
+            self.flush(archive, options, stats)?;
+        }
+        Ok(())
+    }
+
+    /// Read the data of every queued file in one go, then add the entries to `archive` in the order they
+    /// were found. Files too big to batch are read on their own.
+    fn flush(
+        &mut self,
+        archive: &mut TarFile,
+        options: &ArchiveOptions,
+        stats: &mut OperationStats,
+    ) -> Result<(), TarError> {
+        let batched = |n: &TarNode| n.data_len() < uring::BATCH_BYTES;
+        let reads = self
+            .nodes
+            .iter()
+            .filter(|n| batched(n))
+            .filter_map(|n| {
+                let (file, offset) = n.lazy.as_ref()?.file_range()?;
+                Some((file, offset, n.data_len()))
+            })
+            .collect::<Vec<(&File, u64, usize)>>();
+        let mut data = self.ring.read_all(&reads)?.into_iter();
+
+        for mut node in self.nodes.drain(..) {
+            if node.lazy.is_some() {
+                node.data = match batched(&node) {
+                    true => data.next().unwrap_or_default(),
+                    false => node.data()?.into_owned(),
+                };
+                node.lazy = None;
+            }
+            archive.push_walked(node, options, stats)?;
+        }
+        self.bytes = 0;
+        Ok(())
+    }
+}
+
+/// Returns the directory that leads back to one of its ancestors, if `error` reports a file system loop.
+#[cfg(feature = "gitignore")]
+fn loop_path(error: &ignore::Error) -> Option<String> {
+    match error {
+        ignore::Error::Loop { child, .. } => Some(child.to_string_lossy().to_string()),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => loop_path(err),
+        _ => None,
+    }
+}
+
+/// Returns why reading `path` gave no entry: it is either a socket or a special file left out by the
+/// options.
+fn unread_reason(path: &str, options: &ArchiveOptions) -> SkipReason {
+    match file_metadata(path, options) {
+        Ok(meta) if meta.file_type().is_socket() => SkipReason::Socket,
+        _ => SkipReason::Special,
+    }
+}
+
+/// Returns the paths of the entries of the directory `path`, in reverse sorted order. The walk pops paths
+/// from the end of its stack, so this visits them in sorted order.
+fn children(path: &str) -> Result<impl Iterator<Item = String>, TarError> {
+    let mut children = fs::read_dir(path)?
+        .map(|e| Ok(format!("{}/{}", path, e?.file_name().to_string_lossy())))
+        .collect::<Result<Vec<String>, TarError>>()?;
+    children.sort_by(|a, b| b.cmp(a));
+    Ok(children.into_iter())
+}
+
+/// Compile the exclude patterns of `options`.
+pub(crate) fn compile_excludes(options: &ArchiveOptions) -> Result<Vec<glob::Pattern>, TarError> {
+    options
+        .excludes
+        .iter()
+        .map(|p| compile_pattern(p))
+        .collect()
+}
+
+/// Compile the glob `pattern`, failing with `TarError::Pattern` if it is malformed.
+pub(crate) fn compile_pattern(pattern: &str) -> Result<glob::Pattern, TarError> {
+    glob::Pattern::new(pattern).map_err(|e| TarError::Pattern(e.to_string()))
+}
+
+/// Returns `true` if `path`, or any trailing part of it starting at a path component, matches one of
+/// `excludes`. This lets ``target/**`` exclude a ``target`` directory at any depth, like GNU tar.
+pub(crate) fn is_excluded(path: &str, excludes: &[glob::Pattern]) -> bool {
+    let path = path.trim_start_matches("./");
+    let suffixes =
+        std::iter::once(path).chain(path.match_indices('/').map(|(i, _)| &path[i + 1..]));
+    suffixes
+        .filter(|s| !s.is_empty())
+        .any(|s| excludes.iter().any(|p| p.matches(s)))
+}
@@ -0,0 +1,266 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::error::{TarError, TarWarning};
+use crate::tar::*;
+
+/// Prefix of the PAX records holding extended attributes, as written by star, GNU tar and bsdtar.
+const XATTR_PREFIX: &str = "SCHILY.xattr.";
+/// The extended attribute holding the file capabilities of an executable, such as ``cap_net_raw`` on
+/// ``ping``.
+const CAPABILITY: &str = "security.capability";
+
+impl TarNode {
+    /// Returns the extended attributes stored in the ``SCHILY.xattr.*`` PAX records of the entry, as
+    /// `(name, value)` pairs in the order they appear.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minitar::tar::{PaxRecord, TarNode};
+    ///
+    /// let mut node = TarNode::from_data("notes.txt".to_string(), b"hello").unwrap();
+    /// node.push_pax(PaxRecord::new("SCHILY.xattr.user.origin", "web"));
+    /// assert_eq!(node.xattrs(), vec![("user.origin".to_string(), b"web".to_vec())]);
+    /// ```
+    pub fn xattrs(&self) -> Vec<(String, Vec<u8>)> {
+        self.pax
+            .iter()
+            .filter_map(|r| {
+                let name = r.key().strip_prefix(XATTR_PREFIX)?;
+                Some((name.to_string(), r.value().to_vec()))
+            })
+            .collect()
+    }
+
+    /// Set the stored extended attributes on the extracted file at `path`, or only its file capabilities if
+    /// `capabilities_only` is set. Attributes the filesystem or the process privileges don't allow, such as
+    /// ``trusted.*`` ones without root, are skipped with a `TarWarning::XattrSkipped`.
+    pub(crate) fn restore_xattrs(
+        &self,
+        path: &Path,
+        capabilities_only: bool,
+        warnings: &mut Vec<TarWarning>,
+    ) -> Result<(), TarError> {
+        let attrs = self.xattrs().into_iter();
+        for (name, value) in attrs.filter(|(n, _)| !capabilities_only || n == CAPABILITY) {
+            if let Err(e) = set_xattr(path, &name, &value) {
+                match e.raw_os_error() {
+                    Some(libc::EPERM) | Some(libc::EACCES) | Some(libc::ENOTSUP) => {
+                        warnings.push(TarWarning::XattrSkipped {
+                            name: self.name(),
+                            attr: name,
+                        })
+                    }
+                    _ => return Err(e.into()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns ``SCHILY.xattr.*`` records holding the extended attributes of the file at `filename`, following
+/// a symbolic link if `follow` is set.
+pub(crate) fn records(filename: &str, follow: bool) -> Result<Vec<PaxRecord>, TarError> {
+    let attrs = read_xattrs(Path::new(filename), follow)?;
+    Ok(attrs
+        .into_iter()
+        .map(|(name, value)| PaxRecord::new(&format!("{}{}", XATTR_PREFIX, name), value))
+        .collect())
+}
+
+/// Returns a ``SCHILY.xattr.security.capability`` record holding the file capabilities of the file at
+/// `filename`, if it has any, following a symbolic link if `follow` is set.
+pub(crate) fn capability_record(
+    filename: &str,
+    follow: bool,
+) -> Result<Option<PaxRecord>, TarError> {
+    let value = read_xattr(Path::new(filename), CAPABILITY, follow)?;
+    Ok(value.map(|v| PaxRecord::new(&format!("{}{}", XATTR_PREFIX, CAPABILITY), v)))
+}
+
+/// Returns `path` as a C string.
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Read the names and values of the extended attributes of `path`, sorted by name so that archives are
+/// reproducible. Filesystems without extended attributes have none.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_xattrs(path: &Path, follow: bool) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let path = c_path(path)?;
+
+    let names = match fill(|buf, len| sys::list(&path, buf, len, follow)) {
+        Ok(n) => n,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut names: Vec<&[u8]> = names.split(|&b| b == 0).filter(|n| !n.is_empty()).collect();
+    names.sort();
+
+    let mut attrs = Vec::new();
+    for name in names {
+        let c_name = CString::new(name)?;
+        let value = match fill(|buf, len| sys::get(&path, &c_name, buf, len, follow)) {
+            Ok(v) => v,
+            /* The attribute was removed since the names were listed */
+            Err(e) if e.raw_os_error() == Some(sys::ENOATTR) => continue,
+            Err(e) => return Err(e),
+        };
+        attrs.push((String::from_utf8_lossy(name).to_string(), value));
+    }
+    Ok(attrs)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_xattrs(_path: &Path, _follow: bool) -> io::Result<Vec<(String, Vec<u8>)>> {
+    Ok(Vec::new())
+}
+
+/// Read the value of the extended attribute `name` of `path`, or `None` if it isn't set.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_xattr(path: &Path, name: &str, follow: bool) -> io::Result<Option<Vec<u8>>> {
+    let path = c_path(path)?;
+    let name = CString::new(name)?;
+    match fill(|buf, len| sys::get(&path, &name, buf, len, follow)) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.raw_os_error() == Some(sys::ENOATTR) => Ok(None),
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_xattr(_path: &Path, _name: &str, _follow: bool) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+/// Set the extended attribute `name` of `path` to `value`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let path = c_path(path)?;
+    let name = CString::new(name)?;
+    match sys::set(&path, &name, value) {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOTSUP))
+}
+
+/// Call `read` with a buffer of the size it asks for when given an empty one, retrying if the data grew in
+/// between.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn fill<F: Fn(*mut libc::c_void, usize) -> isize>(read: F) -> io::Result<Vec<u8>> {
+    loop {
+        let size = read(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0u8; size as usize];
+        let len = read(buf.as_mut_ptr() as *mut libc::c_void, buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        if io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+            return Err(io::Error::last_os_error());
+        }
+    }
+}
+
+/* SAFETY: the paths and names are NUL terminated strings, and `fill` passes either a buffer of `len`
+ * bytes or a null one with a `len` of zero, which only asks for the size */
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) const ENOATTR: i32 = libc::ENODATA;
+
+    pub(super) fn list(path: &CStr, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        let buf = buf as *mut libc::c_char;
+        unsafe {
+            match follow {
+                true => libc::listxattr(path.as_ptr(), buf, len),
+                false => libc::llistxattr(path.as_ptr(), buf, len),
+            }
+        }
+    }
+
+    pub(super) fn get(
+        path: &CStr,
+        name: &CStr,
+        buf: *mut libc::c_void,
+        len: usize,
+        follow: bool,
+    ) -> isize {
+        unsafe {
+            match follow {
+                true => libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len),
+                false => libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, len),
+            }
+        }
+    }
+
+    pub(super) fn set(path: &CStr, name: &CStr, value: &[u8]) -> i32 {
+        unsafe {
+            libc::lsetxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+            )
+        }
+    }
+}
+
+/* SAFETY: as for Linux */
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) const ENOATTR: i32 = libc::ENOATTR;
+
+    fn flags(follow: bool) -> libc::c_int {
+        match follow {
+            true => 0,
+            false => libc::XATTR_NOFOLLOW,
+        }
+    }
+
+    pub(super) fn list(path: &CStr, buf: *mut libc::c_void, len: usize, follow: bool) -> isize {
+        let buf = buf as *mut libc::c_char;
+        unsafe { libc::listxattr(path.as_ptr(), buf, len, flags(follow)) }
+    }
+
+    pub(super) fn get(
+        path: &CStr,
+        name: &CStr,
+        buf: *mut libc::c_void,
+        len: usize,
+        follow: bool,
+    ) -> isize {
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, len, 0, flags(follow)) }
+    }
+
+    pub(super) fn set(path: &CStr, name: &CStr, value: &[u8]) -> i32 {
+        unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+                libc::XATTR_NOFOLLOW,
+            )
+        }
+    }
+}
@@ -1,4 +1,6 @@
 #[allow(unused_imports)]
+use crate::error::{TarError, TarWarning};
+#[allow(unused_imports)]
 use crate::tar::*;
 use std::fs::File;
 
@@ -11,7 +13,7 @@ fn open_tar_file() {
 fn new_and_write_tar_file() {
     let data = TarFile::new("test/1.txt".to_string()).unwrap();
 
-    let out = File::create("test/2.tar".to_string()).unwrap();
+    let out = File::create("test/2.tar").unwrap();
     data.write(&out).unwrap();
 }
 
@@ -20,7 +22,7 @@ fn new_and_append_tar_file() {
     let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
     data.append("test/1.txt".to_string()).unwrap();
 
-    let out = File::create("test/5.tar".to_string()).unwrap();
+    let out = File::create("test/5.tar").unwrap();
     data.write(&out).unwrap();
 }
 
@@ -28,7 +30,7 @@ fn new_and_append_tar_file() {
 fn open_and_write_tar_file() {
     let data = TarFile::open("test/1.tar".to_string()).unwrap();
 
-    let out = File::create("test/5.tar".to_string()).unwrap();
+    let out = File::create("test/5.tar").unwrap();
     data.write(&out).unwrap();
 }
 
@@ -37,7 +39,7 @@ fn append_remove_tar_file() {
     let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
     data.append("test/1.txt".to_string()).unwrap();
     data.remove("1.txt".to_string()).unwrap();
-    let out = File::create("test/6.tar".to_string()).unwrap();
+    let out = File::create("test/6.tar").unwrap();
     data.write(&out).unwrap();
 }
 
@@ -45,16 +47,3626 @@ fn append_remove_tar_file() {
 fn append_remove_remove_tar_file() {
     let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
     data.append("test/1.txt".to_string()).unwrap();
-    if data.remove("test/1.txt".to_string()).unwrap() {
-        if data.remove("test/1.txt".to_string()).unwrap() {
-            if data.remove("test/1.txt".to_string()).unwrap() {
-                panic!("This should never happen");
-            }
-        }
+    if data.remove("test/1.txt".to_string()).unwrap()
+        && data.remove("test/1.txt".to_string()).unwrap()
+        && data.remove("test/1.txt".to_string()).unwrap()
+    {
+        panic!("This should never happen");
     }
-    let out = File::create("test/99.tar".to_string()).unwrap();
-    if data.write(&out).unwrap() != 0 {
+    let out = File::create("test/99.tar").unwrap();
+    if data.write(&out).unwrap().total_bytes() != 0 {
         panic!("Should be 0 bytes written");
     }
-    std::fs::remove_file("test/99.tar".to_string()).unwrap();
+    std::fs::remove_file("test/99.tar").unwrap();
+}
+
+#[test]
+fn open_tar_file_reads_data() {
+    let data = TarFile::open("test/1.tar".to_string()).unwrap();
+
+    let out = File::create("test/8.tar").unwrap();
+    data.write(&out).unwrap();
+    let out = std::fs::read("test/8.tar").unwrap();
+    assert_eq!(&out[512..533], &std::fs::read("test/1.txt").unwrap()[..]);
+    std::fs::remove_file("test/8.tar").unwrap();
+}
+
+#[test]
+fn journal_round_trip() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.record_delete("gone.txt".to_string()).unwrap();
+    data.record_rename("a.txt".to_string(), "b.txt".to_string())
+        .unwrap();
+
+    let out = File::create("test/7.tar").unwrap();
+    data.write(&out).unwrap();
+
+    let journal = TarFile::open("test/7.tar".to_string()).unwrap().journal();
+    assert_eq!(journal.len(), 2);
+    assert_eq!(journal[0].name, "gone.txt");
+    assert_eq!(journal[0].op, JournalOp::Delete);
+    assert_eq!(journal[1].op, JournalOp::Rename("b.txt".to_string()));
+    std::fs::remove_file("test/7.tar").unwrap();
+}
+
+#[test]
+fn open_concatenated_tar_file() {
+    let mut cat = std::fs::read("test/1.tar").unwrap();
+    cat.extend(std::fs::read("test/1.tar").unwrap());
+    std::fs::write("test/9.tar", &cat).unwrap();
+
+    let mut data = TarFile::open("test/9.tar".to_string()).unwrap();
+    assert!(data.remove("1.txt".to_string()).unwrap());
+    assert!(!data.remove("1.txt".to_string()).unwrap());
+
+    let options = ReadOptions::default().ignore_zeros(true);
+    let mut data = TarFile::open_with("test/9.tar".to_string(), &options).unwrap();
+    assert!(data.remove("1.txt".to_string()).unwrap());
+    assert!(data.remove("1.txt".to_string()).unwrap());
+    std::fs::remove_file("test/9.tar").unwrap();
+}
+
+#[test]
+fn open_truncated_tar_file() {
+    let tar = std::fs::read("test/1.tar").unwrap();
+
+    /* Cut in the middle of the file data */
+    std::fs::write("test/10.tar", &tar[..700]).unwrap();
+    assert!(matches!(
+        TarFile::open("test/10.tar".to_string()),
+        Err(TarError::Truncated)
+    ));
+
+    /* Cut after the file data, before the trailer */
+    std::fs::write("test/10.tar", &tar[..1024]).unwrap();
+    assert!(matches!(
+        TarFile::open("test/10.tar".to_string()),
+        Err(TarError::Truncated)
+    ));
+
+    /* A single zero block at the end is tolerated */
+    std::fs::write("test/10.tar", &tar[..1536]).unwrap();
+    TarFile::open("test/10.tar".to_string()).unwrap();
+    std::fs::remove_file("test/10.tar").unwrap();
+}
+
+#[test]
+fn open_lone_zero_block() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/11.tar").unwrap();
+    data.write(&out).unwrap();
+
+    /* Insert a lone zero block between the two entries */
+    let mut tar = std::fs::read("test/11.tar").unwrap();
+    tar.splice(1024..1024, [0u8; 512]);
+    std::fs::write("test/11.tar", &tar).unwrap();
+
+    let mut data = TarFile::open("test/11.tar".to_string()).unwrap();
+    assert!(data.remove("test/1.txt".to_string()).unwrap());
+    assert!(data.remove("test/1.txt".to_string()).unwrap());
+    std::fs::remove_file("test/11.tar").unwrap();
+}
+
+#[test]
+fn write_trailer_and_record_padding() {
+    let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/12.tar").unwrap();
+    assert_eq!(data.write(&out).unwrap().total_bytes(), 10240);
+
+    let tar = std::fs::read("test/12.tar").unwrap();
+    assert_eq!(tar.len(), 10240);
+    assert!(tar[1024..].iter().all(|&x| x == 0));
+    std::fs::remove_file("test/12.tar").unwrap();
+}
+
+#[test]
+fn extract_tar_file() {
+    let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    data.extract("test/extract".to_string()).unwrap();
+    assert_eq!(
+        std::fs::read("test/extract/1.txt").unwrap(),
+        std::fs::read("test/1.txt").unwrap()
+    );
+    std::fs::remove_dir_all("test/extract").unwrap();
+}
+
+#[test]
+fn restore_point_in_time_chain() {
+    let full = TarFile::new("test/1.txt".to_string()).unwrap();
+    let mut delta = TarFile::default();
+    delta
+        .record_rename("test/1.txt".to_string(), "test/2.txt".to_string())
+        .unwrap();
+    let mut delete = TarFile::default();
+    delete.record_delete("test/2.txt".to_string()).unwrap();
+    let chain = [full, delta, delete];
+
+    restore_point_in_time(&chain, 0, "test/restore".to_string()).unwrap();
+    assert!(!std::path::Path::new("test/restore/test").exists());
+
+    let mtime = std::fs::metadata("test/1.txt")
+        .unwrap()
+        .modified()
+        .unwrap()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    restore_point_in_time(&chain[..1], mtime, "test/restore".to_string()).unwrap();
+    assert!(std::path::Path::new("test/restore/test/1.txt").exists());
+    std::fs::remove_dir_all("test/restore").unwrap();
+
+    restore_point_in_time(&chain[..2], u64::MAX, "test/restore".to_string()).unwrap();
+    assert!(!std::path::Path::new("test/restore/test/1.txt").exists());
+    assert!(std::path::Path::new("test/restore/test/2.txt").exists());
+    std::fs::remove_dir_all("test/restore").unwrap();
+
+    restore_point_in_time(&chain, u64::MAX, "test/restore".to_string()).unwrap();
+    assert!(!std::path::Path::new("test/restore/test/2.txt").exists());
+    std::fs::remove_dir_all("test/restore").unwrap();
+
+    /* A name too long for the header field is kept rather than dropping the file */
+    let long = format!("test/{}.txt", "l".repeat(120));
+    let mut rename = TarFile::default();
+    rename
+        .record_rename("test/1.txt".to_string(), long.clone())
+        .unwrap();
+    let state = TarFile::point_in_time(&[chain[0].clone(), rename], u64::MAX).unwrap();
+    assert_eq!(state.nodes().len(), 1);
+    assert_eq!(state.nodes()[0].name(), long);
+
+    /* Names given with a trailing slash match entries stored without one, and the reverse */
+    let dirs: TarFile = ["dir", "dir/a.txt", "other/"]
+        .iter()
+        .map(|n| TarNode::from_data(n.to_string(), b"").unwrap())
+        .collect();
+    let mut moves = TarFile::default();
+    moves
+        .record_rename("dir/".to_string(), "new/".to_string())
+        .unwrap();
+    moves.record_delete("other".to_string()).unwrap();
+    let state = TarFile::point_in_time(&[dirs.clone(), moves], u64::MAX).unwrap();
+    let names: Vec<String> = state.nodes().iter().map(|n| n.name()).collect();
+    assert_eq!(names, vec!["new", "new/a.txt"]);
+
+    let mut moves = TarFile::default();
+    moves.record_delete("dir/".to_string()).unwrap();
+    let state = TarFile::point_in_time(&[dirs, moves], u64::MAX).unwrap();
+    let names: Vec<String> = state.nodes().iter().map(|n| n.name()).collect();
+    assert_eq!(names, vec!["other/"]);
+}
+
+#[test]
+fn write_blocking_factor() {
+    let options = WriteOptions::default().blocking_factor(1);
+    let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/13.tar").unwrap();
+    assert_eq!(data.write_with(&out, &options).unwrap().total_bytes(), 2048);
+
+    let options = WriteOptions::default().blocking_factor(3);
+    let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/13.tar").unwrap();
+    assert_eq!(data.write_with(&out, &options).unwrap().total_bytes(), 3072);
+    assert_eq!(std::fs::read("test/13.tar").unwrap().len(), 3072);
+    std::fs::remove_file("test/13.tar").unwrap();
+}
+
+#[test]
+fn write_sparse_entries() {
+    let map = SparseMap {
+        real_size: 1 << 20,
+        segments: vec![(0, 4), ((1 << 20) - 4, 4)],
+    };
+
+    let node = TarNode::from_sparse("disk.img".to_string(), map, b"headtail").unwrap();
+    let mut out = Vec::new();
+    node.write(&mut out).unwrap();
+    let text = String::from_utf8_lossy(&out);
+    assert!(text.contains("GNU.sparse.realsize=1048576"));
+    assert!(text.contains("GNUSparseFile.0/disk.img"));
+    assert_eq!(&out[1536..1536 + 18], b"2\n0\n512\n1048572\n4\n");
+    assert_eq!(&out[2048..2052], b"head");
+    assert_eq!(&out[2560..2564], b"tail");
+
+    let segments = (0..30).map(|i| (i * 4096, 1)).collect::<Vec<_>>();
+    let map = SparseMap {
+        real_size: 30 * 4096,
+        segments,
+    };
+    let node = TarNode::from_sparse("many.img".to_string(), map, &[1u8; 30]).unwrap();
+    let options = WriteOptions::default().sparse_format(SparseFormat::OldGnu);
+    let mut out = Vec::new();
+    assert_eq!(node.write_with(&mut out, &options).unwrap(), 33 * 512);
+    assert_eq!(out[156], b'S');
+    assert_eq!(out[482], 1);
+    assert_eq!(out[512 + 504], 1);
+    assert_eq!(out[1024 + 504], 0);
+    assert_eq!(out[1536], 1);
+    assert_eq!(out[2048], 1);
+}
+
+#[test]
+fn sparse_segments_within_a_block() {
+    /* Growing the first segment to a whole block would run past the second, so they are merged */
+    let map = SparseMap {
+        real_size: 1 << 20,
+        segments: vec![(0, 4), (100, 4)],
+    };
+    let node = TarNode::from_sparse("a".to_string(), map, b"aaaabbbb").unwrap();
+    assert_eq!(
+        node.sparse_map().unwrap().unwrap().segments,
+        vec![(0, 104), (1 << 20, 0)]
+    );
+
+    let mut out = Vec::new();
+    node.write(&mut out).unwrap();
+    out.extend_from_slice(&[0u8; 1024]);
+    let read = TarFile::open_stream(out.as_slice(), &ReadOptions::default()).unwrap();
+    let files = read.extract_to_memory().unwrap();
+    let contents = &files[std::path::Path::new("a")].contents;
+    assert_eq!(contents.len(), 1 << 20);
+    assert_eq!(&contents[..4], b"aaaa");
+    assert_eq!(&contents[100..104], b"bbbb");
+    assert!(contents[4..100]
+        .iter()
+        .chain(&contents[104..])
+        .all(|&b| b == 0));
+
+    /* Offsets that overflow when added to their length are rejected rather than wrapping */
+    let map = SparseMap {
+        real_size: 1 << 20,
+        segments: vec![(u64::MAX, 1)],
+    };
+    assert!(matches!(
+        TarNode::from_sparse("b".to_string(), map, b"b"),
+        Err(TarError::InvalidSparseMap)
+    ));
+}
+
+#[test]
+fn old_gnu_sparse_round_trip() {
+    let map = SparseMap {
+        real_size: 30 * 4096,
+        segments: (0..30).map(|i| (i * 4096, 1)).collect(),
+    };
+    let data: TarFile = vec![
+        TarNode::from_sparse("many.img".to_string(), map, &[1u8; 30]).unwrap(),
+        TarNode::from_data("after.txt".to_string(), b"after").unwrap(),
+    ]
+    .into_iter()
+    .collect();
+    let map = data.nodes()[0].sparse_map().unwrap();
+    let options = WriteOptions::default().sparse_format(SparseFormat::OldGnu);
+    data.write_with(&File::create("test/51.tar").unwrap(), &options)
+        .unwrap();
+
+    /* The two extension blocks holding the map come before the data, and must be skipped along with it */
+    for options in [
+        ReadOptions::default(),
+        ReadOptions::default().lazy_data(true),
+    ] {
+        let read = TarFile::open_with("test/51.tar".to_string(), &options).unwrap();
+        assert_eq!(read.nodes()[0].sparse_map().unwrap(), map);
+        assert_eq!(read.nodes()[1].name(), "after.txt");
+        let mut contents = Vec::new();
+        read.extract_entry("after.txt".to_string(), &mut contents)
+            .unwrap();
+        assert_eq!(contents, b"after");
+    }
+    let names = TarFile::list_from(File::open("test/51.tar").unwrap()).unwrap();
+    assert_eq!(names, vec!["many.img", "after.txt"]);
+
+    std::fs::remove_file("test/51.tar").unwrap();
+}
+
+#[test]
+fn list_from_seeks_past_data() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.record_delete("gone.txt".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/14.tar").unwrap();
+    data.write(&out).unwrap();
+
+    /* Journal records are not entries, so they are neither listed nor counted in the positions */
+    let names = TarFile::list_from(File::open("test/14.tar").unwrap()).unwrap();
+    assert_eq!(names, vec!["test/1.txt", "test/1.txt"]);
+    let index = NameIndex::open("test/14.tar".to_string()).unwrap();
+    assert!(index.find_prefix("gone").is_empty());
+    assert_eq!(index.find_prefix("test/")[1].1, 1);
+    std::fs::remove_file("test/14.tar").unwrap();
+}
+
+#[test]
+fn hostile_extension_headers() {
+    let mut node = TarNode::from_data("a.txt".to_string(), b"x").unwrap();
+    node.push_pax(PaxRecord::new("comment", "x"));
+    let mut entry = Vec::new();
+    node.write(&mut entry).unwrap();
+    let (pax, rest) = entry.split_at(1024);
+
+    /* A long chain of extension headers is read without recursing once per header */
+    let mut archive = pax.repeat(1_000);
+    archive.extend_from_slice(rest);
+    archive.extend_from_slice(&[0u8; 1024]);
+    let data = TarFile::open_stream(archive.as_slice(), &ReadOptions::default()).unwrap();
+    assert_eq!(data.nodes()[0].name(), "a.txt");
+    assert_eq!(data.nodes()[0].pax().len(), 1_000);
+
+    /* An extension header claiming a huge size fails once the input runs out, without allocating it */
+    let mut header = pax[..512].to_vec();
+    header[124..136].copy_from_slice(b"77777777777\0");
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    header.extend_from_slice(&[0u8; 1024]);
+    assert!(matches!(
+        TarFile::list_from(std::io::Cursor::new(header)),
+        Err(TarError::Truncated)
+    ));
+}
+
+#[test]
+fn long_name_policies() {
+    let long = format!("test/{}.txt", "a".repeat(120));
+    std::fs::write(&long, "long").unwrap();
+
+    assert!(matches!(
+        TarFile::new(long.clone()),
+        Err(TarError::NameTooLong)
+    ));
+
+    let options = ArchiveOptions::default().long_names(LongNamePolicy::Truncate);
+    let data = TarFile::new_with(long.clone(), &options).unwrap();
+    assert_eq!(data.warnings().len(), 1);
+    let out = File::create("test/15.tar").unwrap();
+    data.write(&out).unwrap();
+    let names = TarFile::list_from(File::open("test/15.tar").unwrap()).unwrap();
+    assert_eq!(names[0], long[..100]);
+
+    let options = ArchiveOptions::default().long_names(LongNamePolicy::HashSuffix);
+    let data = TarFile::new_with(long.clone(), &options).unwrap();
+    let out = File::create("test/15.tar").unwrap();
+    data.write(&out).unwrap();
+    let names = TarFile::list_from(File::open("test/15.tar").unwrap()).unwrap();
+    assert_eq!(names[0].len(), 100);
+    assert!(names[0].ends_with(".txt"));
+    assert!(names[0].contains('~'));
+
+    let options = ArchiveOptions::default().long_names(LongNamePolicy::Pax);
+    let data = TarFile::new_with(long.clone(), &options).unwrap();
+    assert!(data.warnings().is_empty());
+    let out = File::create("test/15.tar").unwrap();
+    data.write(&out).unwrap();
+    let names = TarFile::list_from(File::open("test/15.tar").unwrap()).unwrap();
+    assert_eq!(names[0], long);
+
+    std::fs::remove_file("test/15.tar").unwrap();
+    std::fs::remove_file(&long).unwrap();
+}
+
+#[test]
+fn special_file_policies() {
+    let mut data = TarFile::default();
+    data.append("/dev/null".to_string()).unwrap();
+    assert!(matches!(
+        data.warnings(),
+        [TarWarning::NameSanitized { .. }]
+    ));
+
+    let options = ArchiveOptions::default().specials(SpecialPolicy::Skip);
+    let mut data = TarFile::default();
+    data.append_with("/dev/null".to_string(), &options).unwrap();
+    data.append_with("test/1.txt".to_string(), &options)
+        .unwrap();
+    assert_eq!(
+        data.warnings(),
+        &[TarWarning::SpecialSkipped("/dev/null".to_string())]
+    );
+
+    let options = ArchiveOptions::default().specials(SpecialPolicy::Error);
+    assert!(matches!(
+        TarFile::new_with("/dev/null".to_string(), &options),
+        Err(TarError::SpecialFile(_))
+    ));
+}
+
+#[test]
+fn device_numbers() {
+    use deku::prelude::*;
+    use std::os::unix::fs::MetadataExt;
+
+    let octal = |field: &[u8]| {
+        let digits = std::str::from_utf8(field).unwrap().trim_end_matches('\0');
+        u32::from_str_radix(digits, 8).unwrap()
+    };
+    for device in ["/dev/null", "/dev/zero"] {
+        let data = TarFile::new(device.to_string()).unwrap();
+        let block = data.nodes()[0].header().to_bytes().unwrap();
+        let rdev = std::fs::metadata(device).unwrap().rdev();
+        assert_eq!(block[156], FileType::Char as u8);
+        assert_eq!(octal(&block[329..337]), libc::major(rdev));
+        assert_eq!(octal(&block[337..345]), libc::minor(rdev));
+    }
+}
+
+#[test]
+fn index_records_entry_offsets() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.record_delete("gone.txt".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/16.tar").unwrap();
+    data.write(&out).unwrap();
+
+    let read = TarFile::open("test/16.tar".to_string()).unwrap();
+    let index = read.index();
+    assert_eq!(index.len(), 3);
+    assert_eq!(read.entry_count(), 3);
+    assert_eq!(read.entries_page(1, 1), vec![index[1].clone()]);
+    assert_eq!(read.entries_page(2, 10).len(), 1);
+    assert!(read.entries_page(3, 10).is_empty());
+    assert_eq!((index[0].header_offset, index[0].data_offset), (0, 512));
+    assert_eq!(index[0].size, 21);
+    assert_eq!(index[1].name, "gone.txt");
+    assert_eq!((index[1].header_offset, index[1].data_offset), (1024, 2560));
+    assert_eq!((index[2].header_offset, index[2].data_offset), (2560, 3072));
+
+    let tar = std::fs::read("test/16.tar").unwrap();
+    let start = index[2].data_offset as usize;
+    assert_eq!(
+        &tar[start..start + index[2].size as usize],
+        &std::fs::read("test/1.txt").unwrap()[..]
+    );
+
+    /* Nodes moved into another archive lose the offsets of the one they came from */
+    let copied: TarFile = read.nodes().iter().cloned().collect();
+    assert!(copied.index().is_empty());
+    let mut extended = TarFile::default();
+    extended.extend(read.nodes().iter().cloned());
+    assert!(extended.index().is_empty());
+    std::fs::remove_file("test/16.tar").unwrap();
+}
+
+#[test]
+fn list_entry_metadata() {
+    let _ = std::fs::remove_file("test/19.link");
+    std::os::unix::fs::symlink("1.txt", "test/19.link").unwrap();
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("test/19.link".to_string()).unwrap();
+    data.record_delete("gone.txt".to_string()).unwrap();
+
+    let entries = data.list();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, "test/1.txt");
+    assert_eq!(entries[0].size, 21);
+    assert_eq!(entries[0].file_type, FileType::Normal);
+    assert_eq!(entries[0].link_target, None);
+    assert_eq!(entries[1].file_type, FileType::Sym);
+    assert_eq!(entries[1].link_target.as_deref(), Some("1.txt"));
+
+    let read = TarFile::open("test/1.tar".to_string()).unwrap().list();
+    assert_eq!(read[0].name, "1.txt");
+    assert_eq!(read[0].mode, 0o644);
+    assert_eq!(
+        read[0].to_string(),
+        "-rw-r--r-- geno/users       21 2021-02-20 19:37 1.txt"
+    );
+    assert!(entries[1].to_string().ends_with(" test/19.link -> 1.txt"));
+    std::fs::remove_file("test/19.link").unwrap();
+}
+
+#[test]
+fn name_index_search() {
+    let index = NameIndex::new(vec![
+        "src/tar.rs".to_string(),
+        "src/lib.rs".to_string(),
+        "README.md".to_string(),
+        "src/tar/list.rs".to_string(),
+        "srcs.txt".to_string(),
+    ]);
+    assert_eq!(index.len(), 5);
+
+    let found: Vec<&str> = index
+        .find_prefix("src/")
+        .iter()
+        .map(|n| n.0.as_str())
+        .collect();
+    assert_eq!(found, vec!["src/lib.rs", "src/tar.rs", "src/tar/list.rs"]);
+    assert_eq!(
+        index.find_prefix("src/tar.rs"),
+        &[("src/tar.rs".to_string(), 0)]
+    );
+    assert!(index.find_prefix("test").is_empty());
+
+    let fuzzy = index.find_fuzzy("TarLs");
+    assert_eq!(fuzzy, vec![("src/tar/list.rs".to_string(), 3)]);
+    let fuzzy: Vec<String> = index.find_fuzzy("rs").into_iter().map(|n| n.0).collect();
+    assert_eq!(
+        fuzzy,
+        vec!["src/lib.rs", "src/tar.rs", "src/tar/list.rs", "srcs.txt"]
+    );
+}
+
+#[test]
+fn transaction_commit_and_rollback() {
+    std::fs::copy("test/1.tar", "test/20.tar").unwrap();
+    let mut tx = TarFile::transaction("test/20.tar".to_string());
+    tx.rename("1.txt".to_string(), "one.txt".to_string())
+        .unwrap();
+    tx.append("test/1.txt".to_string()).unwrap();
+    assert!(matches!(
+        tx.rename("one.txt".to_string(), "x".repeat(101)),
+        Err(TarError::NameTooLong)
+    ));
+    tx.commit().unwrap();
+
+    let names = TarFile::list_from(File::open("test/20.tar").unwrap()).unwrap();
+    assert_eq!(names, vec!["one.txt", "test/1.txt"]);
+    assert!(!std::path::Path::new("test/20.tar.minitar-tmp").exists());
+
+    let mut tx = TarFile::transaction("test/20.tar".to_string());
+    tx.remove("one.txt".to_string());
+    tx.rollback();
+    let mut tx = TarFile::transaction("test/20.tar".to_string());
+    tx.remove("test/1.txt".to_string());
+    tx.commit().unwrap();
+    let names = TarFile::list_from(File::open("test/20.tar").unwrap()).unwrap();
+    assert_eq!(names, vec!["one.txt"]);
+
+    assert!(!TarFile::remove_from_disk("test/20.tar".to_string(), "two.txt".to_string()).unwrap());
+    assert!(TarFile::remove_from_disk("test/20.tar".to_string(), "one.txt".to_string()).unwrap());
+    assert!(TarFile::list_from(File::open("test/20.tar").unwrap())
+        .unwrap()
+        .is_empty());
+    assert_eq!(std::fs::metadata("test/20.tar").unwrap().len(), 0);
+
+    let tx = TarFile::transaction("test/missing.tar".to_string());
+    assert!(tx.commit().is_err());
+    assert!(!std::path::Path::new("test/missing.tar.minitar-tmp").exists());
+    std::fs::remove_file("test/20.tar").unwrap();
+}
+
+#[test]
+fn compare_with_filesystem() {
+    let _ = std::fs::remove_dir_all("test/compare");
+    std::fs::create_dir_all("test/compare").unwrap();
+    std::fs::write("test/compare/a.txt", b"hello").unwrap();
+    std::fs::write("test/compare/b.txt", b"hello").unwrap();
+    std::fs::write("test/compare/c.txt", b"hello").unwrap();
+    let mut data = TarFile::new("test/compare/a.txt".to_string()).unwrap();
+    data.append("test/compare/b.txt".to_string()).unwrap();
+    data.append("test/compare/c.txt".to_string()).unwrap();
+    assert!(data.compare(".".to_string()).unwrap().is_empty());
+
+    /* Keep the original modification times so only the intended differences show up */
+    for (name, contents) in [("a", "HELLO"), ("b", "hello world")] {
+        let path = format!("test/compare/{}.txt", name);
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, contents).unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(mtime).unwrap();
+    }
+    std::fs::remove_file("test/compare/c.txt").unwrap();
+
+    let options = CompareOptions::default().contents(true);
+    let diff = data.compare_with(".".to_string(), &options).unwrap();
+    assert_eq!(diff.len(), 3, "{:?}", diff);
+    assert_eq!(
+        diff[0],
+        Difference::Contents("test/compare/a.txt".to_string())
+    );
+    assert_eq!(
+        diff[1],
+        Difference::Size {
+            name: "test/compare/b.txt".to_string(),
+            archive: 5,
+            disk: 11
+        }
+    );
+    assert_eq!(
+        diff[2],
+        Difference::Missing("test/compare/c.txt".to_string())
+    );
+    assert_eq!(data.compare(".".to_string()).unwrap().len(), 2);
+    std::fs::remove_dir_all("test/compare").unwrap();
+}
+
+#[test]
+fn patch_headers_in_place() {
+    std::fs::copy("test/1.tar", "test/21.tar").unwrap();
+    let patch = HeaderPatch::default()
+        .name("2.txt".to_string())
+        .mode(0o600)
+        .uid(42)
+        .mtime(1000);
+    let patched =
+        TarFile::patch_in_place("test/21.tar".to_string(), "1.txt".to_string(), &patch).unwrap();
+    assert_eq!(patched, 1);
+
+    let entries = TarFile::open("test/21.tar".to_string()).unwrap().list();
+    assert_eq!(entries[0].name, "2.txt");
+    assert_eq!(entries[0].mode, 0o600);
+    assert_eq!(entries[0].uid, 42);
+    assert_eq!(entries[0].mtime, 1000);
+    assert_eq!(std::fs::metadata("test/21.tar").unwrap().len(), 10240);
+
+    let rename = HeaderPatch::default().name("22.txt".to_string());
+    assert!(matches!(
+        TarFile::patch_in_place("test/21.tar".to_string(), "2.txt".to_string(), &rename),
+        Err(TarError::NotPatchable(_))
+    ));
+    assert!(matches!(
+        TarFile::patch_in_place("test/21.tar".to_string(), "1.txt".to_string(), &patch),
+        Err(TarError::NotFound(_))
+    ));
+    std::fs::remove_file("test/21.tar").unwrap();
+}
+
+#[test]
+fn diff_two_archives() {
+    let _ = std::fs::remove_dir_all("test/diff");
+    std::fs::create_dir_all("test/diff").unwrap();
+    for name in ["same", "edited", "grown", "gone"] {
+        let path = format!("test/diff/{}", name);
+        std::fs::write(&path, b"hello").unwrap();
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o644))
+            .unwrap();
+    }
+    let mut old = TarFile::default();
+    for name in ["same", "edited", "grown", "gone"] {
+        old.append(format!("test/diff/{}", name)).unwrap();
+    }
+
+    let mut new = TarFile::new("test/diff/same".to_string()).unwrap();
+    let edited = "test/diff/edited";
+    let mtime = std::fs::metadata(edited).unwrap().modified().unwrap();
+    std::fs::write(edited, b"HELLO").unwrap();
+    File::options()
+        .write(true)
+        .open(edited)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    new.append(edited.to_string()).unwrap();
+    std::fs::write("test/diff/grown", b"hello world").unwrap();
+    std::fs::set_permissions(
+        "test/diff/grown",
+        std::os::unix::fs::PermissionsExt::from_mode(0o600),
+    )
+    .unwrap();
+    new.append("test/diff/grown".to_string()).unwrap();
+    std::fs::write("test/diff/added", b"new").unwrap();
+    new.append("test/diff/added".to_string()).unwrap();
+
+    let changes = old.diff(&new).unwrap();
+    assert_eq!(
+        changes[0],
+        ArchiveChange::Contents("test/diff/edited".to_string())
+    );
+    assert_eq!(
+        changes[1],
+        ArchiveChange::Size {
+            name: "test/diff/grown".to_string(),
+            old: 5,
+            new: 11
+        }
+    );
+    assert!(changes.contains(&ArchiveChange::Mode {
+        name: "test/diff/grown".to_string(),
+        old: 0o644,
+        new: 0o600
+    }));
+    assert_eq!(
+        changes[changes.len() - 2..],
+        [
+            ArchiveChange::Removed("test/diff/gone".to_string()),
+            ArchiveChange::Added("test/diff/added".to_string())
+        ]
+    );
+    assert!(new.diff(&new).unwrap().is_empty());
+    std::fs::remove_dir_all("test/diff").unwrap();
+}
+
+#[test]
+fn merge_duplicate_policies() {
+    let base = TarFile::open("test/1.tar".to_string()).unwrap();
+    let mut other = TarFile::new("test/1.txt".to_string()).unwrap();
+    other.append("test/1.txt".to_string()).unwrap();
+    let mut dup = TarFile::open("test/1.tar".to_string()).unwrap();
+    dup.append("test/1.txt".to_string()).unwrap();
+
+    let mut data = base.clone();
+    data.merge(other.clone(), DuplicatePolicy::Error).unwrap();
+    assert_eq!(data.list().len(), 3);
+
+    let mut data = base.clone();
+    assert!(matches!(
+        data.merge(dup.clone(), DuplicatePolicy::Error),
+        Err(TarError::DuplicateEntry(n)) if n == "1.txt"
+    ));
+    assert_eq!(data.list().len(), 1);
+
+    let mut data = base.clone();
+    data.merge(dup.clone(), DuplicatePolicy::KeepFirst).unwrap();
+    let entries = data.list();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].uname, "geno");
+
+    let mut data = base;
+    data.merge(dup, DuplicatePolicy::KeepLast).unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["1.txt", "test/1.txt"]);
+}
+
+#[test]
+fn owner_names() {
+    use std::os::unix::fs::MetadataExt;
+
+    /* Find the name with the given id in a colon separated database */
+    let lookup = |path: &str, id: u32| {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| l.split(':').map(str::to_string).collect::<Vec<String>>())
+            .find(|f| f[2] == id.to_string())
+            .map(|f| f[0].clone())
+            .unwrap()
+    };
+    let meta = std::fs::metadata("test/1.txt").unwrap();
+
+    let data = TarFile::new("test/1.txt".to_string()).unwrap();
+    assert_eq!(data.list()[0].uname, lookup("/etc/passwd", meta.uid()));
+    assert_eq!(data.list()[0].gname, lookup("/etc/group", meta.gid()));
+
+    let long = "u".repeat(40);
+    let options = ArchiveOptions::default()
+        .uname(long.clone())
+        .gname("builders".to_string());
+    let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    let out = File::create("test/22.tar").unwrap();
+    data.write(&out).unwrap();
+
+    let entries = TarFile::open("test/22.tar".to_string()).unwrap().list();
+    assert_eq!(entries[0].uname, long);
+    assert_eq!(entries[0].gname, "builders");
+    std::fs::remove_file("test/22.tar").unwrap();
+}
+
+#[test]
+fn retain_entries() {
+    let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    data.append("test".to_string()).unwrap();
+    data.record_delete("old.txt".to_string()).unwrap();
+
+    data.retain(|e| !e.name.starts_with("test"));
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["1.txt"]);
+    assert_eq!(data.journal().len(), 1);
+}
+
+#[test]
+fn remove_all_matching() {
+    let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    data.append("test/1.tar".to_string()).unwrap();
+    data.append("Cargo.toml".to_string()).unwrap();
+
+    assert!(matches!(
+        data.remove_all("[".to_string()),
+        Err(TarError::Pattern(_))
+    ));
+    assert_eq!(data.remove_all("test/**".to_string()).unwrap(), 3);
+    assert_eq!(data.remove_all("*.tar".to_string()).unwrap(), 0);
+    assert_eq!(data.remove_every("1.txt".to_string()), 1);
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["Cargo.toml"]);
+}
+
+#[test]
+fn streaming_builder() {
+    use std::io::Write;
+
+    let mut builder = TarBuilder::new(Vec::new());
+    builder.append_file("test/1.txt".to_string()).unwrap();
+    let mut entry = builder.start_entry("hello.txt".to_string(), 5).unwrap();
+    entry.write_all(b"hel").unwrap();
+    assert!(entry.write_all(b"lo!").is_err());
+    entry.write_all(b"lo").unwrap();
+    let archive = entry.finish().unwrap().finish().unwrap();
+    assert_eq!(archive.len(), 10240);
+
+    let data = TarFile::open_stream(&archive[..], &ReadOptions::default()).unwrap();
+    let mut out = Vec::new();
+    data.extract_entry("hello.txt".to_string(), &mut out)
+        .unwrap();
+    assert_eq!(out, b"hello");
+    assert_eq!(data.list()[0].name, "test/1.txt");
+
+    let builder = TarBuilder::new(Vec::new());
+    let entry = builder.start_entry("short.txt".to_string(), 5).unwrap();
+    assert!(matches!(entry.finish(), Err(TarError::IncompleteEntry(_))));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "dropped without calling finish")]
+fn unfinished_builder() {
+    let mut builder = TarBuilder::new(Vec::new());
+    builder.append_file("test/1.txt".to_string()).unwrap();
+    drop(builder);
+}
+
+#[test]
+fn failed_builder_append() {
+    /* An error propagated out of a build drops the builder without tripping the finish assertion */
+    let build = || -> Result<Vec<u8>, TarError> {
+        let mut builder = TarBuilder::new(Vec::new());
+        builder.append_file("test/missing.txt".to_string())?;
+        builder.finish()
+    };
+    assert!(matches!(build(), Err(TarError::Io(_))));
+
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::WriteZero.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let node = TarNode::from_data("hello.txt".to_string(), b"hello").unwrap();
+    let mut builder = TarBuilder::new(Full);
+    assert!(builder.append_node(&node).is_err());
+    assert!(TarBuilder::new(Full)
+        .start_entry("hello.txt".to_string(), 5)
+        .is_err());
+}
+
+#[test]
+fn update_newer_files() {
+    std::fs::write("test/23.txt", b"old").unwrap();
+    let file = File::options().write(true).open("test/23.txt").unwrap();
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1000))
+        .unwrap();
+
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    assert!(data.update("test/23.txt".to_string()).unwrap());
+    assert!(!data.update("test/23.txt".to_string()).unwrap());
+    assert!(!data.update("test/1.txt".to_string()).unwrap());
+
+    std::fs::write("test/23.txt", b"newer").unwrap();
+    file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(2000))
+        .unwrap();
+    assert!(data.update("test/23.txt".to_string()).unwrap());
+    let entries = data.list();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[1].size, entries[1].mtime), (5, 2000));
+    std::fs::remove_file("test/23.txt").unwrap();
+}
+
+#[test]
+fn update_renamed_files() {
+    let abs = std::fs::canonicalize("test/1.txt").unwrap();
+    let abs = abs.to_str().unwrap().to_string();
+    let mut data = TarFile::new(abs.clone()).unwrap();
+    assert!(!data.update(abs.clone()).unwrap());
+    assert_eq!(data.list().len(), 1);
+
+    let options = ArchiveOptions::default().transform(Transform::prefix("package/".to_string()));
+    let mut data = TarFile::default();
+    data.append_with("test/1.txt".to_string(), &options)
+        .unwrap();
+    assert!(!data
+        .update_with("test/1.txt".to_string(), &options)
+        .unwrap());
+    let entries = data.list();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "package/test/1.txt");
+}
+
+#[test]
+fn streaming_delete_from_disk() {
+    let contents: Vec<u8> = (0..1 << 20).map(|i: u32| (i % 251) as u8).collect();
+    std::fs::write("test/24.bin", &contents).unwrap();
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("test/24.bin".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    let out = File::create("test/24.tar").unwrap();
+    data.write(&out).unwrap();
+
+    let names = vec!["test/1.txt".to_string(), "missing".to_string()];
+    assert_eq!(
+        TarFile::delete_from_disk("test/24.tar".to_string(), &names).unwrap(),
+        2
+    );
+    assert_eq!(
+        TarFile::delete_from_disk("test/24.tar".to_string(), &names).unwrap(),
+        0
+    );
+
+    let data = TarFile::open("test/24.tar".to_string()).unwrap();
+    let mut out = Vec::new();
+    data.extract_entry("test/24.bin".to_string(), &mut out)
+        .unwrap();
+    assert_eq!(out, contents);
+    assert_eq!(data.list().len(), 1);
+    std::fs::remove_file("test/24.tar").unwrap();
+    std::fs::remove_file("test/24.bin").unwrap();
+}
+
+#[test]
+fn append_dir_with_excludes() {
+    let _ = std::fs::remove_dir_all("test/walk");
+    for dir in ["test/walk/target", "test/walk/sub/target"] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    for file in [
+        "a.txt",
+        "b.o",
+        "target/x",
+        "sub/c.txt",
+        "sub/d.o",
+        "sub/target/y",
+    ] {
+        std::fs::write(format!("test/walk/{}", file), b"data").unwrap();
+    }
+
+    let options = ArchiveOptions::default()
+        .exclude("*.o".to_string())
+        .exclude("target".to_string());
+    let mut data = TarFile::default();
+    data.append_dir_all_with("test/walk/".to_string(), &options)
+        .unwrap();
+    data.append_with("test/walk/b.o".to_string(), &options)
+        .unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(
+        names,
+        vec![
+            "test/walk",
+            "test/walk/a.txt",
+            "test/walk/sub",
+            "test/walk/sub/c.txt"
+        ]
+    );
+
+    let mut data = TarFile::default();
+    data.append_dir_all("test/walk".to_string()).unwrap();
+    assert_eq!(data.list().len(), 10);
+
+    let options = ArchiveOptions::default().exclude("[".to_string());
+    assert!(matches!(
+        data.append_with("test/walk/a.txt".to_string(), &options),
+        Err(TarError::Pattern(_))
+    ));
+    std::fs::remove_dir_all("test/walk").unwrap();
+}
+
+#[test]
+fn collect_and_extend_nodes() {
+    let mut data: TarFile = (0..3)
+        .map(|i| TarNode::from_data(format!("{}.txt", i), format!("file {}", i).as_bytes()))
+        .collect::<Result<Vec<TarNode>, TarError>>()
+        .unwrap()
+        .into_iter()
+        .collect();
+    data.extend(
+        TarFile::open("test/1.tar".to_string())
+            .unwrap()
+            .list()
+            .iter()
+            .map(|e| TarNode::from_data(format!("copy/{}", e.name), b"").unwrap()),
+    );
+
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["0.txt", "1.txt", "2.txt", "copy/1.txt"]);
+    let mut out = Vec::new();
+    data.extract_entry("2.txt".to_string(), &mut out).unwrap();
+    assert_eq!(out, b"file 2");
+}
+
+#[test]
+fn create_from_file_list() {
+    let list = "test/1.txt\n\nCargo.toml\nsrc/lib.rs";
+    let data = TarFile::from_file_list(list.as_bytes()).unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["test/1.txt", "Cargo.toml", "src/lib.rs"]);
+
+    let options = ArchiveOptions::default().exclude("*.toml".to_string());
+    let data = TarFile::from_file_list_with(list.as_bytes(), &options).unwrap();
+    assert_eq!(data.list().len(), 2);
+
+    assert!(TarFile::from_file_list("test/missing\n".as_bytes()).is_err());
+
+    std::fs::write("test/25\nnewline.txt", b"odd").unwrap();
+    let list = "test/1.txt\0test/25\nnewline.txt\0\0";
+    let data = TarFile::from_null_file_list(list.as_bytes()).unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["test/1.txt", "test/25\nnewline.txt"]);
+    std::fs::remove_file("test/25\nnewline.txt").unwrap();
+}
+
+#[test]
+fn append_duplicate_policies() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    assert_eq!(data.list().len(), 2);
+    assert_eq!(
+        data.warnings(),
+        &[TarWarning::DuplicateName("test/1.txt".to_string())]
+    );
+
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    let options = ArchiveOptions::default().duplicates(DuplicatePolicy::Error);
+    assert!(matches!(
+        data.append_with("test/1.txt".to_string(), &options),
+        Err(TarError::DuplicateEntry(_))
+    ));
+    data.append_with("Cargo.toml".to_string(), &options)
+        .unwrap();
+
+    let options = ArchiveOptions::default().duplicates(DuplicatePolicy::KeepLast);
+    data.append_with("test/1.txt".to_string(), &options)
+        .unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["Cargo.toml", "test/1.txt"]);
+
+    let options = ArchiveOptions::default().duplicates(DuplicatePolicy::KeepFirst);
+    data.append_with("Cargo.toml".to_string(), &options)
+        .unwrap();
+    assert_eq!(data.list().len(), 2);
+    assert!(data.warnings().is_empty());
+}
+
+#[test]
+fn push_and_append_nodes() {
+    let source = TarFile::open("test/1.tar".to_string()).unwrap();
+    let mut copy = TarFile::default();
+    let mut builder = TarBuilder::new(Vec::new());
+    for node in source.nodes() {
+        copy.push(node.clone());
+        builder.append_node(node).unwrap();
+    }
+    let node = TarNode::from_data("hello.txt".to_string(), b"hello").unwrap();
+    builder.append_node(&node).unwrap();
+    copy.push(node);
+
+    assert_eq!(copy.list()[0], source.list()[0]);
+    assert!(copy.index().is_empty());
+    let streamed = builder.finish().unwrap();
+    let streamed = TarFile::open_stream(&streamed[..], &ReadOptions::default()).unwrap();
+    assert_eq!(streamed.list(), copy.list());
+}
+
+#[test]
+fn octal_field_encoding() {
+    let mut field = [0xffu8; 8];
+    dec_to_oct(8, &mut field, OctalTerminator::Space).unwrap();
+    assert_eq!(&field, b"0000010 ");
+    dec_to_oct(0o1234, &mut field, OctalTerminator::NulSpace).unwrap();
+    assert_eq!(&field, b"001234\0 ");
+    dec_to_oct(0o77777777, &mut field, OctalTerminator::None).unwrap();
+    assert_eq!(&field, b"77777777");
+    assert!(matches!(
+        dec_to_oct(0o100000000, &mut field, OctalTerminator::None),
+        Err(TarError::FieldOverflow)
+    ));
+    assert_eq!(&field, b"77777777");
+
+    let mut size = [0u8; 12];
+    dec_to_oct(0o77777777777, &mut size, OctalTerminator::Nul).unwrap();
+    assert!(dec_to_oct(1 << 33, &mut size, OctalTerminator::Nul).is_err());
+}
+
+#[test]
+#[cfg(feature = "gitignore")]
+fn append_dir_with_gitignore() {
+    let _ = std::fs::remove_dir_all("test/ignore");
+    std::fs::create_dir_all("test/ignore/target").unwrap();
+    std::fs::create_dir_all("test/ignore/src").unwrap();
+    std::fs::write("test/ignore/.gitignore", "target/\n*.log\n").unwrap();
+    std::fs::write("test/ignore/src/.ignore", "scratch.rs\n").unwrap();
+    for file in ["target/a.o", "build.log", "src/main.rs", "src/scratch.rs"] {
+        std::fs::write(format!("test/ignore/{}", file), b"data").unwrap();
+    }
+
+    let options = ArchiveOptions::default()
+        .gitignore(true)
+        .exclude("*.rs".to_string());
+    let mut data = TarFile::default();
+    data.append_dir_all_with("test/ignore".to_string(), &options)
+        .unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(
+        names,
+        vec![
+            "test/ignore",
+            "test/ignore/.gitignore",
+            "test/ignore/src",
+            "test/ignore/src/.ignore"
+        ]
+    );
+
+    let options = ArchiveOptions::default().gitignore(true);
+    let mut data = TarFile::default();
+    data.append_dir_all_with("test/ignore".to_string(), &options)
+        .unwrap();
+    assert_eq!(data.list().len(), 5);
+    std::fs::remove_dir_all("test/ignore").unwrap();
+}
+
+#[test]
+fn refresh_stale_checksums_on_write() {
+    use deku::prelude::*;
+
+    let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    let mut block = data.nodes()[0].header().to_bytes().unwrap();
+    block[..5].copy_from_slice(b"2.txt");
+    let (_, header) = TarHeader::from_bytes((&block, 0)).unwrap();
+    assert!(!header.validate_checksum().unwrap());
+
+    let mut node = data.nodes()[0].clone();
+    *node.header_mut() = header;
+    data.push(node);
+
+    let out = File::create("test/26.tar").unwrap();
+    let strict = WriteOptions::default().strict(true);
+    assert!(matches!(
+        data.clone().write_with(&out, &strict),
+        Err(TarError::InvalidChecksum)
+    ));
+
+    let out = File::create("test/26.tar").unwrap();
+    data.write_with(&out, &WriteOptions::default()).unwrap();
+    let names: Vec<String> = TarFile::open("test/26.tar".to_string())
+        .unwrap()
+        .list()
+        .into_iter()
+        .map(|e| e.name)
+        .collect();
+    assert_eq!(names, vec!["1.txt", "2.txt"]);
+    std::fs::remove_file("test/26.tar").unwrap();
+}
+
+#[test]
+fn extract_strip_components() {
+    let _ = std::fs::remove_dir_all("test/strip");
+    let mut data = TarFile::new("src".to_string()).unwrap();
+    data.append("src/tar".to_string()).unwrap();
+    data.append("src/tar/walk.rs".to_string()).unwrap();
+    data.append("Cargo.toml".to_string()).unwrap();
+
+    let options = ExtractOptions::default().strip_components(1);
+    data.extract_with("test/strip".to_string(), &options)
+        .unwrap();
+    assert!(std::path::Path::new("test/strip/tar").is_dir());
+    assert_eq!(
+        std::fs::read("test/strip/tar/walk.rs").unwrap(),
+        std::fs::read("src/tar/walk.rs").unwrap()
+    );
+    assert!(!std::path::Path::new("test/strip/Cargo.toml").exists());
+    assert_eq!(std::fs::read_dir("test/strip").unwrap().count(), 1);
+    std::fs::remove_dir_all("test/strip").unwrap();
+}
+
+#[test]
+fn extract_single_entry() {
+    let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(
+        data.extract_entry("1.txt".to_string(), &mut out).unwrap(),
+        21
+    );
+    assert_eq!(out, std::fs::read("test/1.txt").unwrap());
+    assert!(matches!(
+        data.extract_entry("2.txt".to_string(), &mut out),
+        Err(TarError::NotFound(_))
+    ));
+}
+
+#[test]
+fn socket_policies() {
+    let path = "test/17.sock";
+    let _ = std::fs::remove_file(path);
+    let _listener = std::os::unix::net::UnixListener::bind(path).unwrap();
+
+    let mut data = TarFile::default();
+    data.append(path.to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    assert_eq!(
+        data.warnings(),
+        &[TarWarning::SocketSkipped(path.to_string())]
+    );
+    assert!(!data.remove(path.to_string()).unwrap());
+
+    let options = ArchiveOptions::default().sockets(SocketPolicy::Ignore);
+    let data = TarFile::new_with(path.to_string(), &options).unwrap();
+    assert!(data.warnings().is_empty());
+
+    let options = ArchiveOptions::default().sockets(SocketPolicy::Error);
+    assert!(matches!(
+        TarFile::new_with(path.to_string(), &options),
+        Err(TarError::SpecialFile(_))
+    ));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn pax_records_are_sorted_and_canonical() {
+    let mut data = TarFile::default();
+    data.record_rename("a.txt".to_string(), "b.txt".to_string())
+        .unwrap();
+    let out = File::create("test/18.tar").unwrap();
+    data.write(&out).unwrap();
+
+    let mut node = TarNode::read(File::open("test/18.tar").unwrap()).unwrap();
+    node.push_pax(PaxRecord::new("mtime", "+0012.500"));
+    node.push_pax(PaxRecord::new("comment", "x"));
+    let mut out = Vec::new();
+    node.write(&mut out).unwrap();
+
+    let text = String::from_utf8_lossy(&out[512..1024]);
+    let positions = [
+        "MINITAR.journal=",
+        "MINITAR.journal.target=",
+        "comment=",
+        "mtime=12.5\n",
+    ]
+    .iter()
+    .map(|k| text.find(k).unwrap())
+    .collect::<Vec<_>>();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    std::fs::remove_file("test/18.tar").unwrap();
+}
+
+#[test]
+fn extract_tar_file_to_memory() {
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("test".to_string()).unwrap();
+    data.record_delete("gone.txt".to_string()).unwrap();
+
+    let files = data.extract_to_memory().unwrap();
+    assert_eq!(files.len(), 1);
+    let entry = &files[std::path::Path::new("test/1.txt")];
+    assert_eq!(entry.contents, std::fs::read("test/1.txt").unwrap());
+}
+
+#[test]
+fn size_and_data_must_match() {
+    use deku::prelude::*;
+
+    let mut data = TarFile::new("src".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    assert_eq!(data.list()[0].size, 0);
+
+    let node = TarNode::from_data("big.txt".to_string(), b"hello").unwrap();
+    let mut block = node.header().to_bytes().unwrap();
+    dec_to_oct(1000, &mut block[124..136], OctalTerminator::Nul).unwrap();
+    let (_, mut header) = TarHeader::from_bytes((&block, 0)).unwrap();
+    header.update_checksum().unwrap();
+    let mut node = node.clone();
+    *node.header_mut() = header;
+    data.push(node);
+
+    let out = File::create("test/27.tar").unwrap();
+    match data.write(&out) {
+        Err(TarError::SizeMismatch { name, size, blocks }) => {
+            assert_eq!((name.as_str(), size, blocks), ("big.txt", 1000, 1))
+        }
+        r => panic!("unexpected {:?}", r),
+    }
+    assert_eq!(std::fs::metadata("test/27.tar").unwrap().len(), 0);
+    std::fs::remove_file("test/27.tar").unwrap();
+}
+
+#[test]
+fn transform_names() {
+    let options = ArchiveOptions::default().transform(Transform::prefix("package/".to_string()));
+    let mut data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    data.append_with("src/lib.rs".to_string(), &options)
+        .unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["package/test/1.txt", "package/src/lib.rs"]);
+    assert!(data
+        .nodes()
+        .iter()
+        .all(|n| n.header().validate_checksum().unwrap()));
+
+    data.transform(&Transform::replace_dir(
+        "package/src".to_string(),
+        "lib".to_string(),
+    ))
+    .unwrap();
+    assert_eq!(data.list()[1].name, "lib/lib.rs");
+    assert!(data.nodes()[1].header().validate_checksum().unwrap());
+
+    let _ = std::fs::remove_dir_all("test/transform");
+    let options = ExtractOptions::default()
+        .strip_components(1)
+        .transform(Transform::new(|name| match name {
+            "lib.rs" => String::new(),
+            _ => name.replace("test", "docs"),
+        }));
+    data.extract_with("test/transform".to_string(), &options)
+        .unwrap();
+    assert!(std::path::Path::new("test/transform/docs/1.txt").is_file());
+    assert!(!std::path::Path::new("test/transform/lib.rs").exists());
+    std::fs::remove_dir_all("test/transform").unwrap();
+}
+
+#[test]
+fn create_and_extract_in_one_call() {
+    let _ = std::fs::remove_dir_all("test/oneshot");
+    crate::create(
+        "src/tar".to_string(),
+        "test/28.tar".to_string(),
+        &ArchiveOptions::default(),
+    )
+    .unwrap();
+    crate::extract(
+        "test/28.tar".to_string(),
+        "test/oneshot".to_string(),
+        &ExtractOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read("test/oneshot/src/tar/walk.rs").unwrap(),
+        std::fs::read("src/tar/walk.rs").unwrap()
+    );
+    std::fs::remove_dir_all("test/oneshot").unwrap();
+    std::fs::remove_file("test/28.tar").unwrap();
+
+    for dest in ["test/28.tar.gz", "test/28.tar.zst"] {
+        let result = crate::create(
+            "src/lib.rs".to_string(),
+            dest.to_string(),
+            &Default::default(),
+        );
+        let enabled = match Compression::from_path(dest) {
+            Compression::Gzip => cfg!(feature = "gzip"),
+            _ => cfg!(feature = "zstd"),
+        };
+        if !enabled {
+            assert!(matches!(result, Err(TarError::UnsupportedCompression(_))));
+            let _ = std::fs::remove_file(dest);
+            continue;
+        }
+
+        result.unwrap();
+        let options = ExtractOptions::default().strip_components(1);
+        crate::extract(dest.to_string(), "test/oneshot".to_string(), &options).unwrap();
+        assert_eq!(
+            std::fs::read("test/oneshot/lib.rs").unwrap(),
+            std::fs::read("src/lib.rs").unwrap()
+        );
+        std::fs::remove_dir_all("test/oneshot").unwrap();
+        std::fs::remove_file(dest).unwrap();
+    }
+}
+
+#[test]
+fn sanitize_added_names() {
+    let cwd = std::env::current_dir().unwrap();
+    let absolute = format!("{}/test/1.txt", cwd.display());
+    let mut data = TarFile::default();
+    data.append(absolute.clone()).unwrap();
+    data.append("test/../test/1.txt".to_string()).unwrap();
+
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names[0], absolute.trim_start_matches('/'));
+    assert_eq!(names[1], "test/1.txt");
+    assert!(data.warnings().contains(&TarWarning::NameSanitized {
+        name: "test/../test/1.txt".to_string(),
+        stored: "test/1.txt".to_string(),
+    }));
+    let original = data.nodes()[1]
+        .pax()
+        .iter()
+        .find(|r| r.key() == "MINITAR.original_path")
+        .unwrap();
+    assert_eq!(original.value_str(), Some("test/../test/1.txt"));
+
+    let options = ArchiveOptions::default()
+        .sanitize_names(false)
+        .duplicates(DuplicatePolicy::KeepLast);
+    let data = TarFile::new_with(absolute.clone(), &options).unwrap();
+    assert_eq!(data.list()[0].name, absolute);
+    assert!(data.warnings().is_empty());
+}
+
+#[test]
+fn reproducible_archives() {
+    let options = ArchiveOptions::default()
+        .reproducible(true)
+        .source_date_epoch(1_000_000_000);
+    let mut archives = Vec::new();
+    for _ in 0..2 {
+        let mut builder =
+            TarBuilder::with_options(Vec::new(), WriteOptions::default(), options.clone());
+        builder.append_file("src".to_string()).unwrap();
+        builder.append_file("test/1.txt".to_string()).unwrap();
+        archives.push(builder.finish().unwrap());
+    }
+    assert_eq!(archives[0], archives[1]);
+
+    let data = TarFile::open_stream(&archives[0][..], &ReadOptions::default()).unwrap();
+    for entry in data.list() {
+        assert_eq!((entry.uid, entry.gid), (0, 0));
+        assert_eq!((entry.uname.as_str(), entry.gname.as_str()), ("", ""));
+        assert_eq!(entry.mtime, 1_000_000_000);
+    }
+    assert_eq!(data.list()[0].mode, 0o755);
+    assert_eq!(data.list()[1].mode, 0o644);
+}
+
+#[test]
+fn metadata_overrides() {
+    let resolver = StaticResolver::default()
+        .user(1234, "builder".to_string())
+        .group(5678, "builders".to_string());
+    let options = ArchiveOptions::default()
+        .resolver(resolver)
+        .uid(1234)
+        .gid(5678)
+        .mode(0o100640)
+        .mtime(1_500_000_000);
+    let mut data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    let entry = &data.list()[0];
+    assert_eq!((entry.uid, entry.gid), (1234, 5678));
+    assert_eq!(
+        (entry.uname.as_str(), entry.gname.as_str()),
+        ("builder", "builders")
+    );
+    assert_eq!((entry.mode, entry.mtime), (0o640, 1_500_000_000));
+
+    /* Explicit names and forced values win over reproducible normalization */
+    let options = ArchiveOptions::default()
+        .reproducible(true)
+        .source_date_epoch(1_000_000_000)
+        .uid(1000)
+        .uname("builder".to_string())
+        .mode(0o600)
+        .mtime(1_500_000_000);
+    data.append_with("src".to_string(), &options).unwrap();
+    let entry = &data.list()[1];
+    assert_eq!((entry.uid, entry.gid), (1000, 0));
+    assert_eq!(
+        (entry.uname.as_str(), entry.gname.as_str()),
+        ("builder", "")
+    );
+    assert_eq!((entry.mode, entry.mtime), (0o600, 1_500_000_000));
+}
+
+#[test]
+fn third_party_corpus() {
+    let long = format!("{}/{}.txt", "d".repeat(60), "n".repeat(60));
+    let unicode = "ünïcødé-文件.txt";
+    let normal = |name: &str, size| (name.to_string(), FileType::Normal, size, None);
+    let dir = ("dir/".to_string(), FileType::Dir, 0, None);
+    let link = (
+        "dir/link".to_string(),
+        FileType::Sym,
+        0,
+        Some("plain.txt".to_string()),
+    );
+
+    let corpus = vec![
+        (
+            "gnu-longname",
+            vec![
+                normal("plain.txt", 6),
+                normal(&long, 6),
+                dir.clone(),
+                link.clone(),
+            ],
+        ),
+        (
+            "gnu-sparse",
+            vec![("sparse.img".to_string(), FileType::GnuSparse, 1048576, None)],
+        ),
+        ("gnu-pax-sparse", vec![normal("sparse.img", 1048576)]),
+        ("gnu-large", vec![normal("large.img", 9 << 30)]),
+        ("gnu-unicode", vec![normal(unicode, 8)]),
+        (
+            "bsdtar-ustar",
+            vec![normal("plain.txt", 6), normal(&long, 6), dir, link.clone()],
+        ),
+        (
+            "bsdtar-pax",
+            vec![normal("plain.txt", 6), normal(&long, 6), normal(unicode, 8)],
+        ),
+        (
+            "python-pax",
+            vec![
+                normal("plain.txt", 6),
+                normal(&long, 6),
+                normal(unicode, 8),
+                link.clone(),
+            ],
+        ),
+        (
+            "python-gnu",
+            vec![
+                normal("plain.txt", 6),
+                normal(&long, 6),
+                normal(unicode, 8),
+                link,
+            ],
+        ),
+    ];
+
+    for (archive, expected) in corpus {
+        let data = TarFile::open(format!("test/corpus/{}.tar", archive)).unwrap();
+        let entries: Vec<_> = data
+            .list()
+            .into_iter()
+            .map(|e| (e.name, e.file_type, e.size, e.link_target))
+            .collect();
+        assert_eq!(entries, expected, "{}", archive);
+
+        for entry in data.list() {
+            assert_eq!((entry.uid, entry.gid), (0, 0), "{}", archive);
+            assert_eq!(entry.mtime, 1613849820, "{}", archive);
+        }
+
+        if archive.contains("large") {
+            continue;
+        }
+        let mut sparse = vec![0u8; 1 << 20];
+        sparse[..4].copy_from_slice(b"head");
+        sparse[(1 << 20) - 4..].copy_from_slice(b"tail");
+        for (path, file) in data.extract_to_memory().unwrap() {
+            let expected: &[u8] = match path.to_str() {
+                Some(name) if name == unicode => b"unicode\n",
+                Some("sparse.img") => &sparse,
+                _ => b"hello\n",
+            };
+            assert_eq!(file.contents, expected, "{} {:?}", archive, path);
+        }
+    }
+}
+
+#[test]
+fn sort_entries() {
+    let mut data = TarFile::new("src/tar.rs".to_string()).unwrap();
+    data.append("Cargo.toml".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+
+    data.sort_by_name();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["Cargo.toml", "src/tar.rs", "test/1.txt"]);
+
+    data.sort_by(|a, b| b.size.cmp(&a.size));
+    assert_eq!(data.list()[0].name, "src/tar.rs");
+    assert_eq!(data.list()[2].name, "test/1.txt");
+}
+
+#[test]
+fn detect_duplicate_names() {
+    let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    data.check_duplicates().unwrap();
+
+    let mut dir = TarFile::new("src".to_string()).unwrap();
+    dir.transform(&Transform::new(|_| "./1.txt/".to_string()))
+        .unwrap();
+    data.merge(dir, DuplicatePolicy::KeepBoth).unwrap();
+    data.push(TarNode::from_data("2.txt".to_string(), b"two").unwrap());
+    data.push(TarNode::from_data("1.txt".to_string(), b"one").unwrap());
+
+    assert_eq!(data.duplicates(), vec![("1.txt".to_string(), 3)]);
+    assert!(matches!(
+        data.check_duplicates(),
+        Err(TarError::DuplicateEntry(name)) if name == "1.txt"
+    ));
+}
+
+#[test]
+fn header_fields_at_boundary_lengths() {
+    /* Offset and length of every ustar header field */
+    let fields = [
+        (0, 100),
+        (100, 8),
+        (108, 8),
+        (116, 8),
+        (124, 12),
+        (136, 12),
+        (148, 8),
+        (156, 1),
+        (157, 100),
+        (257, 6),
+        (263, 2),
+        (265, 32),
+        (297, 32),
+        (329, 8),
+        (337, 8),
+        (345, 155),
+        (500, 12),
+    ];
+    assert_eq!(fields.iter().map(|f| f.1).sum::<usize>(), 512);
+
+    for (offset, len) in fields {
+        let mut block = [b'x'; 512];
+        let field = &mut block[offset..offset + len];
+        set_field(field, &vec![b'a'; len]).unwrap();
+        assert!(field.iter().all(|&b| b == b'a'));
+
+        assert!(matches!(
+            set_field(field, &vec![b'b'; len + 1]),
+            Err(TarError::FieldOverflow)
+        ));
+        assert!(field.iter().all(|&b| b == b'a'));
+
+        set_field(field, &vec![b'c'; len - 1]).unwrap();
+        assert_eq!(field[len - 1], 0);
+        set_field(field, b"").unwrap();
+        assert!(field.iter().all(|&b| b == 0));
+
+        /* Numeric fields hold one octal digit per byte, less the terminator */
+        if (2..=12).contains(&len) {
+            let max = (1u64 << (3 * (len - 1))) - 1;
+            dec_to_oct(max, field, OctalTerminator::Nul).unwrap();
+            assert!(dec_to_oct(max + 1, field, OctalTerminator::Nul).is_err());
+        }
+        assert_eq!(
+            dec_to_oct(0, field, OctalTerminator::NulSpace).is_err(),
+            len < 3
+        );
+
+        /* Nothing outside the field is touched */
+        assert!(block[..offset].iter().all(|&b| b == b'x'));
+        assert!(block[offset + len..].iter().all(|&b| b == b'x'));
+    }
+
+    let long = "n".repeat(101);
+    let mut data = TarFile::open("test/1.tar".to_string()).unwrap();
+    assert!(!data.remove("n".repeat(200)).unwrap());
+    assert!(matches!(
+        TarNode::from_data(long.clone(), b""),
+        Err(TarError::NameTooLong)
+    ));
+    assert!(TarNode::from_data("n".repeat(100), b"").is_ok());
+    let map = SparseMap {
+        real_size: 4,
+        segments: vec![(0, 4)],
+    };
+    let node = TarNode::from_sparse(format!("{}ü", "n".repeat(85)), map, b"data").unwrap();
+    let options = WriteOptions::default().sparse_format(SparseFormat::Pax1_0);
+    node.write_with(&mut Vec::new(), &options).unwrap();
+}
+
+#[test]
+fn pluggable_owner_resolver() {
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug)]
+    struct Counting(std::sync::Arc<AtomicUsize>);
+
+    impl OwnerResolver for Counting {
+        fn user_name(&self, uid: u32) -> Option<String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Some(format!("user{}", uid))
+        }
+
+        fn group_name(&self, _: u32) -> Option<String> {
+            None
+        }
+    }
+
+    let lookups = std::sync::Arc::new(AtomicUsize::new(0));
+    let cached = CachedResolver::new(Counting(lookups.clone()));
+    for _ in 0..3 {
+        assert_eq!(cached.user_name(7).as_deref(), Some("user7"));
+        assert_eq!(cached.group_name(7), None);
+    }
+    assert_eq!(cached.user_name(8).as_deref(), Some("user8"));
+    assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+    let meta = std::fs::metadata("test/1.txt").unwrap();
+    let resolver = StaticResolver::default()
+        .user(meta.uid(), "builder".to_string())
+        .group(meta.gid(), "builders".to_string());
+    let options = ArchiveOptions::default().resolver(resolver);
+    let mut data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    let entry = &data.list()[0];
+    assert_eq!(
+        (entry.uname.as_str(), entry.gname.as_str()),
+        ("builder", "builders")
+    );
+
+    let options = ArchiveOptions::default().resolver(StaticResolver::default());
+    data.append_with("src/lib.rs".to_string(), &options)
+        .unwrap();
+    let entry = &data.list()[1];
+    assert_eq!((entry.uname.as_str(), entry.gname.as_str()), ("", ""));
+}
+
+#[test]
+fn extract_owner_mapping() {
+    use std::os::unix::fs::MetadataExt;
+
+    let options = ArchiveOptions::default()
+        .uid(1234)
+        .gid(1234)
+        .uname("builder".to_string())
+        .gname("nobody-here".to_string());
+    let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+
+    /* The ids only change when running as root, so check the mapping itself first */
+    let resolver = StaticResolver::default().user(4321, "builder".to_string());
+    assert_eq!(resolver.user_id("builder"), Some(4321));
+    assert_eq!(resolver.group_id("nobody-here"), None);
+    let owned = |mapping: OwnerMapping| {
+        let options = ExtractOptions::default()
+            .resolver(resolver.clone())
+            .owners(mapping);
+        let _ = std::fs::remove_dir_all("test/owners");
+        data.extract_with("test/owners".to_string(), &options)
+            .unwrap();
+        let meta = std::fs::metadata("test/owners/test/1.txt").unwrap();
+        (meta.uid(), meta.gid())
+    };
+    let root = unsafe { libc::geteuid() } == 0;
+    let (uid, gid) = owned(OwnerMapping::Names);
+    if root {
+        assert_eq!((uid, gid), (4321, 1234));
+        assert_eq!(owned(OwnerMapping::Numeric), (1234, 1234));
+        /* Files are created by root, so owners that don't resolve stay root */
+        assert_eq!(owned(OwnerMapping::NamesOnly), (4321, 0));
+    }
+
+    std::fs::remove_dir_all("test/owners").unwrap();
+}
+
+#[test]
+fn extract_owner_fallback() {
+    use std::os::unix::fs::MetadataExt;
+
+    let options = ArchiveOptions::default().uid(1234).gid(1234);
+    let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    let extract = |options: ExtractOptions| {
+        let _ = std::fs::remove_dir_all("test/fallback");
+        let warnings = data
+            .extract_with(
+                "test/fallback".to_string(),
+                &options.owners(OwnerMapping::Numeric),
+            )
+            .unwrap()
+            .warnings;
+        let meta = std::fs::metadata("test/fallback/test/1.txt").unwrap();
+        (warnings, meta.uid())
+    };
+
+    /* Without root the owner is left alone and reported, rather than failing the extraction */
+    let (warnings, uid) = extract(ExtractOptions::default());
+    let skipped = TarWarning::OwnerNotRestored {
+        name: "test/1.txt".to_string(),
+        uid: 1234,
+        gid: 1234,
+    };
+    match unsafe { libc::geteuid() } {
+        0 => assert_eq!((warnings, uid), (vec![], 1234)),
+        euid => assert_eq!((warnings, uid), (vec![skipped], euid)),
+    }
+
+    let (warnings, uid) = extract(ExtractOptions::default().same_owner(false));
+    assert!(warnings.is_empty());
+    assert_eq!(uid, unsafe { libc::geteuid() });
+
+    /* The warning is only reported from the default verbosity up */
+    let (warnings, _) = extract(ExtractOptions::default().verbosity(Verbosity::Quiet));
+    assert!(warnings.is_empty());
+    let (warnings, _) = extract(ExtractOptions::default().verbosity(Verbosity::Verbose));
+    assert_eq!(warnings.len(), (unsafe { libc::geteuid() } != 0) as usize);
+
+    std::fs::remove_dir_all("test/fallback").unwrap();
+}
+
+#[test]
+fn extract_umask() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let options = ArchiveOptions::default().mode(0o777);
+    let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    let extract = |options: ExtractOptions| {
+        data.extract_with("test/umask".to_string(), &options)
+            .unwrap();
+        let meta = std::fs::metadata("test/umask/test/1.txt").unwrap();
+        meta.permissions().mode() & 0o7777
+    };
+
+    let filtered = ExtractOptions::default().same_permissions(false);
+    let umask = filtered.umask();
+    assert_eq!(umask & !0o777, 0);
+    assert_eq!(extract(filtered), 0o777 & !umask);
+    let exact = ExtractOptions::default().same_permissions(true);
+    assert_eq!(exact.umask(), 0);
+    assert_eq!(extract(exact), 0o777);
+
+    std::fs::remove_dir_all("test/umask").unwrap();
+}
+
+#[test]
+fn deferred_directory_modes() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    std::fs::create_dir_all("test/deferred/ro").unwrap();
+    std::fs::write(
+        "test/deferred/ro/inner.txt",
+        b"inside a read-only directory",
+    )
+    .unwrap();
+    let options = ArchiveOptions::default().mode(0o500).mtime(1_000_000_000);
+    let mut data = TarFile::new_with("test/deferred/ro".to_string(), &options).unwrap();
+    data.append_with("test/deferred/ro/inner.txt".to_string(), &options)
+        .unwrap();
+    std::fs::remove_dir_all("test/deferred").unwrap();
+
+    /* Creating the file would fail if the directory got its final mode first */
+    let options = ExtractOptions::default()
+        .same_permissions(true)
+        .strip_components(1);
+    data.extract_with("test/deferred".to_string(), &options)
+        .unwrap();
+    let dir = std::fs::metadata("test/deferred/deferred/ro").unwrap();
+    assert_eq!(dir.permissions().mode() & 0o7777, 0o500);
+    assert_eq!(dir.mtime(), 1_000_000_000);
+    let file = std::fs::metadata("test/deferred/deferred/ro/inner.txt").unwrap();
+    assert_eq!(file.mtime(), 1_000_000_000);
+    assert_eq!(
+        std::fs::read("test/deferred/deferred/ro/inner.txt").unwrap(),
+        b"inside a read-only directory"
+    );
+
+    let writable = std::fs::Permissions::from_mode(0o700);
+    std::fs::set_permissions("test/deferred/deferred/ro", writable).unwrap();
+    std::fs::remove_dir_all("test/deferred").unwrap();
+}
+
+#[test]
+fn precise_mtimes() {
+    use std::os::unix::fs::MetadataExt;
+    use std::time::{Duration, SystemTime};
+
+    std::fs::write("test/42.txt", b"timed to the nanosecond").unwrap();
+    let time = SystemTime::UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789);
+    File::options()
+        .write(true)
+        .open("test/42.txt")
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+
+    /* Whole seconds stay in the header, so only precise archives hold the fraction */
+    let data = TarFile::new("test/42.txt".to_string()).unwrap();
+    assert_eq!(data.list()[0].mtime_nanos, 0);
+    let options = ArchiveOptions::default().precise_mtime(true);
+    let data = TarFile::new_with("test/42.txt".to_string(), &options).unwrap();
+    let entry = &data.list()[0];
+    assert_eq!(
+        (entry.mtime, entry.mtime_nanos),
+        (1_600_000_000, 123_456_789)
+    );
+    assert_eq!(
+        data.nodes()[0].pax()[0].value_str(),
+        Some("1600000000.123456789")
+    );
+
+    data.write(&File::create("test/42.tar").unwrap()).unwrap();
+    let data = TarFile::open("test/42.tar".to_string()).unwrap();
+    data.extract("test/precise".to_string()).unwrap();
+    let meta = std::fs::metadata("test/precise/test/42.txt").unwrap();
+    assert_eq!(
+        (meta.mtime(), meta.mtime_nsec()),
+        (1_600_000_000, 123_456_789)
+    );
+
+    /* A forced time has no fraction */
+    let options = options.mtime(1_700_000_000);
+    let data = TarFile::new_with("test/42.txt".to_string(), &options).unwrap();
+    assert!(data.nodes()[0].pax().is_empty());
+
+    std::fs::remove_file("test/42.txt").unwrap();
+    std::fs::remove_file("test/42.tar").unwrap();
+    std::fs::remove_dir_all("test/precise").unwrap();
+}
+
+#[test]
+fn out_of_range_times() {
+    use std::os::unix::fs::MetadataExt;
+
+    /* The last second that fits 11 octal digits, the first that doesn't, one before 1970 and year 1 */
+    let max_octal = (1 << 33) - 1;
+    for (mtime, octal) in [
+        (max_octal, true),
+        (max_octal + 1, false),
+        (-1, false),
+        (-62_135_596_800, false),
+    ] {
+        let options = ArchiveOptions::default().mtime(mtime);
+        let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+        let header = data.nodes()[0].header();
+        assert_eq!(header.mod_time[0] & 0x80 == 0, octal);
+        assert_eq!(data.nodes()[0].pax().is_empty(), octal);
+
+        data.write(&File::create("test/43.tar").unwrap()).unwrap();
+        let data = TarFile::open("test/43.tar".to_string()).unwrap();
+        assert_eq!(data.list()[0].mtime, mtime);
+        data.extract("test/times".to_string()).unwrap();
+        /* Many filesystems clamp times to 32 bits */
+        let meta = std::fs::metadata("test/times/test/1.txt").unwrap();
+        if mtime >= i32::MIN as i64 {
+            assert_eq!(meta.mtime(), mtime);
+        }
+    }
+
+    /* Negative times are sign extended, and listed as dates before the epoch */
+    let mut field = [0u8; 12];
+    signed_to_base256(-1, &mut field).unwrap();
+    assert_eq!(field, [0xff; 12]);
+    let options = ArchiveOptions::default().mtime(-1);
+    let data = TarFile::new_with("test/1.txt".to_string(), &options).unwrap();
+    assert!(data.list()[0]
+        .to_string()
+        .contains(" 1969-12-31 23:59 test/1.txt"));
+
+    std::fs::remove_file("test/43.tar").unwrap();
+    std::fs::remove_dir_all("test/times").unwrap();
+}
+
+#[test]
+fn contiguous_entry_data() {
+    let contents: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    let node = TarNode::from_data("data.bin".to_string(), &contents).unwrap();
+    assert_eq!(&node.data().unwrap()[..], &contents[..]);
+
+    /* Padding is only added when the entry is written */
+    let mut out = Vec::new();
+    assert_eq!(node.write(&mut out).unwrap(), 512 + 1024);
+    assert!(out[512 + 1000..].iter().all(|&b| b == 0));
+    let node = TarNode::read(&out[..]).unwrap();
+    assert_eq!(&node.data().unwrap()[..], &contents[..]);
+
+    let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    assert_eq!(data.nodes()[0].data().unwrap().len(), 21);
+}
+
+#[test]
+fn lazy_entry_data() {
+    let large: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+    let data: TarFile = vec![
+        TarNode::from_data("small.txt".to_string(), b"small").unwrap(),
+        TarNode::from_data("large.bin".to_string(), &large).unwrap(),
+        TarNode::from_data("empty.txt".to_string(), b"").unwrap(),
+    ]
+    .into_iter()
+    .collect();
+    data.write(&File::create("test/44.tar").unwrap()).unwrap();
+
+    /* The data is read from the archive when it is needed */
+    let options = ReadOptions::default().lazy_data(true);
+    let mut data = TarFile::open_with("test/44.tar".to_string(), &options).unwrap();
+    assert_eq!(&data.nodes()[1].data().unwrap()[..], &large[..]);
+    assert_eq!(data.index()[1].data_offset, 1536);
+    data.extract("test/lazy".to_string()).unwrap();
+    assert_eq!(std::fs::read("test/lazy/large.bin").unwrap(), large);
+    assert_eq!(std::fs::read("test/lazy/empty.txt").unwrap(), b"");
+
+    /* Dropping an entry and writing the rest to a new file copies the data across */
+    data.retain(|e| e.name != "small.txt");
+    data.write(&File::create("test/44.out.tar").unwrap())
+        .unwrap();
+    let data = TarFile::open("test/44.out.tar".to_string()).unwrap();
+    let names: Vec<String> = data.list().into_iter().map(|e| e.name).collect();
+    assert_eq!(names, vec!["large.bin", "empty.txt"]);
+    assert_eq!(&data.nodes()[0].data().unwrap()[..], &large[..]);
+
+    /* Data cut off by the end of the archive is found when it is opened */
+    let bytes = std::fs::read("test/44.tar").unwrap();
+    std::fs::write("test/44.tar", &bytes[..1536 + 1024]).unwrap();
+    assert!(matches!(
+        TarFile::open_with("test/44.tar".to_string(), &options),
+        Err(TarError::Truncated)
+    ));
+
+    std::fs::remove_file("test/44.tar").unwrap();
+    std::fs::remove_file("test/44.out.tar").unwrap();
+    std::fs::remove_dir_all("test/lazy").unwrap();
+}
+
+#[test]
+fn lazy_extraction_copies_data() {
+    let sizes = [1usize, 511, 513, 200_000];
+    let contents = |i: usize| -> Vec<u8> { (0..sizes[i]).map(|b| (b * 7 + i) as u8).collect() };
+    let data: TarFile = (0..sizes.len())
+        .map(|i| TarNode::from_data(format!("{}.bin", i), &contents(i)).unwrap())
+        .collect();
+    data.write(&File::create("test/52.tar").unwrap()).unwrap();
+
+    /* The data is copied straight from the archive, and through a buffer where the kernel can't copy it,
+     * such as into another filesystem */
+    let mut dests = vec!["test/52".to_string()];
+    if std::path::Path::new("/dev/shm").is_dir() {
+        dests.push(format!("/dev/shm/minitar-52-{}", std::process::id()));
+    }
+    let reads = [
+        ReadOptions::default().lazy_data(true),
+        #[cfg(feature = "mmap")]
+        ReadOptions::default().lazy_data(true).mmap(true),
+    ];
+    for read in &reads {
+        let lazy = TarFile::open_with("test/52.tar".to_string(), read).unwrap();
+        for dest in &dests {
+            lazy.extract(dest.clone()).unwrap();
+            for i in 0..sizes.len() {
+                let path = format!("{}/{}.bin", dest, i);
+                assert_eq!(std::fs::read(path).unwrap(), contents(i));
+            }
+            std::fs::remove_dir_all(dest).unwrap();
+        }
+    }
+
+    std::fs::remove_file("test/52.tar").unwrap();
+}
+
+#[test]
+fn vectored_writes() {
+    /* Counts the calls made to write out an archive */
+    #[derive(Default)]
+    struct Calls {
+        calls: usize,
+        bytes: Vec<u8>,
+    }
+    impl std::io::Write for Calls {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.write_vectored(&[std::io::IoSlice::new(buf)])
+        }
+        fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+            self.calls += 1;
+            let n = bufs.iter().map(|b| b.len()).sum();
+            bufs.iter().for_each(|b| self.bytes.extend_from_slice(b));
+            Ok(n)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let contents = vec![7u8; 5000];
+    let mut node = TarNode::from_data("data.bin".to_string(), &contents).unwrap();
+    node.push_pax(PaxRecord::new("comment", "written in one call"));
+    let mut calls = Calls::default();
+    let written = node.clone().write(&mut calls).unwrap();
+    assert_eq!(calls.calls, 1);
+    assert_eq!(written, calls.bytes.len());
+
+    let mut out = Vec::new();
+    node.write(&mut out).unwrap();
+    assert_eq!(out, calls.bytes);
+}
+
+#[test]
+fn reflink_extraction() {
+    let _ = std::fs::remove_dir_all("test/reflink");
+    let large = vec![9u8; 10000];
+    let data: TarFile = vec![
+        TarNode::from_data("a.bin".to_string(), &large).unwrap(),
+        TarNode::from_data("b.bin".to_string(), &large).unwrap(),
+        TarNode::from_data("a.bin".to_string(), b"replaced").unwrap(),
+        TarNode::from_data("c.bin".to_string(), &large).unwrap(),
+        TarNode::from_data("d.bin".to_string(), b"replaced").unwrap(),
+    ]
+    .into_iter()
+    .collect();
+    data.write(&File::create("test/45.tar").unwrap()).unwrap();
+
+    /* Files are written as usual where the filesystem can't share blocks */
+    let options = ExtractOptions::default().reflink(true);
+    for lazy in [false, true] {
+        let read = ReadOptions::default().lazy_data(lazy);
+        let data = TarFile::open_with("test/45.tar".to_string(), &read).unwrap();
+        data.extract_with("test/reflink".to_string(), &options)
+            .unwrap();
+        for (name, contents) in [
+            ("a.bin", &b"replaced"[..]),
+            ("b.bin", &large[..]),
+            ("c.bin", &large[..]),
+            ("d.bin", &b"replaced"[..]),
+        ] {
+            let path = format!("test/reflink/{}", name);
+            assert_eq!(std::fs::read(path).unwrap(), contents);
+        }
+        std::fs::remove_dir_all("test/reflink").unwrap();
+    }
+
+    std::fs::remove_file("test/45.tar").unwrap();
+}
+
+#[test]
+fn read_buffer_size() {
+    let data = TarFile::open("test/3.tar".to_string()).unwrap();
+    for size in [1, 512, 1 << 20] {
+        let options = ReadOptions::default().buffer_size(size);
+        let other = TarFile::open_with("test/3.tar".to_string(), &options).unwrap();
+        assert_eq!(other.list(), data.list());
+        assert_eq!(other.index(), data.index());
+
+        let other = TarFile::open_with("test/3.tar".to_string(), &options.lazy_data(true)).unwrap();
+        assert_eq!(other.index(), data.index());
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mapped_entry_data() {
+    let options = ReadOptions::default().mmap(true);
+    let data = TarFile::open_with("test/1.tar".to_string(), &options).unwrap();
+    let eager = TarFile::open("test/1.tar".to_string()).unwrap();
+    assert_eq!(data.list(), eager.list());
+    assert_eq!(data.index(), eager.index());
+
+    /* The data is a slice of the mapping rather than a copy */
+    let contents = data.nodes()[0].data().unwrap();
+    assert!(matches!(contents, std::borrow::Cow::Borrowed(_)));
+    assert_eq!(contents, eager.nodes()[0].data().unwrap());
+
+    let mut out = Vec::new();
+    data.extract_entry("1.txt".to_string(), &mut out).unwrap();
+    assert_eq!(out, std::fs::read("test/1.txt").unwrap());
+    let (mut a, mut b) = (Vec::new(), Vec::new());
+    data.nodes()[0].clone().write(&mut a).unwrap();
+    eager.nodes()[0].clone().write(&mut b).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn hard_links_stored_once() {
+    std::fs::create_dir_all("test/links").unwrap();
+    std::fs::write("test/links/a.txt", b"linked data").unwrap();
+    let _ = std::fs::remove_file("test/links/b.txt");
+    std::fs::hard_link("test/links/a.txt", "test/links/b.txt").unwrap();
+
+    let mut data = TarFile::default();
+    data.append_dir_all("test/links".to_string()).unwrap();
+    let entries = data.list();
+    assert_eq!(entries[1].name, "test/links/a.txt");
+    assert_eq!(entries[1].file_type, FileType::Normal);
+    assert_eq!(entries[1].size, 11);
+    assert_eq!(entries[2].name, "test/links/b.txt");
+    assert_eq!(entries[2].file_type, FileType::Hard);
+    assert_eq!(entries[2].link_target.as_deref(), Some("test/links/a.txt"));
+    assert_eq!(entries[2].size, 0);
+
+    let mut builder = TarBuilder::new(Vec::new());
+    builder.append_file("test/links/a.txt".to_string()).unwrap();
+    builder.append_file("test/links/b.txt".to_string()).unwrap();
+    let archive = builder.finish().unwrap();
+    let read = TarFile::open_stream(&archive[..], &ReadOptions::default()).unwrap();
+    assert_eq!(read.list()[1].file_type, FileType::Hard);
+    assert_eq!(
+        read.list()[1].link_target.as_deref(),
+        Some("test/links/a.txt")
+    );
+
+    let options = ArchiveOptions::default().hard_links(false);
+    let mut data = TarFile::new_with("test/links/a.txt".to_string(), &options).unwrap();
+    data.append_with("test/links/b.txt".to_string(), &options)
+        .unwrap();
+    assert_eq!(data.list()[1].file_type, FileType::Normal);
+
+    std::fs::remove_dir_all("test/links").unwrap();
+}
+
+#[test]
+fn extract_hard_links() {
+    use deku::prelude::*;
+    use std::os::unix::fs::MetadataExt;
+
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    let mut link = TarNode::from_data("copy.txt".to_string(), b"").unwrap();
+    let mut block = link.header().to_bytes().unwrap();
+    block[156] = FileType::Hard as u8;
+    block[157..167].copy_from_slice(b"test/1.txt");
+    let (_, mut header) = TarHeader::from_bytes((&block, 0)).unwrap();
+    header.update_checksum().unwrap();
+    *link.header_mut() = header;
+    data.push(link);
+
+    data.extract("test/hardlinks".to_string()).unwrap();
+    let original = std::fs::metadata("test/hardlinks/test/1.txt").unwrap();
+    let copy = std::fs::metadata("test/hardlinks/copy.txt").unwrap();
+    assert_eq!(original.ino(), copy.ino());
+    assert_eq!(original.nlink(), 2);
+
+    /* Extracting again replaces the existing link */
+    data.extract("test/hardlinks".to_string()).unwrap();
+
+    let files = data.extract_to_memory().unwrap();
+    assert_eq!(
+        files[std::path::Path::new("copy.txt")],
+        files[std::path::Path::new("test/1.txt")]
+    );
+    std::fs::remove_dir_all("test/hardlinks").unwrap();
+
+    let mut missing = TarFile::default();
+    missing.push(data.nodes()[1].clone());
+    assert!(matches!(
+        missing.extract("test/hardlinks".to_string()),
+        Err(TarError::MissingLinkTarget { .. })
+    ));
+    std::fs::remove_dir_all("test/hardlinks").unwrap();
+}
+
+#[test]
+fn archive_stats() {
+    let mut data = TarFile::open("test/corpus/python-pax.tar".to_string()).unwrap();
+    data.append("src/lib.rs".to_string()).unwrap();
+    data.append("test".to_string()).unwrap();
+    let stats = data.stats(2);
+
+    assert_eq!(stats.entries, 6);
+    assert_eq!(
+        stats.by_type,
+        vec![
+            (FileType::Normal, 4),
+            (FileType::Sym, 1),
+            (FileType::Dir, 1)
+        ]
+    );
+    assert_eq!(
+        stats.by_extension,
+        vec![("txt".to_string(), 3), ("rs".to_string(), 1)]
+    );
+    let lib = std::fs::metadata("src/lib.rs").unwrap().len();
+    assert_eq!(stats.total_size, 20 + lib);
+    assert_eq!(stats.largest.len(), 2);
+    assert_eq!(stats.largest[0].name, "src/lib.rs");
+
+    let mut written = 0;
+    for node in data.nodes() {
+        written += node.clone().write(&mut Vec::new()).unwrap();
+    }
+    assert_eq!(stats.stored_size as usize, written);
+}
+
+#[test]
+fn warning_verbosity() {
+    let long = format!("test/{}", "v".repeat(120));
+    std::fs::write(&long, b"").unwrap();
+    std::fs::write("test/29.txt", b"linked").unwrap();
+    let _ = std::fs::remove_file("test/30.txt");
+    std::fs::hard_link("test/29.txt", "test/30.txt").unwrap();
+    let absolute = format!("{}/test/29.txt", env!("CARGO_MANIFEST_DIR"));
+
+    let add = |verbosity| {
+        let options = ArchiveOptions::default()
+            .long_names(LongNamePolicy::Truncate)
+            .verbosity(verbosity);
+        let mut data = TarFile::default();
+        for name in [&long, &absolute, "test/30.txt", "src/lib.rs", "src/lib.rs"] {
+            data.append_with(name.to_string(), &options).unwrap();
+        }
+        data.warnings().to_vec()
+    };
+
+    assert!(add(Verbosity::Silent).is_empty());
+    let quiet = add(Verbosity::Quiet);
+    assert_eq!(quiet.len(), 1);
+    assert!(matches!(quiet[0], TarWarning::NameTruncated { .. }));
+    assert_eq!(add(Verbosity::Normal).len(), 3);
+    let verbose = add(Verbosity::Verbose);
+    assert_eq!(verbose.len(), 4);
+    assert!(verbose.contains(&TarWarning::HardLinked {
+        name: "test/30.txt".to_string(),
+        target: absolute.trim_start_matches('/').to_string(),
+    }));
+
+    std::fs::remove_file(&long).unwrap();
+    std::fs::remove_file("test/29.txt").unwrap();
+    std::fs::remove_file("test/30.txt").unwrap();
+}
+
+#[test]
+fn extract_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    std::fs::create_dir_all("test/symlinks/src/sub").unwrap();
+    std::fs::write("test/symlinks/src/a.txt", b"target").unwrap();
+    symlink("../a.txt", "test/symlinks/src/sub/up").unwrap();
+    symlink("missing", "test/symlinks/src/dangling").unwrap();
+    let mut data = TarFile::default();
+    data.append_dir_all("test/symlinks/src".to_string())
+        .unwrap();
+    let strip = ExtractOptions::default().strip_components(3);
+
+    data.extract_with("test/symlinks/out".to_string(), &strip)
+        .unwrap();
+    assert_eq!(
+        std::fs::read_link("test/symlinks/out/sub/up").unwrap(),
+        std::path::Path::new("../a.txt")
+    );
+    assert_eq!(
+        std::fs::read("test/symlinks/out/sub/up").unwrap(),
+        b"target"
+    );
+    assert!(std::fs::symlink_metadata("test/symlinks/out/dangling").is_ok());
+
+    let options = strip
+        .clone()
+        .symlinks(SymlinkPolicy::Copy)
+        .dangling_links(LinkPolicy::Skip);
+    data.extract_with("test/symlinks/copy".to_string(), &options)
+        .unwrap();
+    let copied = std::fs::symlink_metadata("test/symlinks/copy/sub/up").unwrap();
+    assert!(copied.file_type().is_file());
+    assert!(std::fs::symlink_metadata("test/symlinks/copy/dangling").is_err());
+
+    let options = strip.clone().dangling_links(LinkPolicy::Error);
+    assert!(matches!(
+        data.extract_with("test/symlinks/error".to_string(), &options),
+        Err(TarError::MissingLinkTarget { .. })
+    ));
+
+    /* Dropping one more component moves the link to the top of the destination */
+    let options = ExtractOptions::default().strip_components(4);
+    assert!(matches!(
+        data.extract_with("test/symlinks/escape".to_string(), &options),
+        Err(TarError::UnsafeLink { .. })
+    ));
+    let options = options.escaping_links(LinkPolicy::Skip);
+    data.extract_with("test/symlinks/escape".to_string(), &options)
+        .unwrap();
+    assert!(std::fs::symlink_metadata("test/symlinks/escape/up").is_err());
+
+    std::fs::remove_dir_all("test/symlinks").unwrap();
+}
+
+#[test]
+fn extract_chained_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    /* "q" stays inside by its name, but leads out through "s" once that exists */
+    std::fs::create_dir_all("test/chain/src").unwrap();
+    symlink(".", "test/chain/src/s").unwrap();
+    symlink("s/s/s/..", "test/chain/src/q").unwrap();
+    let mut data = TarFile::default();
+    data.append("test/chain/src/s".to_string()).unwrap();
+    data.append("test/chain/src/q".to_string()).unwrap();
+
+    let options = ExtractOptions::default().strip_components(3);
+    assert!(matches!(
+        data.extract_with("test/chain/out".to_string(), &options),
+        Err(TarError::UnsafeLink { .. })
+    ));
+    let options = options.escaping_links(LinkPolicy::Skip);
+    data.extract_with("test/chain/skip".to_string(), &options)
+        .unwrap();
+    assert!(std::fs::symlink_metadata("test/chain/skip/s").is_ok());
+    assert!(std::fs::symlink_metadata("test/chain/skip/q").is_err());
+
+    std::fs::remove_dir_all("test/chain").unwrap();
+}
+
+#[test]
+fn convert_formats() {
+    use deku::prelude::*;
+
+    let names = |data: &TarFile| {
+        data.list()
+            .into_iter()
+            .map(|e| (e.name, e.link_target))
+            .collect::<Vec<_>>()
+    };
+    let mut data = TarFile::open("test/corpus/gnu-longname.tar".to_string()).unwrap();
+    let expected = names(&data);
+
+    /* The long name of the corpus can be split into the prefix field */
+    let mut ustar = data.clone();
+    ustar.convert(TargetFormat::Ustar).unwrap();
+    assert_eq!(names(&ustar), expected);
+    for node in ustar.nodes() {
+        assert!(node.pax().is_empty());
+        assert_eq!(&node.header().to_bytes().unwrap()[257..265], b"ustar\x0000");
+        assert!(node.header().validate_checksum().unwrap());
+    }
+
+    let long = format!("test/{}", "c".repeat(120));
+    std::fs::write(&long, b"long").unwrap();
+    let options = ArchiveOptions::default().long_names(LongNamePolicy::Pax);
+    data.append_with(long.clone(), &options).unwrap();
+    let expected = names(&data);
+    assert!(matches!(
+        data.clone().convert(TargetFormat::Ustar),
+        Err(TarError::NameTooLong)
+    ));
+
+    for (format, extension) in [(TargetFormat::Pax, b'x'), (TargetFormat::Gnu, b'L')] {
+        let mut converted = data.clone();
+        converted.convert(format).unwrap();
+        converted
+            .write(&File::create("test/29.tar").unwrap())
+            .unwrap();
+
+        let raw = std::fs::read("test/29.tar").unwrap();
+        let types: Vec<u8> = raw.chunks(512).map(|b| b[156]).collect();
+        assert!(types.contains(&extension));
+        let reread = TarFile::open("test/29.tar".to_string()).unwrap();
+        assert_eq!(names(&reread), expected);
+    }
+
+    std::fs::remove_file(&long).unwrap();
+    std::fs::remove_file("test/29.tar").unwrap();
+}
+
+#[test]
+fn dereference_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    std::fs::create_dir_all("test/deref/sub").unwrap();
+    std::fs::write("test/deref/real.txt", b"real contents").unwrap();
+    symlink("real.txt", "test/deref/link.txt").unwrap();
+    symlink("..", "test/deref/sub/up").unwrap();
+
+    let mut data = TarFile::default();
+    data.append_dir_all("test/deref".to_string()).unwrap();
+    let link = data
+        .list()
+        .into_iter()
+        .find(|e| e.name == "test/deref/link.txt")
+        .unwrap();
+    assert_eq!(link.file_type, FileType::Sym);
+
+    let options = ArchiveOptions::default().dereference(true);
+    let mut data = TarFile::default();
+    data.append_dir_all_with("test/deref".to_string(), &options)
+        .unwrap();
+    let link = data
+        .list()
+        .into_iter()
+        .find(|e| e.name == "test/deref/link.txt")
+        .unwrap();
+    assert_eq!(link.file_type, FileType::Normal);
+    assert_eq!(link.size, 13);
+    let mut contents = Vec::new();
+    data.extract_entry("test/deref/link.txt".to_string(), &mut contents)
+        .unwrap();
+    assert_eq!(contents, b"real contents");
+    assert_eq!(
+        data.warnings(),
+        [TarWarning::FilesystemLoop("test/deref/sub/up".to_string())]
+    );
+
+    std::fs::remove_dir_all("test/deref").unwrap();
+}
+
+#[test]
+fn extract_special_files() {
+    use std::ffi::CString;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let fifo = CString::new("test/30.fifo").unwrap();
+    let _ = std::fs::remove_file("test/30.fifo");
+    assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o640) }, 0);
+    let mut data = TarFile::new("test/30.fifo".to_string()).unwrap();
+    std::fs::remove_file("test/30.fifo").unwrap();
+    data.append_with("/dev/null".to_string(), &ArchiveOptions::default())
+        .unwrap();
+
+    let warnings = data
+        .extract_with("test/special".to_string(), &ExtractOptions::default())
+        .unwrap()
+        .warnings;
+    let meta = std::fs::symlink_metadata("test/special/test/30.fifo").unwrap();
+    assert!(meta.file_type().is_fifo());
+    assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+
+    /* Devices can only be created with privileges */
+    match std::fs::symlink_metadata("test/special/dev/null") {
+        Ok(meta) => {
+            use std::os::unix::fs::MetadataExt;
+            assert!(meta.file_type().is_char_device());
+            assert_eq!(meta.rdev(), std::fs::metadata("/dev/null").unwrap().rdev());
+        }
+        Err(_) => assert_eq!(
+            warnings,
+            [TarWarning::SpecialSkipped("dev/null".to_string())]
+        ),
+    }
+
+    std::fs::remove_dir_all("test/special").unwrap();
+}
+
+#[test]
+fn read_timeouts() {
+    use std::io::Read;
+    use std::time::Duration;
+
+    /// Hands out the archive a few bytes at a time, pausing before each read.
+    struct Slow<R>(R, Duration);
+
+    impl<R: Read> Read for Slow<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(self.1);
+            let len = buf.len().min(512);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    let archive = std::fs::read("test/1.tar").unwrap();
+    let slow = Slow(&archive[..], Duration::from_millis(20));
+    let options = ReadOptions::default().timeout(Duration::from_millis(5));
+    assert!(matches!(
+        TarFile::open_stream(slow, &options),
+        Err(TarError::Timeout)
+    ));
+
+    let slow = Slow(&archive[..], Duration::from_millis(5));
+    let options = ReadOptions::default().deadline(Duration::from_millis(20));
+    assert!(matches!(
+        TarFile::open_stream(slow, &options),
+        Err(TarError::Timeout)
+    ));
+
+    let slow = Slow(&archive[..], Duration::from_millis(1));
+    let options = ReadOptions::default().timeout(Duration::from_secs(5));
+    assert_eq!(
+        TarFile::open_stream(slow, &options).unwrap().list().len(),
+        1
+    );
+
+    /* A peer that connects and then sends nothing */
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let _client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    let options = ReadOptions::default().timeout(Duration::from_millis(50));
+    assert!(matches!(
+        TarFile::open_tcp(stream, &options),
+        Err(TarError::Timeout)
+    ));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_dictionary() {
+    let _ = std::fs::remove_dir_all("test/dict");
+    std::fs::create_dir_all("test/dict/in").unwrap();
+    let samples: Vec<Vec<u8>> = (0..200)
+        .map(|i| {
+            format!(
+                "{{\"id\": {}, \"name\": \"user{}\", \"active\": {}, \"roles\": [\"reader\", \"writer\"]}}\n",
+                i,
+                i * 7,
+                i % 2 == 0
+            )
+            .into_bytes()
+        })
+        .collect();
+    for (i, sample) in samples.iter().enumerate() {
+        std::fs::write(format!("test/dict/in/{}.json", i), sample).unwrap();
+    }
+    let dictionary = zstd::dict::from_samples(&samples, 4096).unwrap();
+    let id = zstd::zstd_safe::get_dict_id_from_dict(&dictionary)
+        .unwrap()
+        .get();
+
+    let options = ArchiveOptions::default().zstd_dictionary(dictionary.clone());
+    crate::create(
+        "test/dict/in".to_string(),
+        "test/31.tar.zst".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        crate::tar::zstd_dictionary_id("test/31.tar.zst".to_string()).unwrap(),
+        Some(id)
+    );
+
+    let result = crate::extract(
+        "test/31.tar.zst".to_string(),
+        "test/dict/out".to_string(),
+        &ExtractOptions::default(),
+    );
+    assert!(matches!(result, Err(TarError::MissingDictionary(d)) if d == id));
+
+    let options = ExtractOptions::default()
+        .strip_components(2)
+        .zstd_dictionary(dictionary);
+    crate::extract(
+        "test/31.tar.zst".to_string(),
+        "test/dict/out".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read("test/dict/out/in/17.json").unwrap(),
+        samples[17]
+    );
+    std::fs::remove_dir_all("test/dict").unwrap();
+    std::fs::remove_file("test/31.tar.zst").unwrap();
+}
+
+#[test]
+fn archive_sparse_files() {
+    use std::os::unix::fs::FileExt;
+
+    let file = std::fs::File::create("test/32.img").unwrap();
+    file.set_len(1 << 20).unwrap();
+    file.write_all_at(b"head", 0).unwrap();
+    file.write_all_at(b"tail", (1 << 20) - 4).unwrap();
+    drop(file);
+    let contents = std::fs::read("test/32.img").unwrap();
+
+    let options = ArchiveOptions::default().sparse(true);
+    let mut data = TarFile::default();
+    data.append_with("test/32.img".to_string(), &options)
+        .unwrap();
+    let map = data.nodes()[0].sparse_map().unwrap().unwrap();
+    assert_eq!(map.real_size, 1 << 20);
+    assert!(map.segments.iter().map(|s| s.1).sum::<u64>() < 1 << 16);
+
+    for format in [SparseFormat::Pax1_0, SparseFormat::OldGnu] {
+        let mut archive = Vec::new();
+        let options = WriteOptions::default().sparse_format(format);
+        data.nodes()[0]
+            .clone()
+            .write_with(&mut archive, &options)
+            .unwrap();
+        assert!(archive.len() < 1 << 16);
+        archive.extend_from_slice(&[0; 1024]);
+
+        let read = TarFile::open_stream(&archive[..], &ReadOptions::default()).unwrap();
+        assert_eq!(read.list()[0].name, "test/32.img");
+        assert_eq!(read.nodes()[0].sparse_map().unwrap(), Some(map.clone()));
+        let files = read.extract_to_memory().unwrap();
+        let path = std::path::Path::new("test/32.img");
+        assert_eq!(files[path].contents, contents);
+    }
+
+    let mut data = TarFile::default();
+    data.append("test/32.img".to_string()).unwrap();
+    assert_eq!(data.nodes()[0].sparse_map().unwrap(), None);
+    std::fs::remove_file("test/32.img").unwrap();
+}
+
+#[test]
+fn digest_algorithms() {
+    let hex = |digest: Vec<u8>| -> String { digest.iter().map(|b| format!("{:02x}", b)).collect() };
+    assert_eq!(
+        hex(HashAlgorithm::Sha256.digest(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert!(hex(HashAlgorithm::Sha512.digest(b"abc")).starts_with("ddaf35a193617aba"));
+    #[cfg(feature = "blake3")]
+    assert_eq!(
+        hex(HashAlgorithm::Blake3.digest(b"abc")),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+
+    let data = TarFile::open("test/1.tar".to_string()).unwrap();
+    let contents = std::fs::read("test/1.txt").unwrap();
+    for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+        assert_eq!(HashAlgorithm::from_name(algorithm.name()), Some(algorithm));
+        let digest = algorithm.digest(&contents);
+        assert_eq!(data.nodes()[0].digest(algorithm).unwrap(), digest);
+        assert_eq!(
+            data.manifest(algorithm).unwrap(),
+            format!("{}  1.txt\n", hex(digest))
+        );
+    }
+}
+
+#[test]
+fn entry_signatures() {
+    use deku::prelude::*;
+
+    let jefe = HmacKey::new("jefe".to_string(), b"Jefe".to_vec());
+    let mac: String = jefe
+        .sign(b"what do ya want for nothing?")
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(
+        mac,
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    let key = HmacKey::new("release".to_string(), b"secret".to_vec());
+    let mut data = TarFile::new("test/1.txt".to_string()).unwrap();
+    data.append("src/lib.rs".to_string()).unwrap();
+    assert!(matches!(data.verify(&key), Err(TarError::Unsigned(_))));
+    data.sign(&key, HashAlgorithm::Sha512).unwrap();
+    data.verify(&key).unwrap();
+    assert!(matches!(
+        data.verify(&jefe),
+        Err(TarError::InvalidSignature(_))
+    ));
+
+    data.write(&File::create("test/33.tar").unwrap()).unwrap();
+    let options = ReadOptions::default().verifier(key.clone());
+    let read = TarFile::open_with("test/33.tar".to_string(), &options).unwrap();
+    let options = ExtractOptions::default().verifier(key.clone());
+    read.extract_with("test/signed".to_string(), &options)
+        .unwrap();
+    assert_eq!(
+        std::fs::read("test/signed/src/lib.rs").unwrap(),
+        std::fs::read("src/lib.rs").unwrap()
+    );
+    std::fs::remove_dir_all("test/signed").unwrap();
+
+    /* Making the file executable after signing it invalidates the signature */
+    let mut node = read.nodes()[1].clone();
+    let mut block = node.header().to_bytes().unwrap();
+    block[100..107].copy_from_slice(b"0000755");
+    let (_, header) = TarHeader::from_bytes((&block, 0)).unwrap();
+    *node.header_mut() = header;
+    assert!(matches!(
+        node.verify(&key),
+        Err(TarError::InvalidSignature(n)) if n == "src/lib.rs"
+    ));
+
+    let mut tampered = TarFile::default();
+    tampered.push(node);
+    tampered
+        .write(&File::create("test/33.tar").unwrap())
+        .unwrap();
+    let options = ReadOptions::default().verifier(key);
+    assert!(matches!(
+        TarFile::open_with("test/33.tar".to_string(), &options),
+        Err(TarError::InvalidSignature(_))
+    ));
+    std::fs::remove_file("test/33.tar").unwrap();
+}
+
+#[test]
+fn punch_holes_on_extract() {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut contents = vec![0u8; 1 << 20];
+    contents[..5].copy_from_slice(b"head\n");
+    contents[(1 << 20) - 5..].copy_from_slice(b"tail\n");
+    let mut data = TarFile::default();
+    data.push(TarNode::from_data("zeros.img".to_string(), &contents).unwrap());
+
+    let options = ExtractOptions::default().punch_holes(true);
+    data.extract_with("test/punch".to_string(), &options)
+        .unwrap();
+    let meta = std::fs::metadata("test/punch/zeros.img").unwrap();
+    assert_eq!(std::fs::read("test/punch/zeros.img").unwrap(), contents);
+    assert!(meta.blocks() * 512 < meta.len() / 4);
+
+    data.extract("test/punch".to_string()).unwrap();
+    let meta = std::fs::metadata("test/punch/zeros.img").unwrap();
+    assert!(meta.blocks() * 512 >= meta.len());
+    std::fs::remove_dir_all("test/punch").unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn archive_xattrs() {
+    let _ = std::fs::remove_dir_all("test/xattr");
+    std::fs::create_dir_all("test/xattr/in").unwrap();
+    std::fs::write("test/xattr/in/a.txt", b"attributes\n").unwrap();
+    let set = |name: &str, value: &[u8]| {
+        let path = std::ffi::CString::new("test/xattr/in/a.txt").unwrap();
+        let name = std::ffi::CString::new(name).unwrap();
+        let len = value.len();
+        unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr() as _, len, 0) }
+    };
+    if set("user.origin", b"web") != 0 {
+        /* The filesystem holding the tests doesn't support extended attributes */
+        std::fs::remove_dir_all("test/xattr").unwrap();
+        return;
+    }
+    assert_eq!(set("user.binary", b"\x00\xff\n"), 0);
+    let expected = vec![
+        ("user.binary".to_string(), b"\x00\xff\n".to_vec()),
+        ("user.origin".to_string(), b"web".to_vec()),
+    ];
+
+    let mut data = TarFile::default();
+    data.append("test/xattr/in/a.txt".to_string()).unwrap();
+    assert!(data.nodes()[0].xattrs().is_empty());
+
+    let options = ArchiveOptions::default().xattrs(true);
+    let mut data = TarFile::default();
+    data.append_with("test/xattr/in/a.txt".to_string(), &options)
+        .unwrap();
+    assert_eq!(data.nodes()[0].xattrs(), expected);
+
+    data.write(&File::create("test/34.tar").unwrap()).unwrap();
+    let data = TarFile::open("test/34.tar".to_string()).unwrap();
+    assert_eq!(data.nodes()[0].xattrs(), expected);
+
+    let extract = ExtractOptions::default().strip_components(3).xattrs(true);
+    let warnings = data
+        .extract_with("test/xattr/out".to_string(), &extract)
+        .unwrap()
+        .warnings;
+    assert!(warnings.is_empty());
+    let mut restored = TarFile::default();
+    restored
+        .append_with("test/xattr/out/a.txt".to_string(), &options)
+        .unwrap();
+    assert_eq!(restored.nodes()[0].xattrs(), expected);
+
+    std::fs::remove_dir_all("test/xattr").unwrap();
+    std::fs::remove_file("test/34.tar").unwrap();
+}
+
+#[test]
+fn enforcement_profiles() {
+    use deku::prelude::*;
+    use std::os::unix::fs::symlink;
+
+    let _ = std::fs::remove_dir_all("test/profile");
+    std::fs::create_dir_all("test/profile/dir").unwrap();
+    std::fs::write("test/profile/dir/plain", b"plain\n").unwrap();
+    std::fs::write("test/profile/setuid", b"#!/bin/sh\n").unwrap();
+    symlink("dir/plain", "test/profile/inside").unwrap();
+    symlink("/etc/passwd", "test/profile/absolute").unwrap();
+    symlink("../../../../secret", "test/profile/dir/climbing").unwrap();
+    let fifo = std::ffi::CString::new("test/profile/fifo").unwrap();
+    assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+
+    let node = |name: &str| {
+        let mut data = TarFile::default();
+        data.append(format!("test/profile/{}", name)).unwrap();
+        let mut node = data.nodes()[0].clone();
+        if name == "setuid" {
+            let mut block = node.header().to_bytes().unwrap();
+            block[100..107].copy_from_slice(b"0004755");
+            *node.header_mut() = TarHeader::from_bytes((&block, 0)).unwrap().1;
+        }
+        node
+    };
+    let reason = |profile: Profile, name: &str| match profile.check(&node(name)) {
+        Ok(()) => None,
+        Err(TarError::Disallowed { reason, .. }) => Some(reason),
+        Err(e) => panic!("{}", e),
+    };
+
+    for name in ["dir", "dir/plain", "setuid", "inside", "absolute", "fifo"] {
+        assert_eq!(reason(Profile::Unrestricted, name), None);
+    }
+    assert_eq!(reason(Profile::NoSpecials, "dir/plain"), None);
+    assert_eq!(reason(Profile::NoSpecials, "inside"), None);
+    assert_eq!(reason(Profile::NoSpecials, "fifo").unwrap(), "FIFO");
+    assert_eq!(
+        reason(Profile::NoSpecials, "setuid").unwrap(),
+        "setuid or setgid"
+    );
+    assert_eq!(
+        reason(Profile::NoSpecials, "absolute").unwrap(),
+        "link target outside the archive"
+    );
+    assert!(reason(Profile::NoSpecials, "dir/climbing").is_some());
+    assert_eq!(reason(Profile::RegularFilesOnly, "dir"), None);
+    assert_eq!(reason(Profile::RegularFilesOnly, "inside").unwrap(), "link");
+
+    /* Nothing is extracted from an archive with a disallowed entry */
+    let mut data = TarFile::default();
+    data.push(node("dir/plain"));
+    data.push(node("fifo"));
+    let options = ExtractOptions::default().profile(Profile::NoSpecials);
+    assert!(matches!(
+        data.extract_with("test/profile/out".to_string(), &options),
+        Err(TarError::Disallowed { .. })
+    ));
+    assert!(!std::path::Path::new("test/profile/out").exists());
+
+    data.write(&File::create("test/35.tar").unwrap()).unwrap();
+    let options = ReadOptions::default().profile(Profile::NoSpecials);
+    assert!(matches!(
+        TarFile::open_with("test/35.tar".to_string(), &options),
+        Err(TarError::Disallowed { .. })
+    ));
+    std::fs::remove_file("test/35.tar").unwrap();
+    std::fs::remove_dir_all("test/profile").unwrap();
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn decompression_ratio_guard() {
+    let _ = std::fs::remove_dir_all("test/bomb");
+    std::fs::create_dir_all("test/bomb/in").unwrap();
+    std::fs::write("test/bomb/in/zeros.bin", vec![0u8; 8 << 20]).unwrap();
+    crate::create(
+        "test/bomb/in".to_string(),
+        "test/36.tar.gz".to_string(),
+        &ArchiveOptions::default(),
+    )
+    .unwrap();
+
+    let options = ExtractOptions::default().max_compression_ratio(100);
+    let result = crate::extract(
+        "test/36.tar.gz".to_string(),
+        "test/bomb/out".to_string(),
+        &options,
+    );
+    assert!(matches!(result, Err(TarError::CompressionRatio(100))));
+    assert!(!std::path::Path::new("test/bomb/out/in/zeros.bin").exists());
+
+    let options = ExtractOptions::default()
+        .strip_components(2)
+        .max_compression_ratio(10_000);
+    crate::extract(
+        "test/36.tar.gz".to_string(),
+        "test/bomb/out".to_string(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::metadata("test/bomb/out/in/zeros.bin")
+            .unwrap()
+            .len(),
+        8 << 20
+    );
+
+    std::fs::remove_dir_all("test/bomb").unwrap();
+    std::fs::remove_file("test/36.tar.gz").unwrap();
+}
+
+#[test]
+fn empty_numeric_fields() {
+    for field in ["mode", "uid", "gid", "size", "mtime"] {
+        let data = TarFile::open(format!("test/corpus/blank-{}.tar", field)).unwrap();
+        assert_eq!(
+            data.warnings(),
+            [TarWarning::EmptyField {
+                name: "blank.txt".to_string(),
+                field: field.to_string(),
+            }],
+            "{}",
+            field
+        );
+
+        let entry = &data.list()[0];
+        let value = match field {
+            "mode" => entry.mode as u64,
+            "uid" => entry.uid,
+            "gid" => entry.gid,
+            "size" => entry.size,
+            _ => entry.mtime as u64,
+        };
+        assert_eq!(value, 0, "{}", field);
+
+        let expected: &[u8] = if field == "size" { b"" } else { b"hello\n" };
+        let files = data.extract_to_memory().unwrap();
+        assert_eq!(
+            files[std::path::Path::new("blank.txt")].contents,
+            expected,
+            "{}",
+            field
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn archive_capabilities() {
+    let _ = std::fs::remove_dir_all("test/caps");
+    std::fs::create_dir_all("test/caps/in").unwrap();
+    std::fs::write("test/caps/in/ping", b"#!/bin/sh\n").unwrap();
+    /* cap_net_raw+ep, as set on ping */
+    let caps = b"\x01\x00\x00\x02\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+    let set = |name: &str, value: &[u8]| {
+        let path = std::ffi::CString::new("test/caps/in/ping").unwrap();
+        let name = std::ffi::CString::new(name).unwrap();
+        let len = value.len();
+        unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr() as _, len, 0) }
+    };
+    if set("security.capability", caps) != 0 || set("user.origin", b"web") != 0 {
+        /* Setting capabilities needs CAP_SETFCAP and a filesystem supporting them */
+        std::fs::remove_dir_all("test/caps").unwrap();
+        return;
+    }
+    let expected = vec![("security.capability".to_string(), caps.to_vec())];
+
+    let options = ArchiveOptions::default().capabilities(true);
+    let mut data = TarFile::default();
+    data.append_with("test/caps/in/ping".to_string(), &options)
+        .unwrap();
+    assert_eq!(data.nodes()[0].xattrs(), expected);
+
+    /* Only the capabilities are restored without xattrs */
+    let mut node = data.nodes()[0].clone();
+    node.push_pax(PaxRecord::new("SCHILY.xattr.user.extra", "x"));
+    let mut data = TarFile::default();
+    data.push(node);
+    let extract = ExtractOptions::default()
+        .strip_components(3)
+        .capabilities(true);
+    let warnings = data
+        .extract_with("test/caps/out".to_string(), &extract)
+        .unwrap()
+        .warnings;
+    assert!(warnings.is_empty());
+    let mut restored = TarFile::default();
+    restored
+        .append_with(
+            "test/caps/out/ping".to_string(),
+            &ArchiveOptions::default().xattrs(true),
+        )
+        .unwrap();
+    assert_eq!(restored.nodes()[0].xattrs(), expected);
+
+    std::fs::remove_dir_all("test/caps").unwrap();
+}
+
+#[test]
+fn stable_entry_indices() {
+    let mut data = TarFile::new("src/tar.rs".to_string()).unwrap();
+    data.append("Cargo.toml".to_string()).unwrap();
+    data.append("test/1.txt".to_string()).unwrap();
+    data.append_with(
+        "src/tar.rs".to_string(),
+        &ArchiveOptions::default().duplicates(DuplicatePolicy::KeepBoth),
+    )
+    .unwrap();
+    data.write(&File::create("test/37.tar").unwrap()).unwrap();
+
+    /* Entries are read back in the order they were written */
+    let mut data = TarFile::open("test/37.tar".to_string()).unwrap();
+    let names: Vec<(usize, String)> = data.list().into_iter().map(|e| (e.index, e.name)).collect();
+    assert_eq!(
+        names,
+        vec![
+            (0, "src/tar.rs".to_string()),
+            (1, "Cargo.toml".to_string()),
+            (2, "test/1.txt".to_string()),
+            (3, "src/tar.rs".to_string()),
+        ]
+    );
+    let index: Vec<usize> = data.index().iter().map(|l| l.index).collect();
+    assert_eq!(index, vec![0, 1, 2, 3]);
+    assert_eq!(data.position_of("test/1.txt"), Some(2));
+    assert_eq!(
+        data.positions_of("src/tar.rs").collect::<Vec<_>>(),
+        vec![0, 3]
+    );
+    assert_eq!(data.nodes()[2].name(), "test/1.txt");
+
+    /* Indices passed to `retain` are those before any entry was removed */
+    let mut seen = Vec::new();
+    data.retain(|e| {
+        seen.push(e.index);
+        e.name != "Cargo.toml"
+    });
+    assert_eq!(seen, vec![0, 1, 2, 3]);
+    assert_eq!(data.position_of("test/1.txt"), Some(1));
+    assert_eq!(data.list()[1].index, 1);
+
+    std::fs::remove_file("test/37.tar").unwrap();
+}
+
+#[test]
+fn large_owner_ids() {
+    use deku::prelude::*;
+
+    let _ = std::fs::remove_dir_all("test/ids");
+    std::fs::create_dir_all("test/ids").unwrap();
+    std::fs::write("test/ids/big.txt", b"dynamic user\n").unwrap();
+    if std::os::unix::fs::chown("test/ids/big.txt", Some(3_000_000), Some(4_000_000_000)).is_err() {
+        /* Changing the owner needs root */
+        std::fs::remove_dir_all("test/ids").unwrap();
+        return;
+    }
+
+    let mut data = TarFile::new("test/ids/big.txt".to_string()).unwrap();
+    let node = &data.nodes()[0];
+    assert_eq!(node.header().to_bytes().unwrap()[108], 0x80);
+    let records: Vec<(&str, &[u8])> = node.pax().iter().map(|r| (r.key(), r.value())).collect();
+    assert!(records.contains(&("uid", b"3000000")));
+    assert!(records.contains(&("gid", b"4000000000")));
+    data.write(&File::create("test/38.tar").unwrap()).unwrap();
+
+    let read = TarFile::open("test/38.tar".to_string()).unwrap();
+    let entry = &read.list()[0];
+    assert_eq!((entry.uid, entry.gid), (3_000_000, 4_000_000_000));
+
+    /* GNU archives have no PAX records, leaving only the base-256 fields */
+    data = read;
+    data.convert(TargetFormat::Gnu).unwrap();
+    assert!(data.nodes()[0].pax().is_empty());
+    let entry = &data.list()[0];
+    assert_eq!((entry.uid, entry.gid), (3_000_000, 4_000_000_000));
+    assert!(data.convert(TargetFormat::Ustar).is_err());
+
+    std::fs::copy("test/1.tar", "test/39.tar").unwrap();
+    let patch = HeaderPatch::default().uid(1 << 40);
+    TarFile::patch_in_place("test/39.tar".to_string(), "1.txt".to_string(), &patch).unwrap();
+    let patched = TarFile::open("test/39.tar".to_string()).unwrap();
+    assert_eq!(patched.list()[0].uid, 1 << 40);
+
+    std::fs::remove_dir_all("test/ids").unwrap();
+    std::fs::remove_file("test/38.tar").unwrap();
+    std::fs::remove_file("test/39.tar").unwrap();
+}
+
+#[test]
+fn compat_fixtures() {
+    let link = Some("../hello.txt".to_string());
+    let expected = vec![
+        ("hello.txt".to_string(), FileType::Normal, None),
+        ("block.bin".to_string(), FileType::Normal, None),
+        ("empty.txt".to_string(), FileType::Normal, None),
+        ("dir".to_string(), FileType::Dir, None),
+        ("dir/nested.txt".to_string(), FileType::Normal, None),
+        ("dir/link".to_string(), FileType::Sym, link),
+    ];
+    let names: Vec<String> = expected.iter().map(|e| e.0.clone()).collect();
+
+    for version in ["0.2.1"] {
+        let path = format!("test/compat/minitar-{}.tar", version);
+        let data = TarFile::open(path.clone()).unwrap();
+        let entries: Vec<_> = data
+            .list()
+            .into_iter()
+            .map(|e| (e.name, e.file_type, e.link_target))
+            .collect();
+        assert_eq!(entries, expected, "{}", version);
+        assert!(data.list().iter().all(|e| e.mtime == 1613849820));
+        assert_eq!(
+            TarFile::list_from(File::open(&path).unwrap()).unwrap(),
+            names,
+            "{}",
+            version
+        );
+
+        let _ = std::fs::remove_dir_all("test/compat-out");
+        data.extract_with("test/compat-out".to_string(), &ExtractOptions::default())
+            .unwrap();
+        let read = |name: &str| std::fs::read(format!("test/compat-out/{}", name)).unwrap();
+        assert_eq!(read("hello.txt"), b"hello\n");
+        assert_eq!(read("block.bin"), vec![b'a'; 512]);
+        assert!(read("empty.txt").is_empty());
+        assert_eq!(read("dir/nested.txt"), b"nested\n");
+        assert_eq!(
+            std::fs::read_link("test/compat-out/dir/link").unwrap(),
+            std::path::Path::new("../hello.txt")
+        );
+        std::fs::remove_dir_all("test/compat-out").unwrap();
+    }
+}
+
+#[test]
+fn gnu_tar_reads_output() {
+    let version = match std::process::Command::new("tar").arg("--version").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).to_string(),
+        Err(_) => return,
+    };
+    if !version.contains("GNU tar") {
+        return;
+    }
+
+    let _ = std::fs::remove_dir_all("test/gnu");
+    let long = format!("{}/{}.txt", "d".repeat(60), "n".repeat(60));
+    std::fs::create_dir_all(format!("test/gnu/in/{}", "d".repeat(60))).unwrap();
+    std::fs::write(format!("test/gnu/in/{}", long), b"long\n").unwrap();
+    std::fs::write("test/gnu/in/empty.txt", b"").unwrap();
+    std::os::unix::fs::symlink("empty.txt", "test/gnu/in/link").unwrap();
+    let mut sparse = File::create("test/gnu/in/sparse.img").unwrap();
+    sparse.set_len(1 << 20).unwrap();
+    std::io::Write::write_all(&mut sparse, b"head").unwrap();
+
+    let options = ArchiveOptions::default()
+        .long_names(LongNamePolicy::Pax)
+        .sparse(true);
+    let mut data = TarFile::default();
+    data.append_dir_all_with("test/gnu/in".to_string(), &options)
+        .unwrap();
+    let mut gnu = data.clone();
+    gnu.convert(TargetFormat::Gnu).unwrap();
+
+    for (archive, data) in [("test/40.tar", data), ("test/41.tar", gnu)] {
+        data.write(&File::create(archive).unwrap()).unwrap();
+        let _ = std::fs::remove_dir_all("test/gnu/out");
+        std::fs::create_dir_all("test/gnu/out").unwrap();
+        let output = std::process::Command::new("tar")
+            .args(["-xf", archive, "-C", "test/gnu/out", "--strip-components=3"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", archive);
+        assert!(
+            output.stderr.is_empty(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let read = |name: &str| std::fs::read(format!("test/gnu/out/{}", name)).unwrap();
+        assert_eq!(read(&long), b"long\n", "{}", archive);
+        assert!(read("empty.txt").is_empty());
+        assert_eq!(
+            std::fs::read_link("test/gnu/out/link").unwrap(),
+            std::path::Path::new("empty.txt")
+        );
+        let mut expected = vec![0u8; 1 << 20];
+        expected[..4].copy_from_slice(b"head");
+        assert_eq!(read("sparse.img"), expected, "{}", archive);
+        std::fs::remove_file(archive).unwrap();
+    }
+    std::fs::remove_dir_all("test/gnu").unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn system_owner_names() {
+    assert_eq!(SystemResolver.user_name(0).as_deref(), Some("root"));
+    assert_eq!(SystemResolver.group_name(0).as_deref(), Some("root"));
+    assert_eq!(SystemResolver.user_name(u32::MAX - 7), None);
+    assert_eq!(SystemResolver.user_id("root"), Some(0));
+    assert_eq!(SystemResolver.group_id("root"), Some(0));
+    assert_eq!(SystemResolver.user_id("no-such-user-minitar"), None);
+
+    /* The resolver agrees with the passwd database on every user it lists */
+    let passwd = std::fs::read_to_string("/etc/passwd").unwrap();
+    for fields in passwd.lines().map(|l| l.split(':').collect::<Vec<_>>()) {
+        if let (Some(name), Some(Ok(uid))) = (fields.first(), fields.get(2).map(|u| u.parse())) {
+            assert_eq!(SystemResolver.user_name(uid).as_deref(), Some(*name));
+            assert_eq!(SystemResolver.user_id(name), Some(uid));
+        }
+    }
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+#[test]
+fn io_uring_batches() {
+    use std::os::unix::fs::MetadataExt;
+
+    let _ = std::fs::remove_dir_all("test/uring");
+    let mut nodes = (0..100)
+        .map(|i| {
+            let data = i.to_string().repeat(i);
+            TarNode::from_data(format!("dir/{}.txt", i), data.as_bytes()).unwrap()
+        })
+        .collect::<Vec<TarNode>>();
+    nodes.push(TarNode::from_data("dir/5.txt".to_string(), b"replaced").unwrap());
+    nodes.push(TarNode::from_data("big.bin".to_string(), &vec![7u8; 17 << 20]).unwrap());
+    let data: TarFile = nodes.into_iter().collect();
+
+    /* Every file gets its data and metadata, whether or not the kernel allows io_uring */
+    let options = ExtractOptions::default().io_uring(true);
+    data.extract_with("test/uring".to_string(), &options)
+        .unwrap();
+    for (path, entry) in data.extract_to_memory().unwrap() {
+        let path = std::path::Path::new("test/uring").join(path);
+        assert_eq!(std::fs::read(&path).unwrap(), entry.contents);
+        assert_eq!(std::fs::metadata(&path).unwrap().mtime(), entry.mtime);
+    }
+
+    std::fs::hard_link("test/uring/dir/1.txt", "test/uring/link.txt").unwrap();
+    let mut plain = TarFile::default();
+    plain.append_dir_all("test/uring".to_string()).unwrap();
+    let mut batched = TarFile::default();
+    let options = ArchiveOptions::default().io_uring(true);
+    batched
+        .append_dir_all_with("test/uring".to_string(), &options)
+        .unwrap();
+    assert_eq!(batched.list(), plain.list());
+    for (a, b) in batched.nodes().iter().zip(plain.nodes()) {
+        assert_eq!(a.data().unwrap(), b.data().unwrap());
+    }
+
+    std::fs::remove_dir_all("test/uring").unwrap();
+}
+
+#[cfg(all(feature = "gzip", feature = "zstd"))]
+#[test]
+fn parallel_compression() {
+    use std::io::Read;
+
+    let _ = std::fs::remove_dir_all("test/parallel");
+    std::fs::create_dir_all("test/parallel/in").unwrap();
+    for i in 0..3u32 {
+        let data = (0..1_500_000u32)
+            .map(|n| (n.wrapping_mul(2654435761).wrapping_add(i) >> 24) as u8)
+            .collect::<Vec<u8>>();
+        std::fs::write(format!("test/parallel/in/{}.bin", i), data).unwrap();
+    }
+
+    for dest in ["test/46.tar.gz", "test/46.tar.zst"] {
+        let options = ArchiveOptions::default().compression_threads(4);
+        crate::create("test/parallel/in".to_string(), dest.to_string(), &options).unwrap();
+        let options = ExtractOptions::default().strip_components(3);
+        crate::extract(dest.to_string(), "test/parallel/out".to_string(), &options).unwrap();
+        for i in 0..3 {
+            assert_eq!(
+                std::fs::read(format!("test/parallel/out/{}.bin", i)).unwrap(),
+                std::fs::read(format!("test/parallel/in/{}.bin", i)).unwrap()
+            );
+        }
+        std::fs::remove_dir_all("test/parallel/out").unwrap();
+    }
+
+    /* Gzip is written as a series of members, the first holding exactly one chunk */
+    let mut first = Vec::new();
+    flate2::read::GzDecoder::new(File::open("test/46.tar.gz").unwrap())
+        .read_to_end(&mut first)
+        .unwrap();
+    assert_eq!(first.len(), 1 << 20);
+
+    std::fs::remove_dir_all("test/parallel").unwrap();
+    std::fs::remove_file("test/46.tar.gz").unwrap();
+    std::fs::remove_file("test/46.tar.zst").unwrap();
+}
+
+#[test]
+fn parallel_reads() {
+    let _ = std::fs::remove_dir_all("test/threads");
+    std::fs::create_dir_all("test/threads/sub").unwrap();
+    for i in 0..200 {
+        let dir = if i % 3 == 0 {
+            "test/threads/sub"
+        } else {
+            "test/threads"
+        };
+        std::fs::write(format!("{}/{}.txt", dir, i), i.to_string().repeat(i)).unwrap();
+    }
+    std::fs::hard_link("test/threads/1.txt", "test/threads/sub/link.txt").unwrap();
+
+    let mut serial = TarFile::default();
+    serial.append_dir_all("test/threads".to_string()).unwrap();
+    for threads in [0, 4] {
+        let options = ArchiveOptions::default().read_threads(threads);
+        let mut parallel = TarFile::default();
+        parallel
+            .append_dir_all_with("test/threads".to_string(), &options)
+            .unwrap();
+        assert_eq!(parallel.list(), serial.list());
+        for (a, b) in parallel.nodes().iter().zip(serial.nodes()) {
+            assert_eq!(a.data().unwrap(), b.data().unwrap());
+        }
+        assert_eq!(parallel.warnings(), serial.warnings());
+    }
+    assert!(serial.list().iter().any(|e| e.file_type == FileType::Hard));
+
+    std::fs::remove_dir_all("test/threads").unwrap();
+}
+
+#[test]
+fn cancellation() {
+    let _ = std::fs::remove_dir_all("test/cancel");
+    std::fs::create_dir_all("test/cancel/in").unwrap();
+    for name in ["a", "b", "c", "d"] {
+        std::fs::write(format!("test/cancel/in/{}.txt", name), name).unwrap();
+    }
+    std::os::unix::fs::symlink("a.txt", "test/cancel/in/link").unwrap();
+    let mut data = TarFile::default();
+    data.append_dir_all("test/cancel/in".to_string()).unwrap();
+
+    /* Entries before the cancellation are extracted, and nothing after it, including the symlink */
+    let token = CancelToken::new();
+    let canceller = token.clone();
+    let options = ExtractOptions::default()
+        .strip_components(3)
+        .transform(Transform::new(move |name| {
+            if name == "c.txt" {
+                canceller.cancel();
+            }
+            name.to_string()
+        }))
+        .cancel(token.clone());
+    let result = data.extract_with("test/cancel/out".to_string(), &options);
+    assert!(matches!(result, Err(TarError::Cancelled)));
+    assert_eq!(std::fs::read("test/cancel/out/b.txt").unwrap(), b"b");
+    assert!(!std::path::Path::new("test/cancel/out/d.txt").exists());
+    assert!(std::fs::symlink_metadata("test/cancel/out/link").is_err());
+
+    let options = WriteOptions::default().cancel(token.clone());
+    assert!(matches!(
+        data.clone()
+            .write_with(&File::create("test/47.tar").unwrap(), &options),
+        Err(TarError::Cancelled)
+    ));
+    assert_eq!(std::fs::metadata("test/47.tar").unwrap().len(), 0);
+
+    data.write(&File::create("test/47.tar").unwrap()).unwrap();
+    let options = ReadOptions::default().cancel(token);
+    for options in [options.clone(), options.lazy_data(true)] {
+        assert!(matches!(
+            TarFile::open_with("test/47.tar".to_string(), &options),
+            Err(TarError::Cancelled)
+        ));
+    }
+
+    std::fs::remove_dir_all("test/cancel").unwrap();
+    std::fs::remove_file("test/47.tar").unwrap();
+}
+
+#[test]
+fn rate_limits() {
+    use std::time::{Duration, Instant};
+
+    let _ = std::fs::remove_dir_all("test/throttle");
+    let data: TarFile = vec![TarNode::from_data("a.bin".to_string(), &vec![3u8; 100_000]).unwrap()]
+        .into_iter()
+        .collect();
+    let rate = 1 << 20;
+
+    /* Each operation moves about 100 KiB, which takes at least 90ms at 1 MiB a second */
+    let start = Instant::now();
+    let options = WriteOptions::default().rate_limit(rate);
+    data.clone()
+        .write_with(&File::create("test/48.tar").unwrap(), &options)
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+
+    let start = Instant::now();
+    let options = ReadOptions::default().rate_limit(rate);
+    let read = TarFile::open_with("test/48.tar".to_string(), &options).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+    assert_eq!(read.list(), data.list());
+
+    let start = Instant::now();
+    let options = ExtractOptions::default().rate_limit(rate);
+    read.extract_with("test/throttle".to_string(), &options)
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+    assert_eq!(
+        std::fs::read("test/throttle/a.bin").unwrap(),
+        vec![3u8; 100_000]
+    );
+
+    std::fs::remove_dir_all("test/throttle").unwrap();
+    std::fs::remove_file("test/48.tar").unwrap();
+}
+
+#[test]
+fn operation_stats() {
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_dir_all("test/opstats");
+    std::fs::create_dir_all("test/opstats/in/sub").unwrap();
+    std::fs::write("test/opstats/in/a.txt", b"hello").unwrap();
+    std::fs::write("test/opstats/in/sub/b.txt", vec![1u8; 600]).unwrap();
+    std::fs::write("test/opstats/in/c.tmp", b"scratch").unwrap();
+    std::os::unix::fs::symlink("a.txt", "test/opstats/in/link").unwrap();
+    let _listener = UnixListener::bind("test/opstats/in/sock").unwrap();
+
+    let options = ArchiveOptions::default()
+        .exclude("*.tmp".to_string())
+        .verbosity(Verbosity::Silent);
+    let mut data = TarFile::default();
+    let stats = data
+        .append_dir_all_with("test/opstats/in".to_string(), &options)
+        .unwrap();
+    assert_eq!(
+        stats.by_type,
+        [
+            (FileType::Dir, 2),
+            (FileType::Normal, 2),
+            (FileType::Sym, 1)
+        ]
+    );
+    assert_eq!(stats.data_bytes, 605);
+    assert_eq!(
+        stats.skipped,
+        [
+            ("test/opstats/in/c.tmp".to_string(), SkipReason::Excluded),
+            ("test/opstats/in/sock".to_string(), SkipReason::Socket),
+        ]
+    );
+    assert!(stats.warnings.is_empty());
+
+    /* Five headers, 605 bytes of data padded to three blocks, and the end of the archive */
+    let stats = data
+        .clone()
+        .write(&File::create("test/49.tar").unwrap())
+        .unwrap();
+    assert_eq!(stats.entries(), 5);
+    assert_eq!((stats.header_bytes, stats.data_bytes), (2560, 605));
+    assert_eq!(stats.total_bytes(), 10240);
+    assert_eq!(
+        stats.total_bytes(),
+        std::fs::metadata("test/49.tar").unwrap().len()
+    );
+
+    /* Stripping the leading components leaves nothing of the top directory's name */
+    let options = ExtractOptions::default()
+        .strip_components(3)
+        .symlinks(SymlinkPolicy::Skip);
+    let stats = data
+        .extract_with("test/opstats/out".to_string(), &options)
+        .unwrap();
+    assert_eq!(stats.entries(), 3);
+    assert_eq!(stats.data_bytes, 605);
+    assert_eq!(
+        stats.skipped,
+        [
+            ("test/opstats/in".to_string(), SkipReason::NoName),
+            ("test/opstats/in/link".to_string(), SkipReason::Link),
+        ]
+    );
+
+    std::fs::remove_dir_all("test/opstats").unwrap();
+    std::fs::remove_file("test/49.tar").unwrap();
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_events() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /* Records each span and event as its name or message followed by its fields */
+    struct Collector {
+        lines: Arc<Mutex<Vec<String>>>,
+        next: AtomicU64,
+    }
+    struct Line(String);
+    impl Visit for Line {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0 += &format!(" {}={:?}", field.name(), value);
+        }
+    }
+    impl Subscriber for Collector {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut line = Line(format!("span {}", span.metadata().name()));
+            span.record(&mut line);
+            self.lines.lock().unwrap().push(line.0);
+            Id::from_u64(self.next.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut line = Line(event.metadata().level().to_string());
+            event.record(&mut line);
+            self.lines.lock().unwrap().push(line.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let _ = std::fs::remove_dir_all("test/tracing");
+    std::fs::create_dir_all("test/tracing/in").unwrap();
+    std::fs::write("test/tracing/in/a.txt", b"hello").unwrap();
+    let _listener = std::os::unix::net::UnixListener::bind("test/tracing/in/sock").unwrap();
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let collector = Collector {
+        lines: lines.clone(),
+        next: AtomicU64::new(0),
+    };
+    tracing::subscriber::with_default(collector, || {
+        let mut data = TarFile::default();
+        data.append_dir_all("test/tracing/in".to_string()).unwrap();
+        data.write(&File::create("test/50.tar").unwrap()).unwrap();
+        TarFile::open("test/50.tar".to_string())
+            .unwrap()
+            .extract("test/tracing/out".to_string())
+            .unwrap();
+    });
+
+    let lines = lines.lock().unwrap();
+    let has = |parts: &[&str]| lines.iter().any(|l| parts.iter().all(|p| l.contains(p)));
+    assert!(has(&["span append", "path=test/tracing/in"]));
+    assert!(has(&[
+        "DEBUG",
+        "added entry",
+        "name=test/tracing/in/a.txt",
+        "size=5"
+    ]));
+    assert!(has(&["DEBUG", "skipped entry", "reason=Socket"]));
+    assert!(has(&["WARN", "test/tracing/in/sock: socket ignored"]));
+    assert!(has(&["span write", "entries=2"]));
+    assert!(has(&["span open", "path=test/50.tar"]));
+    assert!(has(&["DEBUG", "read entry", "name=test/tracing/in/a.txt"]));
+    assert!(has(&["span extract", "dest=test/tracing/out"]));
+    assert!(has(&[
+        "DEBUG",
+        "extracting entry",
+        "name=test/tracing/in/a.txt"
+    ]));
+    assert!(has(&["INFO", "finished", "entries=2", "data_bytes=5"]));
+
+    std::fs::remove_dir_all("test/tracing").unwrap();
+    std::fs::remove_file("test/50.tar").unwrap();
 }
@@ -0,0 +1,47 @@
+#!/bin/sh
+# Write the archive of a released minitar version, for example `./generate.sh 0.2.1`. Requires cargo and
+# network access to fetch that release from crates.io.
+set -e
+version=$1
+[ -n "$version" ] || { echo "usage: $0 <version>" >&2; exit 1; }
+
+out=$(cd "$(dirname "$0")" && pwd)
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+mkdir -p "$work/src" "$work/in/dir"
+cd "$work/in"
+printf 'hello\n' > hello.txt
+head -c 512 /dev/zero | tr '\0' a > block.bin
+: > empty.txt
+printf 'nested\n' > dir/nested.txt
+ln -s ../hello.txt dir/link
+touch -h -d '2021-02-20 19:37:00 UTC' hello.txt block.bin empty.txt dir/nested.txt dir/link dir
+
+cat > "$work/Cargo.toml" <<TOML
+[package]
+name = "compat"
+version = "0.0.0"
+edition = "2018"
+
+[dependencies]
+minitar = "=$version"
+TOML
+
+# Only the API of the first release is used, so the program builds against every version
+cat > "$work/src/main.rs" <<'RS'
+use minitar::tar::TarFile;
+use std::fs::File;
+
+fn main() {
+    let out = std::env::args().nth(1).unwrap();
+    let mut data = TarFile::new("hello.txt".to_string()).unwrap();
+    for name in ["block.bin", "empty.txt", "dir", "dir/nested.txt", "dir/link"] {
+        data.append(name.to_string()).unwrap();
+    }
+    data.write(&File::create(out).unwrap()).unwrap();
+}
+RS
+
+cargo build -q --manifest-path "$work/Cargo.toml"
+"$work/target/debug/compat" "$out/minitar-$version.tar"
@@ -0,0 +1,70 @@
+#!/bin/sh
+# Regenerate the third-party archives in this directory. Requires GNU tar, bsdtar and python3.
+set -e
+export LC_ALL=C.UTF-8
+
+out=$(cd "$(dirname "$0")" && pwd)
+src=$(mktemp -d)
+trap 'rm -rf "$src"' EXIT
+cd "$src"
+
+long=$(printf 'd%.0s' $(seq 1 60))/$(printf 'n%.0s' $(seq 1 60)).txt
+mkdir -p "$(dirname "$long")" dir
+printf 'hello\n' > "$long"
+printf 'hello\n' > plain.txt
+printf 'unicode\n' > 'ünïcødé-文件.txt'
+ln -s plain.txt dir/link
+truncate -s 1M sparse.img
+printf 'head' | dd of=sparse.img conv=notrunc 2>/dev/null
+printf 'tail' | dd of=sparse.img bs=1 seek=1048572 conv=notrunc 2>/dev/null
+truncate -s 9G large.img
+printf 'large' | dd of=large.img bs=1 seek=8589934592 conv=notrunc 2>/dev/null
+touch -h -d '2021-02-20 19:37:00 UTC' ./* dir/* "$long" "$(dirname "$long")" dir
+
+opts="--owner=0 --group=0 --numeric-owner --mtime=2021-02-20T19:37:00Z --sort=name"
+tar $opts --format=gnu -cf "$out/gnu-longname.tar" plain.txt "$long" dir
+tar $opts --format=gnu --sparse -cf "$out/gnu-sparse.tar" sparse.img
+tar $opts --format=posix --pax-option=delete=atime,delete=ctime --sparse -cf "$out/gnu-pax-sparse.tar" sparse.img
+tar $opts --format=posix --pax-option=delete=atime,delete=ctime --sparse -cf "$out/gnu-large.tar" large.img
+tar $opts --format=posix --pax-option=delete=atime,delete=ctime -cf "$out/gnu-unicode.tar" 'ünïcødé-文件.txt'
+bsdtar --format=ustar --uid 0 --gid 0 -cf "$out/bsdtar-ustar.tar" plain.txt "$long" dir
+bsdtar --format=pax --uid 0 --gid 0 -cf "$out/bsdtar-pax.tar" plain.txt "$long" 'ünïcødé-文件.txt'
+
+python3 - "$out" "$long" <<'PY'
+import sys, tarfile
+out, long = sys.argv[1], sys.argv[2]
+for name, fmt in (("python-pax.tar", tarfile.PAX_FORMAT), ("python-gnu.tar", tarfile.GNU_FORMAT)):
+    with tarfile.open(f"{out}/{name}", "w", format=fmt) as tar:
+        for path in ("plain.txt", long, "ünïcødé-文件.txt", "dir/link"):
+            info = tar.gettarinfo(path)
+            info.uid = info.gid = 0
+            info.uname = info.gname = "root"
+            info.mtime = 1613849820
+            if info.isreg():
+                with open(path, "rb") as f:
+                    tar.addfile(info, f)
+            else:
+                tar.addfile(info)
+PY
+
+# Archives with one numeric header field left empty, as some archivers do. Alternate fields are blanked with
+# NULs and with spaces, and the checksum is recomputed.
+python3 - "$out" <<'PY'
+import io, sys, tarfile
+out = sys.argv[1]
+fields = {"mode": (100, 8, b"\0"), "uid": (108, 8, b" "), "gid": (116, 8, b"\0"),
+          "size": (124, 12, b" "), "mtime": (136, 12, b"\0")}
+for field, (offset, length, fill) in fields.items():
+    info = tarfile.TarInfo("blank.txt")
+    data = b"" if field == "size" else b"hello\n"
+    info.size, info.mtime, info.mode = len(data), 1613849820, 0o644
+    buf = io.BytesIO()
+    with tarfile.open(fileobj=buf, mode="w", format=tarfile.USTAR_FORMAT) as tar:
+        tar.addfile(info, io.BytesIO(data))
+    archive = bytearray(buf.getvalue())
+    archive[offset:offset + length] = fill * length
+    archive[148:156] = b" " * 8
+    archive[148:156] = b"%06o\0 " % sum(archive[:512])
+    with open(f"{out}/blank-{field}.tar", "wb") as f:
+        f.write(archive)
+PY